@@ -1,15 +1,11 @@
-import type { Layer, WasmAstar, WasmModuleAstar } from '../types';
+import type { AstarGame, Layer, WasmAstar, WasmModuleAstar } from '../types';
 import { loadWasmModule, validateWasmModule } from '../wasm/loader';
 import { WasmLoadError, WasmInitError } from '../wasm/types';
 
 // Lazy WASM import - only load when init() is called
 let wasmModuleExports: {
   default: () => Promise<unknown>;
-  wasm_init: (debug: number, renderIntervalMs: number, windowWidth: number, windowHeight: number) => void;
-  tick: (elapsedTime: number) => void;
-  key_down: (keyCode: number) => void;
-  key_up: (keyCode: number) => void;
-  mouse_move: (x: number, y: number) => void;
+  AstarGame: new (gameId: number) => AstarGame;
 } | null = null;
 
 const getInitWasm = async (): Promise<unknown> => {
@@ -18,11 +14,7 @@ const getInitWasm = async (): Promise<unknown> => {
     const module = await import('../../pkg/wasm_astar/wasm_astar.js');
     wasmModuleExports = {
       default: module.default,
-      wasm_init: module.wasm_init,
-      tick: module.tick,
-      key_down: module.key_down,
-      key_up: module.key_up,
-      mouse_move: module.mouse_move,
+      AstarGame: module.AstarGame,
     };
   }
   if (!wasmModuleExports) {
@@ -46,6 +38,187 @@ const WASM_ASTAR: WasmAstar = {
   renderIntervalMs: 1000,
   layers: new Map(),
   layerWrapperEl: null,
+  games: new Map(),
+  wrappers: new Map(),
+};
+
+// The page runs a single game, drawing into layer_wrapper
+const MAIN_GAME_ID = 0;
+
+// Opcodes of js_draw_batch
+const DRAW_OP_CLEAR_SCREEN = 0;
+const DRAW_OP_CLEAR_RECT = 1;
+const DRAW_OP_TILE = 2;
+const DRAW_OP_CIRCLE = 3;
+const DRAW_OP_POLYLINE = 4;
+const DRAW_OP_POLYGON = 5;
+
+const getMainGame = (): AstarGame | undefined => WASM_ASTAR.games.get(MAIN_GAME_ID);
+
+// The first connected gamepad's left stick steers the main game. Gamepads
+// have no events for stick movement, so they are polled every tick.
+let gamepadSeen = false;
+
+const pollGamepad = (game: AstarGame): void => {
+  const gamepad = navigator.getGamepads().find((pad) => pad !== null);
+  if (!gamepad || gamepad.axes.length < 2) {
+    // Centre the stick once when the pad goes away, or a stick held while
+    // it disconnected keeps the player walking
+    if (gamepadSeen) {
+      game.set_gamepad_axis(0, 0);
+      game.set_gamepad_axis(1, 0);
+      gamepadSeen = false;
+    }
+    return;
+  }
+  gamepadSeen = true;
+  game.set_gamepad_axis(0, gamepad.axes[0]);
+  game.set_gamepad_axis(1, gamepad.axes[1]);
+};
+
+const getControl = <T extends HTMLElement>(id: string, type: new () => T): T => {
+  const element = document.getElementById(id);
+  if (!(element instanceof type)) {
+    throw new Error(`${id} control not found`);
+  }
+  return element;
+};
+
+// Keys typed into the controls shouldn't move the player or regenerate the map
+const isControlEvent = (e: Event): boolean =>
+  e.target instanceof HTMLInputElement
+  || e.target instanceof HTMLSelectElement
+  || e.target instanceof HTMLTextAreaElement;
+
+// Hooks the #controls panel up to the main game. onMapResized runs after an
+// import, which can change the canvas size.
+const setupControls = (onMapResized: () => void): void => {
+  const withGame = (action: (game: AstarGame) => void): void => {
+    const game = getMainGame();
+    if (game) {
+      action(game);
+    }
+  };
+  const onToggle = (id: string, action: (game: AstarGame, enabled: boolean) => void): void => {
+    const toggle = getControl(id, HTMLInputElement);
+    toggle.addEventListener('change', () => withGame((game) => action(game, toggle.checked)));
+  };
+  const onSelect = (id: string, action: (game: AstarGame, value: number) => void): void => {
+    const select = getControl(id, HTMLSelectElement);
+    select.addEventListener('change', () => withGame((game) => action(game, Number(select.value))));
+  };
+
+  onToggle('diagonalToggle', (game, enabled) => game.set_diagonal_movement(enabled));
+  onSelect('algorithmSelect', (game, id) => game.set_search_algorithm(id));
+
+  // Pause and step only mean something while stepping through the search
+  const pauseButton = getControl('pauseSearchBtn', HTMLButtonElement);
+  const stepButton = getControl('stepSearchBtn', HTMLButtonElement);
+  let searchPaused = false;
+  onToggle('stepModeToggle', (game, enabled) => {
+    game.set_step_mode(enabled, 1);
+    if (searchPaused) {
+      game.resume_search();
+      searchPaused = false;
+      pauseButton.textContent = 'Pause';
+    }
+    pauseButton.disabled = !enabled;
+    stepButton.disabled = !enabled;
+  });
+  pauseButton.addEventListener('click', () => withGame((game) => {
+    if (searchPaused) {
+      game.resume_search();
+    } else {
+      game.pause_search();
+    }
+    searchPaused = !searchPaused;
+    pauseButton.textContent = searchPaused ? 'Resume' : 'Pause';
+  }));
+  stepButton.addEventListener('click', () => withGame((game) => game.step_search(1)));
+
+  const seedInput = getControl('seedInput', HTMLInputElement);
+  seedInput.addEventListener('change', () => {
+    const seed = Number(seedInput.value);
+    if (Number.isInteger(seed) && seed >= 0) {
+      withGame((game) => game.set_seed(seed));
+    }
+  });
+
+  onSelect('generatorSelect', (game, id) => game.set_map_generator(id));
+
+  const formatSelect = getControl('mapFormatSelect', HTMLSelectElement);
+  const mapText = getControl('mapText', HTMLTextAreaElement);
+  const errorEl = document.getElementById('error');
+  const showError = (error: unknown): void => {
+    if (errorEl) {
+      // import_map and export_map throw their error messages as strings
+      errorEl.textContent = typeof error === 'string' ? error : error instanceof Error ? error.message : 'Unknown error';
+    }
+  };
+  getControl('exportMapBtn', HTMLButtonElement).addEventListener('click', () => withGame((game) => {
+    try {
+      mapText.value = game.export_map(formatSelect.value);
+    } catch (error) {
+      showError(error);
+    }
+  }));
+  getControl('importMapBtn', HTMLButtonElement).addEventListener('click', () => withGame((game) => {
+    try {
+      game.import_map(mapText.value, formatSelect.value);
+      if (errorEl) {
+        errorEl.textContent = '';
+      }
+      onMapResized();
+    } catch (error) {
+      showError(error);
+    }
+  }));
+
+  // Anything above MAX_AGENTS (the input's max) is capped by the game
+  const agentCountInput = getControl('agentCountInput', HTMLInputElement);
+  agentCountInput.addEventListener('change', () => {
+    const count = Math.max(0, Math.floor(Number(agentCountInput.value) || 0));
+    withGame((game) => game.set_agent_count(count));
+  });
+
+  onToggle('smoothingToggle', (game, enabled) => game.set_path_smoothing(enabled));
+
+  onSelect('topologySelect', (game, id) => game.set_topology(id));
+
+  onToggle('flowFieldToggle', (game, enabled) => game.set_flow_field(enabled));
+
+  onToggle('fogToggle', (game, enabled) => game.set_fog_of_war(enabled));
+
+  // Spacebar picks a new seed, so what the panel shows is refreshed every
+  // renderIntervalMs
+  const frameStatsEl = getControl('frameStats', HTMLDivElement);
+  const refreshControls = (): void => withGame((game) => {
+    const [minMs, avgMs, p95Ms, searchMs] = game.get_frame_stats();
+    frameStatsEl.textContent = `frame min/avg/p95: ${minMs.toFixed(1)}/${avgMs.toFixed(1)}/${p95Ms.toFixed(1)} ms, `
+      + `search: ${searchMs.toFixed(2)} ms, expanded: ${game.get_expanded_nodes()}`;
+    if (document.activeElement !== seedInput) {
+      seedInput.value = String(game.get_seed());
+    }
+  });
+  let lastRefreshTime = -Infinity;
+  const pollControls = (time: number): void => {
+    if (time - lastRefreshTime >= WASM_ASTAR.renderIntervalMs) {
+      lastRefreshTime = time;
+      refreshControls();
+    }
+    requestAnimationFrame(pollControls);
+  };
+  requestAnimationFrame(pollControls);
+};
+
+const tickGame = (gameId: number): void => {
+  const game = WASM_ASTAR.games.get(gameId);
+  if (game) {
+    if (gameId === MAIN_GAME_ID) {
+      pollGamepad(game);
+    }
+    game.tick(performance.now());
+  }
 };
 
 function validateAstarModule(exports: unknown): WasmModuleAstar | null {
@@ -78,20 +251,8 @@ function validateAstarModule(exports: unknown): WasmModuleAstar | null {
   if (!wasmModuleExports) {
     missingExports.push('module exports (wasmModuleExports is null)');
   } else {
-    if (typeof wasmModuleExports.wasm_init !== 'function') {
-      missingExports.push('wasm_init (function)');
-    }
-    if (typeof wasmModuleExports.tick !== 'function') {
-      missingExports.push('tick (function)');
-    }
-    if (typeof wasmModuleExports.key_down !== 'function') {
-      missingExports.push('key_down (function)');
-    }
-    if (typeof wasmModuleExports.key_up !== 'function') {
-      missingExports.push('key_up (function)');
-    }
-    if (typeof wasmModuleExports.mouse_move !== 'function') {
-      missingExports.push('mouse_move (function)');
+    if (typeof wasmModuleExports.AstarGame !== 'function') {
+      missingExports.push('AstarGame (class)');
     }
   }
   
@@ -113,11 +274,7 @@ function validateAstarModule(exports: unknown): WasmModuleAstar | null {
   
   return {
     memory,
-    wasm_init: wasmModuleExports.wasm_init,
-    tick: wasmModuleExports.tick,
-    key_down: wasmModuleExports.key_down,
-    key_up: wasmModuleExports.key_up,
-    mouse_move: wasmModuleExports.mouse_move,
+    AstarGame: wasmModuleExports.AstarGame,
   };
 }
 
@@ -127,6 +284,7 @@ export const init = async (): Promise<void> => {
   
   // Get layer wrapper element (lazy initialization - only when init is called)
   WASM_ASTAR.layerWrapperEl = getLayerWrapper();
+  WASM_ASTAR.wrappers.set(MAIN_GAME_ID, WASM_ASTAR.layerWrapperEl);
   
   const { debug, renderIntervalMs } = WASM_ASTAR;
   
@@ -135,20 +293,18 @@ export const init = async (): Promise<void> => {
   
   // Make functions available globally for wasm-bindgen
   const globalObj: { [key: string]: unknown } = globalThis;
-  globalObj.js_random = (): number => wasmImports.js_random();
   globalObj.js_random_range = (min: number, max: number): number => wasmImports.js_random_range(min, max);
+  globalObj.js_now = (): number => wasmImports.js_now();
   globalObj.js_log = (): void => wasmImports.js_log();
-  globalObj.js_request_tick = (): void => wasmImports.js_request_tick();
-  globalObj.js_start_interval_tick = (ms: number): void => wasmImports.js_start_interval_tick(ms);
-  globalObj.js_create_layer = (id: string, key: number): void => wasmImports.js_create_layer(id, key);
-  globalObj.js_set_screen_size = (width: number, height: number, quality: number): void => wasmImports.js_set_screen_size(width, height, quality);
+  globalObj.js_request_tick = (gameId: number): void => wasmImports.js_request_tick(gameId);
+  globalObj.js_start_interval_tick = (gameId: number, ms: number): void => wasmImports.js_start_interval_tick(gameId, ms);
+  globalObj.js_create_layer = (gameId: number, id: string, key: number): void => wasmImports.js_create_layer(gameId, id, key);
+  globalObj.js_set_screen_size = (gameId: number, width: number, height: number, quality: number): void => wasmImports.js_set_screen_size(gameId, width, height, quality);
   globalObj.js_set_layer_size = (layerId: number, width: number, height: number, quality: number): void => wasmImports.js_set_layer_size(layerId, width, height, quality);
-  globalObj.js_clear_screen = (layerId: number): void => wasmImports.js_clear_screen(layerId);
   globalObj.js_update = (): void => wasmImports.js_update();
-  globalObj.js_draw_tile = (layerId: number, px: number, py: number, size: number, ch: number, cs: number, cl: number, ca: number): void => wasmImports.js_draw_tile(layerId, px, py, size, ch, cs, cl, ca);
-  globalObj.js_draw_circle = (layerId: number, px: number, py: number, r: number, ch: number, cs: number, cl: number, ca: number): void => wasmImports.js_draw_circle(layerId, px, py, r, ch, cs, cl, ca);
-  globalObj.js_draw_fps = (layerId: number, fps: number): void => wasmImports.js_draw_fps(layerId, fps);
-  globalObj.js_path_count = (layerId: number, count: number): void => wasmImports.js_path_count(layerId, count);
+  globalObj.js_draw_batch = (commands: Float64Array): void => wasmImports.js_draw_batch(commands);
+  globalObj.js_draw_fps = (layerId: number, fps: number, minMs: number, avgMs: number, p95Ms: number, searchMs: number): void => wasmImports.js_draw_fps(layerId, fps, minMs, avgMs, p95Ms, searchMs);
+  globalObj.js_search_stats = (layerId: number, pathCount: number, pathCost: number, expandedNodes: number): void => wasmImports.js_search_stats(layerId, pathCount, pathCost, expandedNodes);
   
   // Initialize WASM module using loadWasmModule helper
   try {
@@ -163,7 +319,9 @@ export const init = async (): Promise<void> => {
     
     WASM_ASTAR.wasmModule = wasmModule;
     
-    wasmModule.wasm_init(
+    const game = new wasmModule.AstarGame(MAIN_GAME_ID);
+    WASM_ASTAR.games.set(MAIN_GAME_ID, game);
+    game.init(
       debug ? 1 : 0,
       renderIntervalMs,
       window.innerWidth,
@@ -225,76 +383,99 @@ export const init = async (): Promise<void> => {
     }
     const x = e.clientX - rect.left;
     const y = e.clientY - rect.top;
-    if (WASM_ASTAR.wasmModule) {
-      WASM_ASTAR.wasmModule.mouse_move(x, y);
+    const game = getMainGame();
+    if (game) {
+      game.mouse_move(x, y);
+    }
+  });
+  
+  // Painting starts on the canvas but may end anywhere, so mouseup is
+  // listened for on the window.
+  layerWrapperEl.addEventListener('mousedown', (e: MouseEvent) => {
+    if (!cachedRect) {
+      updateCachedRect();
+    }
+    const rect = cachedRect;
+    const game = getMainGame();
+    if (!rect || !game) {
+      return;
+    }
+    e.preventDefault();
+    game.mouse_down(e.clientX - rect.left, e.clientY - rect.top, e.button);
+  });
+  
+  layerWrapperEl.addEventListener('contextmenu', (e: MouseEvent) => {
+    e.preventDefault();
+  });
+  
+  window.addEventListener('mouseup', () => {
+    const game = getMainGame();
+    if (game) {
+      game.mouse_up();
     }
   });
   
   window.addEventListener('keydown', (e: KeyboardEvent) => {
-    if (WASM_ASTAR.wasmModule) {
-      WASM_ASTAR.wasmModule.key_down(e.keyCode);
+    const game = getMainGame();
+    if (game && !isControlEvent(e)) {
+      game.key_down(e.keyCode);
     }
   });
   
   window.addEventListener('keyup', (e: KeyboardEvent) => {
-    if (WASM_ASTAR.wasmModule) {
-      WASM_ASTAR.wasmModule.key_up(e.keyCode);
+    const game = getMainGame();
+    if (game && !isControlEvent(e)) {
+      game.key_up(e.keyCode);
     }
   });
   
   layerWrapperEl.addEventListener('touchend', () => {
     // Simulating spacebar for mobile support
-    if (WASM_ASTAR.wasmModule) {
-      WASM_ASTAR.wasmModule.key_down(32);
+    const game = getMainGame();
+    if (game) {
+      game.key_down(32);
       requestAnimationFrame(() => {
-        if (WASM_ASTAR.wasmModule) {
-          WASM_ASTAR.wasmModule.key_up(32);
-        }
+        game.key_up(32);
       });
     }
   });
+
+  setupControls(updateCachedRect);
 };
 
 const getWasmImports = () => {
-  let isIntervalTick = false;
+  // Games ticked on an interval ignore their own tick requests
+  const intervalTickGames = new Set<number>();
 
   return {
-    js_random(): number {
-      return Math.random();
-    },
-
     js_random_range(min: number, max: number): number {
       return Math.floor(Math.random() * (max + 1 - min)) + min;
     },
 
+    js_now(): number {
+      return performance.now();
+    },
+
     js_log(): void {
       // Logging disabled per code requirements
     },
 
-    js_request_tick(): void {
-      if (isIntervalTick) return;
-      requestAnimationFrame(() => {
-        if (WASM_ASTAR.wasmModule) {
-          WASM_ASTAR.wasmModule.tick(performance.now());
-        }
-      });
+    js_request_tick(gameId: number): void {
+      if (intervalTickGames.has(gameId)) return;
+      requestAnimationFrame(() => tickGame(gameId));
     },
 
-    js_start_interval_tick(ms: number): void {
-      isIntervalTick = true;
-      requestAnimationFrame(() => {
-        if (WASM_ASTAR.wasmModule) {
-          WASM_ASTAR.wasmModule.tick(performance.now());
-        }
-      });
+    js_start_interval_tick(gameId: number, ms: number): void {
+      intervalTickGames.add(gameId);
+      requestAnimationFrame(() => tickGame(gameId));
       const scheduleNext = (): void => {
-        if (WASM_ASTAR.wasmModule && isIntervalTick) {
+        if (intervalTickGames.has(gameId)) {
           const startTime = performance.now();
           requestAnimationFrame(() => {
-            if (WASM_ASTAR.wasmModule && isIntervalTick) {
+            if (intervalTickGames.has(gameId)) {
               const elapsed = performance.now() - startTime;
               if (elapsed >= ms) {
-                WASM_ASTAR.wasmModule.tick(performance.now());
+                tickGame(gameId);
                 scheduleNext();
               } else {
                 scheduleNext();
@@ -306,13 +487,13 @@ const getWasmImports = () => {
       scheduleNext();
     },
 
-        js_create_layer(id: string, key: number): void {
-          const wrapperEl = WASM_ASTAR.layerWrapperEl;
-          if (!wrapperEl) {
-            throw new Error('layer_wrapper element not found');
-          }
-          const canvasElement = document.createElement('canvas');
-          const canvas = wrapperEl.appendChild(canvasElement);
+    js_create_layer(gameId: number, id: string, key: number): void {
+      const wrapperEl = WASM_ASTAR.wrappers.get(gameId);
+      if (!wrapperEl) {
+        throw new Error(`No layer wrapper for game ${gameId}`);
+      }
+      const canvasElement = document.createElement('canvas');
+      const canvas = wrapperEl.appendChild(canvasElement);
       if (!(canvas instanceof HTMLCanvasElement)) {
         throw new Error('Failed to create canvas element');
       }
@@ -334,6 +515,9 @@ const getWasmImports = () => {
         clearScreen(): void {
           ctx.clearRect(0, 0, canvas.width, canvas.height);
         },
+        clearRect(px: number, py: number, sx: number, sy: number): void {
+          ctx.clearRect(px, py, sx, sy);
+        },
         drawRect(px: number, py: number, sx: number, sy: number, ch: number, cs: number, cl: number, ca: number): void {
           ctx.fillStyle = `hsla(${ch}, ${cs}%, ${cl}%, ${ca})`;
           ctx.fillRect(px, py, sx, sy);
@@ -345,6 +529,34 @@ const getWasmImports = () => {
           ctx.closePath();
           ctx.fill();
         },
+        drawPolyline(points: Float64Array, width: number, ch: number, cs: number, cl: number, ca: number): void {
+          if (points.length < 4) {
+            return;
+          }
+          ctx.strokeStyle = `hsla(${ch}, ${cs}%, ${cl}%, ${ca})`;
+          ctx.lineWidth = width;
+          ctx.lineCap = 'round';
+          ctx.lineJoin = 'round';
+          ctx.beginPath();
+          ctx.moveTo(points[0], points[1]);
+          for (let i = 2; i + 1 < points.length; i += 2) {
+            ctx.lineTo(points[i], points[i + 1]);
+          }
+          ctx.stroke();
+        },
+        drawPolygon(points: Float64Array, ch: number, cs: number, cl: number, ca: number): void {
+          if (points.length < 6) {
+            return;
+          }
+          ctx.fillStyle = `hsla(${ch}, ${cs}%, ${cl}%, ${ca})`;
+          ctx.beginPath();
+          ctx.moveTo(points[0], points[1]);
+          for (let i = 2; i + 1 < points.length; i += 2) {
+            ctx.lineTo(points[i], points[i + 1]);
+          }
+          ctx.closePath();
+          ctx.fill();
+        },
         drawText(text: string, fontSize: number, px: number, py: number): void {
           ctx.fillStyle = '#fff';
           ctx.font = `${fontSize}px Monaco, Consolas, Courier, monospace`;
@@ -355,8 +567,8 @@ const getWasmImports = () => {
       WASM_ASTAR.layers.set(key, layer);
     },
 
-    js_set_screen_size(width: number, height: number, quality: number): void {
-      const wrapper = WASM_ASTAR.layerWrapperEl;
+    js_set_screen_size(gameId: number, width: number, height: number, quality: number): void {
+      const wrapper = WASM_ASTAR.wrappers.get(gameId);
       if (wrapper) {
         wrapper.style.width = `${width / quality}px`;
         wrapper.style.height = `${height / quality}px`;
@@ -370,42 +582,75 @@ const getWasmImports = () => {
       }
     },
 
-    js_clear_screen(layerId: number): void {
-      const layer = WASM_ASTAR.layers.get(layerId);
-      if (layer) {
-        layer.clearScreen();
-      }
-    },
-
     js_update(): void {
       // For minimal necessary client updates
     },
 
-    js_draw_tile(layerId: number, px: number, py: number, size: number, ch: number, cs: number, cl: number, ca: number): void {
-      const layer = WASM_ASTAR.layers.get(layerId);
-      if (layer) {
-        layer.drawRect(px, py, size, size, ch, cs, cl, ca);
-      }
-    },
-
-    js_draw_circle(layerId: number, px: number, py: number, r: number, ch: number, cs: number, cl: number, ca: number): void {
-      const layer = WASM_ASTAR.layers.get(layerId);
-      if (layer) {
-        layer.drawCircle(px, py, r, ch, cs, cl, ca);
+    // A frame's clears and shapes in one buffer, see the opcodes in
+    // wasm-astar/src/browser/mod.rs. Every command is its opcode, the layer
+    // id and its arguments, colours as h, s, l, a.
+    js_draw_batch(commands: Float64Array): void {
+      let i = 0;
+      while (i < commands.length) {
+        const op = commands[i];
+        const layer = WASM_ASTAR.layers.get(commands[i + 1]);
+        i += 2;
+        switch (op) {
+          case DRAW_OP_CLEAR_SCREEN:
+            layer?.clearScreen();
+            break;
+          case DRAW_OP_CLEAR_RECT:
+            layer?.clearRect(commands[i], commands[i + 1], commands[i + 2], commands[i + 3]);
+            i += 4;
+            break;
+          case DRAW_OP_TILE: {
+            const size = commands[i + 2];
+            layer?.drawRect(commands[i], commands[i + 1], size, size, commands[i + 3], commands[i + 4], commands[i + 5], commands[i + 6]);
+            i += 7;
+            break;
+          }
+          case DRAW_OP_CIRCLE:
+            layer?.drawCircle(commands[i], commands[i + 1], commands[i + 2], commands[i + 3], commands[i + 4], commands[i + 5], commands[i + 6]);
+            i += 7;
+            break;
+          case DRAW_OP_POLYLINE: {
+            const numPoints = commands[i + 5];
+            const points = commands.subarray(i + 6, i + 6 + numPoints * 2);
+            layer?.drawPolyline(points, commands[i], commands[i + 1], commands[i + 2], commands[i + 3], commands[i + 4]);
+            i += 6 + numPoints * 2;
+            break;
+          }
+          case DRAW_OP_POLYGON: {
+            const numPoints = commands[i + 4];
+            const points = commands.subarray(i + 5, i + 5 + numPoints * 2);
+            layer?.drawPolygon(points, commands[i], commands[i + 1], commands[i + 2], commands[i + 3]);
+            i += 5 + numPoints * 2;
+            break;
+          }
+          default:
+            // Unknown opcode, the rest of the batch can't be decoded
+            return;
+        }
       }
     },
 
-    js_draw_fps(layerId: number, fps: number): void {
+    js_draw_fps(layerId: number, fps: number, minMs: number, avgMs: number, p95Ms: number, searchMs: number): void {
       const layer = WASM_ASTAR.layers.get(layerId);
       if (layer) {
         layer.drawText(`fps: ${Math.round(fps)}`, 35, 5, 45);
+        layer.drawText(`min/avg: ${minMs.toFixed(1)}/${avgMs.toFixed(1)} ms`, 25, 5, 90);
+        layer.drawText(`p95: ${p95Ms.toFixed(1)} ms`, 25, 5, 130);
+        layer.drawText(`search: ${searchMs.toFixed(2)} ms`, 25, 5, 170);
       }
     },
 
-    js_path_count(layerId: number, count: number): void {
+    js_search_stats(layerId: number, pathCount: number, pathCost: number, expandedNodes: number): void {
       const layer = WASM_ASTAR.layers.get(layerId);
       if (layer) {
-        layer.drawText(`path: ${count}`, 35, 5, 95);
+        // Below the frame stats, which share the top left corner
+        layer.drawText(`path: ${pathCount}`, 35, 5, 230);
+        layer.drawText(`cost: ${pathCost}`, 35, 5, 280);
+        layer.drawText(`expanded: ${expandedNodes}`, 35, 5, 330);
       }
     },
   };
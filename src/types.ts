@@ -1,13 +1,48 @@
 // Type definitions for WASM modules
 
 // A* Pathfinding module types
-export interface WasmModuleAstar {
-  memory: WebAssembly.Memory;
-  wasm_init(debug: number, renderIntervalMs: number, windowWidth: number, windowHeight: number): void;
+// One simulation, see AstarGame in wasm-astar/src/lib.rs
+export interface AstarGame {
+  init(debug: number, renderIntervalMs: number, windowWidth: number, windowHeight: number): void;
   tick(elapsedTime: number): void;
   key_down(keyCode: number): void;
   key_up(keyCode: number): void;
+  bind_key(actionId: number, keyCode: number): void;
+  unbind_key(keyCode: number): void;
+  set_gamepad_axis(axis: number, value: number): void;
   mouse_move(x: number, y: number): void;
+  mouse_down(x: number, y: number, button: number): void;
+  mouse_up(): void;
+  set_seed(seed: number): void;
+  get_seed(): number;
+  set_map_generator(generatorId: number): void;
+  set_topology(topologyId: number): void;
+  // format is "csv" or "json", both throw on anything else or bad data
+  import_map(data: string, format: string): void;
+  export_map(format: string): string;
+  set_agent_count(count: number): void;
+  set_diagonal_movement(enabled: boolean): void;
+  set_search_algorithm(algorithmId: number): void;
+  set_flow_field(enabled: boolean): void;
+  // [x, y] unit vector towards the goal at a canvas position
+  sample_flow_field(x: number, y: number): Float64Array;
+  set_fog_of_war(enabled: boolean): void;
+  // One byte per tile: 0 never seen, 1 seen before, 2 in view
+  get_visibility_mask(): Uint8Array;
+  set_path_smoothing(enabled: boolean): void;
+  set_step_mode(enabled: boolean, stepsPerFrame: number): void;
+  pause_search(): void;
+  resume_search(): void;
+  step_search(count: number): void;
+  get_expanded_nodes(): number;
+  // [min, average, p95, search] in milliseconds
+  get_frame_stats(): Float64Array;
+  free(): void;
+}
+
+export interface WasmModuleAstar {
+  memory: WebAssembly.Memory;
+  AstarGame: new (gameId: number) => AstarGame;
 }
 
 export interface Layer {
@@ -15,8 +50,11 @@ export interface Layer {
   canvas: HTMLCanvasElement;
   setSize(width: number, height: number, quality: number): void;
   clearScreen(): void;
+  clearRect(px: number, py: number, sx: number, sy: number): void;
   drawRect(px: number, py: number, sx: number, sy: number, ch: number, cs: number, cl: number, ca: number): void;
   drawCircle(px: number, py: number, r: number, ch: number, cs: number, cl: number, ca: number): void;
+  drawPolyline(points: Float64Array, width: number, ch: number, cs: number, cl: number, ca: number): void;
+  drawPolygon(points: Float64Array, ch: number, cs: number, cl: number, ca: number): void;
   drawText(text: string, fontSize: number, px: number, py: number): void;
 }
 
@@ -27,6 +65,9 @@ export interface WasmAstar {
   renderIntervalMs: number;
   layers: Map<number, Layer>;
   layerWrapperEl: HTMLElement | null;
+  // Keyed by game id, game 0 draws into layerWrapperEl
+  games: Map<number, AstarGame>;
+  wrappers: Map<number, HTMLElement>;
 }
 
 // Preprocessing module types
@@ -1,46 +1,223 @@
 use wasm_bindgen::prelude::*;
 
+use crate::engine::{Color, FrameSummary};
+use crate::platform::{Layer, Platform, NUM_LAYERS};
+
 #[wasm_bindgen]
 extern "C" {
     #[wasm_bindgen(js_name = "js_create_layer")]
-    fn js_create_layer(id: &str, key: i32);
-    
-    #[wasm_bindgen(js_name = "js_clear_screen")]
-    fn js_clear_screen(layer_id: i32);
-    
+    fn js_create_layer(game_id: u32, id: &str, key: i32);
+
     #[wasm_bindgen(js_name = "js_set_screen_size")]
-    fn js_set_screen_size(width: i32, height: i32, quality: i32);
-    
+    fn js_set_screen_size(game_id: u32, width: i32, height: i32, quality: i32);
+
     #[wasm_bindgen(js_name = "js_set_layer_size")]
     fn js_set_layer_size(layer_id: i32, width: i32, height: i32, quality: i32);
-    
+
     #[wasm_bindgen(js_name = "js_request_tick")]
-    fn js_request_tick();
-    
+    fn js_request_tick(game_id: u32);
+
     #[wasm_bindgen(js_name = "js_start_interval_tick")]
-    fn js_start_interval_tick(ms: i32);
-}
+    fn js_start_interval_tick(game_id: u32, ms: i32);
 
-pub fn create_layer(id: &str, key: i32) {
-    js_create_layer(id, key);
-}
+    // Draws every command batched since the last call, see JsPlatform.
+    #[wasm_bindgen(js_name = "js_draw_batch")]
+    fn js_draw_batch(commands: &[f64]);
 
-pub fn clear_screen(layer: i32) {
-    js_clear_screen(layer);
-}
+    #[wasm_bindgen(js_name = "js_update")]
+    fn js_update();
+
+    #[wasm_bindgen(js_name = "js_draw_fps")]
+    fn js_draw_fps(
+        layer_id: i32,
+        fps: f64,
+        min_ms: f64,
+        avg_ms: f64,
+        p95_ms: f64,
+        search_ms: f64,
+    );
 
-pub fn set_layer_size(layer: i32, width: u32, height: u32, quality: u32) {
-    js_set_layer_size(layer, width as i32, height as i32, quality as i32);
+    #[wasm_bindgen(js_name = "js_search_stats")]
+    fn js_search_stats(layer_id: i32, path_count: i32, path_cost: i32, expanded_nodes: u32);
+
+    // performance.now()
+    #[wasm_bindgen(js_name = "js_now")]
+    fn js_now() -> f64;
+
+    #[wasm_bindgen(js_name = "js_random_range")]
+    fn js_random_range(min: i32, max: i32) -> i32;
+
+    #[wasm_bindgen(js_name = "js_log")]
+    fn js_log(msg: &str);
 }
 
-pub fn set_screen_size(width: u32, height: u32, quality: u32) {
-    js_set_screen_size(width as i32, height as i32, quality as i32);
+// Opcodes in the draw batch. A command is its opcode, the layer id and
+// then its arguments, colours as h, s, l and a.
+const OP_CLEAR_SCREEN: f64 = 0_f64;
+// x, y, width, height
+const OP_CLEAR_RECT: f64 = 1_f64;
+// px, py, size, colour
+const OP_TILE: f64 = 2_f64;
+// px, py, radius, colour
+const OP_CIRCLE: f64 = 3_f64;
+// width, colour, number of points, then x and y of every point
+const OP_POLYLINE: f64 = 4_f64;
+// colour, number of points, then x and y of every point
+const OP_POLYGON: f64 = 5_f64;
+
+// The real platform, forwarding to the JS imports. Clearing and shapes go
+// into one buffer that js_draw_batch draws in a single call on present, so
+// a frame costs one crossing into JS however much it draws. Everything
+// else is sent right away, after the batch so far.
+// Each game gets its own block of NUM_LAYERS layer ids, game 0 uses the
+// Layer values themselves, game 1 the next NUM_LAYERS ids and so on.
+pub struct JsPlatform {
+    game_id: u32,
+    batch: Vec<f64>,
 }
 
-pub fn request_next_tick() {
-    js_request_tick();
+impl JsPlatform {
+    pub fn new(game_id: u32) -> JsPlatform {
+        JsPlatform {
+            game_id,
+            batch: Vec::new(),
+        }
+    }
+
+    fn layer_id(&self, layer: Layer) -> i32 {
+        (self.game_id * NUM_LAYERS) as i32 + layer as i32
+    }
+
+    fn push_command(&mut self, op: f64, layer: Layer, args: &[f64]) {
+        let layer_id = self.layer_id(layer) as f64;
+        self.batch.push(op);
+        self.batch.push(layer_id);
+        self.batch.extend_from_slice(args);
+    }
+
+    fn push_color(&mut self, c: &Color) {
+        self.batch
+            .extend_from_slice(&[c.h as f64, c.s as f64, c.l as f64, c.a as f64]);
+    }
+
+    fn flush(&mut self) {
+        if !self.batch.is_empty() {
+            js_draw_batch(&self.batch);
+            self.batch.clear();
+        }
+    }
 }
 
-pub fn start_interval_tick(ms: i32) {
-    js_start_interval_tick(ms);
+impl Platform for JsPlatform {
+    fn create_layer(&mut self, layer: Layer) {
+        // Canvas ids must be unique on the page.
+        let id = if self.game_id == 0 {
+            String::from(layer.name())
+        } else {
+            format!("{}{}", layer.name(), self.game_id)
+        };
+        js_create_layer(self.game_id, &id, self.layer_id(layer));
+    }
+
+    fn clear_screen(&mut self, layer: Layer) {
+        self.push_command(OP_CLEAR_SCREEN, layer, &[]);
+    }
+
+    fn clear_rect(&mut self, layer: Layer, x: f64, y: f64, width: f64, height: f64) {
+        self.push_command(OP_CLEAR_RECT, layer, &[x, y, width, height]);
+    }
+
+    fn set_screen_size(&mut self, width: u32, height: u32, quality: u32) {
+        js_set_screen_size(self.game_id, width as i32, height as i32, quality as i32);
+    }
+
+    fn set_layer_size(&mut self, layer: Layer, width: u32, height: u32, quality: u32) {
+        self.flush();
+        js_set_layer_size(
+            self.layer_id(layer),
+            width as i32,
+            height as i32,
+            quality as i32,
+        );
+    }
+
+    fn request_next_tick(&mut self) {
+        js_request_tick(self.game_id);
+    }
+
+    fn start_interval_tick(&mut self, ms: i32) {
+        js_start_interval_tick(self.game_id, ms);
+    }
+
+    fn draw_tile(&mut self, layer: Layer, px: f64, py: f64, size: f64, c: &Color) {
+        self.push_command(OP_TILE, layer, &[px, py, size]);
+        self.push_color(c);
+    }
+
+    fn draw_circle(&mut self, layer: Layer, px: f64, py: f64, radius: f64, c: &Color) {
+        self.push_command(OP_CIRCLE, layer, &[px, py, radius]);
+        self.push_color(c);
+    }
+
+    fn draw_polyline(&mut self, layer: Layer, points: &[(f64, f64)], width: f64, c: &Color) {
+        self.push_command(OP_POLYLINE, layer, &[width]);
+        self.push_color(c);
+        self.batch.push(points.len() as f64);
+        self.batch.extend(points.iter().flat_map(|&(x, y)| [x, y]));
+    }
+
+    fn draw_polygon(&mut self, layer: Layer, points: &[(f64, f64)], c: &Color) {
+        self.push_command(OP_POLYGON, layer, &[]);
+        self.push_color(c);
+        self.batch.push(points.len() as f64);
+        self.batch.extend(points.iter().flat_map(|&(x, y)| [x, y]));
+    }
+
+    fn draw_fps(&mut self, layer: Layer, fps: f64, stats: &FrameSummary) {
+        self.flush();
+        js_draw_fps(
+            self.layer_id(layer),
+            fps,
+            stats.min_ms,
+            stats.avg_ms,
+            stats.p95_ms,
+            stats.search_ms,
+        );
+    }
+
+    fn draw_search_stats(
+        &mut self,
+        layer: Layer,
+        path_count: i32,
+        path_cost: i32,
+        expanded_nodes: u32,
+    ) {
+        self.flush();
+        js_search_stats(
+            self.layer_id(layer),
+            path_count,
+            path_cost,
+            expanded_nodes,
+        );
+    }
+
+    fn update(&mut self) {
+        js_update();
+    }
+
+    fn present(&mut self) {
+        self.flush();
+    }
+
+    fn now(&mut self) -> f64 {
+        js_now()
+    }
+
+    fn random_seed(&mut self) -> u32 {
+        js_random_range(0, i32::MAX) as u32
+    }
+
+    fn log(&mut self, msg: &str) {
+        js_log(msg);
+    }
 }
@@ -0,0 +1,140 @@
+use std::collections::{HashMap, HashSet};
+
+use super::KeyCode;
+
+// What the player can do. Keys and gamepad sticks are mapped to these, the
+// game only ever asks about actions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    MoveUp = 0,
+    MoveDown = 1,
+    MoveLeft = 2,
+    MoveRight = 3,
+    Regenerate = 4,
+}
+
+pub const NUM_ACTIONS: usize = 5;
+
+impl Action {
+    pub fn from_id(id: i32) -> Option<Action> {
+        match id {
+            0 => Some(Action::MoveUp),
+            1 => Some(Action::MoveDown),
+            2 => Some(Action::MoveLeft),
+            3 => Some(Action::MoveRight),
+            4 => Some(Action::Regenerate),
+            _ => None,
+        }
+    }
+}
+
+// Stick positions closer to the centre than this don't count as a move.
+pub const GAMEPAD_DEAD_ZONE: f64 = 0.5;
+
+// Turns key and gamepad events into per-frame action state. Events can
+// arrive at any time, update() is called once per frame and decides what
+// was pressed or released since the last one.
+pub struct InputState {
+    bindings: HashMap<u32, Action>,
+    keys_down: HashSet<u32>,
+    // Keys pressed since the last frame, so a tap released before the frame
+    // ran still counts as pressed for one frame.
+    keys_pressed: HashSet<u32>,
+    // Left stick, x then y, -1 to 1 like Gamepad.axes.
+    gamepad_axes: [f64; 2],
+    down: [bool; NUM_ACTIONS],
+    was_down: [bool; NUM_ACTIONS],
+}
+
+impl Default for InputState {
+    fn default() -> InputState {
+        InputState::new()
+    }
+}
+
+impl InputState {
+    // Arrow keys and WASD move, space regenerates.
+    pub fn new() -> InputState {
+        let mut input = InputState {
+            bindings: HashMap::new(),
+            keys_down: HashSet::new(),
+            keys_pressed: HashSet::new(),
+            gamepad_axes: [0_f64; 2],
+            down: [false; NUM_ACTIONS],
+            was_down: [false; NUM_ACTIONS],
+        };
+        let defaults = [
+            (KeyCode::ArrowUp, Action::MoveUp),
+            (KeyCode::KeyW, Action::MoveUp),
+            (KeyCode::ArrowDown, Action::MoveDown),
+            (KeyCode::KeyS, Action::MoveDown),
+            (KeyCode::ArrowLeft, Action::MoveLeft),
+            (KeyCode::KeyA, Action::MoveLeft),
+            (KeyCode::ArrowRight, Action::MoveRight),
+            (KeyCode::KeyD, Action::MoveRight),
+            (KeyCode::Spacebar, Action::Regenerate),
+        ];
+        for (key, action) in defaults {
+            input.bind_key(key as u32, action);
+        }
+        input
+    }
+
+    // A key triggers one action, binding it again replaces the old one.
+    pub fn bind_key(&mut self, key_code: u32, action: Action) {
+        self.bindings.insert(key_code, action);
+    }
+
+    pub fn unbind_key(&mut self, key_code: u32) {
+        self.bindings.remove(&key_code);
+    }
+
+    pub fn key_down(&mut self, key_code: u32) {
+        self.keys_down.insert(key_code);
+        self.keys_pressed.insert(key_code);
+    }
+
+    pub fn key_up(&mut self, key_code: u32) {
+        self.keys_down.remove(&key_code);
+    }
+
+    // axis 0 is the stick's x, 1 its y. Other axes are ignored.
+    pub fn set_gamepad_axis(&mut self, axis: usize, value: f64) {
+        if let Some(a) = self.gamepad_axes.get_mut(axis) {
+            *a = value.clamp(-1_f64, 1_f64);
+        }
+    }
+
+    // Starts a new frame: actions are down if a bound key is held or was
+    // tapped since the last frame, or the stick is pushed their way.
+    pub fn update(&mut self) {
+        self.was_down = self.down;
+        self.down = [false; NUM_ACTIONS];
+        for key_code in self.keys_down.iter().chain(self.keys_pressed.iter()) {
+            if let Some(&action) = self.bindings.get(key_code) {
+                self.down[action as usize] = true;
+            }
+        }
+        self.keys_pressed.clear();
+
+        let [x, y] = self.gamepad_axes;
+        self.down[Action::MoveLeft as usize] |= x <= -GAMEPAD_DEAD_ZONE;
+        self.down[Action::MoveRight as usize] |= x >= GAMEPAD_DEAD_ZONE;
+        self.down[Action::MoveUp as usize] |= y <= -GAMEPAD_DEAD_ZONE;
+        self.down[Action::MoveDown as usize] |= y >= GAMEPAD_DEAD_ZONE;
+    }
+
+    pub fn is_down(&self, action: Action) -> bool {
+        self.down[action as usize]
+    }
+
+    // Down this frame but not the one before.
+    pub fn was_pressed(&self, action: Action) -> bool {
+        self.down[action as usize] && !self.was_down[action as usize]
+    }
+
+    // Down the frame before but not this one.
+    pub fn was_released(&self, action: Action) -> bool {
+        !self.down[action as usize] && self.was_down[action as usize]
+    }
+}
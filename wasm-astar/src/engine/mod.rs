@@ -1,11 +1,26 @@
-use std::collections::HashMap;
+mod input;
+mod timing;
 
+pub use self::input::{Action, InputState, GAMEPAD_DEAD_ZONE, NUM_ACTIONS};
+pub use self::timing::{FrameStats, FrameSummary, FIXED_STEP_MS, MAX_STEPS_PER_FRAME};
+
+// KeyboardEvent.keyCode values
 pub enum KeyCode {
     ArrowUp = 38,
     ArrowDown = 40,
     ArrowLeft = 37,
     ArrowRight = 39,
     Spacebar = 32,
+    KeyA = 65,
+    KeyD = 68,
+    KeyS = 83,
+    KeyW = 87,
+}
+
+// MouseEvent.button values
+pub enum MouseButton {
+    Left = 0,
+    Right = 2,
 }
 
 pub struct EngineState {
@@ -14,8 +29,16 @@ pub struct EngineState {
     pub fps: f64,
     pub mouse_x: i32,
     pub mouse_y: i32,
-    key_state: HashMap<u32, bool>,
-    was_down: HashMap<u32, bool>,
+    pub input: InputState,
+    pub stats: FrameStats,
+    // Time not yet simulated, less than one FIXED_STEP_MS after update().
+    accumulator: f64,
+}
+
+impl Default for EngineState {
+    fn default() -> EngineState {
+        EngineState::new()
+    }
 }
 
 impl EngineState {
@@ -26,17 +49,39 @@ impl EngineState {
             fps: 0_f64,
             mouse_x: 0,
             mouse_y: 0,
-            key_state: HashMap::new(),
-            was_down: HashMap::new(),
+            input: InputState::new(),
+            stats: FrameStats::new(),
+            accumulator: 0_f64,
         }
     }
 
-    pub fn update(&mut self, elapsed_time: f64) {
-        if self.last_timestamp != 0_f64 {
-            let delta: f64 = (elapsed_time - self.last_timestamp) / 1000_f64;
-            self.fps = 1_f64 / delta;
+    // Starts the frame at elapsed_time and returns how many fixed steps the
+    // simulation has to run to catch up with it.
+    pub fn update(&mut self, elapsed_time: f64) -> u32 {
+        if self.last_timestamp == 0_f64 {
+            self.last_timestamp = elapsed_time;
+            return 0;
         }
+        let delta = (elapsed_time - self.last_timestamp).max(0_f64);
         self.last_timestamp = elapsed_time;
+        self.stats.record_frame(delta);
+        let avg_ms = self.stats.avg_frame_ms();
+        self.fps = if avg_ms > 0_f64 {
+            1000_f64 / avg_ms
+        } else {
+            0_f64
+        };
+
+        self.accumulator += delta;
+        let steps = (self.accumulator / FIXED_STEP_MS) as u32;
+        self.accumulator -= steps as f64 * FIXED_STEP_MS;
+        steps.min(MAX_STEPS_PER_FRAME)
+    }
+
+    // How far the frame is between the last fixed step and the next one,
+    // 0 to 1. Drawing blends the last two steps by this much.
+    pub fn interpolation(&self) -> f64 {
+        self.accumulator / FIXED_STEP_MS
     }
 
     pub fn render_fps<F>(&mut self, elapsed_time: f64, render_delay_ms: i32, render_cb: F)
@@ -49,39 +94,6 @@ impl EngineState {
         }
     }
 
-    pub fn set_key_down(&mut self, raw_key_code: u32) {
-        let was_down = self.is_raw_key_down(raw_key_code);
-        self.was_down.insert(raw_key_code, was_down);
-        if !was_down {
-            self.key_state.insert(raw_key_code, true);
-        } else {
-            self.was_down.insert(raw_key_code, true);
-        }
-    }
-
-    pub fn set_key_up(&mut self, raw_key_code: u32) {
-        self.was_down.insert(raw_key_code, false);
-        self.key_state.insert(raw_key_code, false);
-    }
-
-    pub fn is_key_down(&self, key_code: KeyCode) -> bool {
-        self.is_raw_key_down(key_code as u32)
-    }
-
-    fn is_raw_key_down(&self, raw_key_code: u32) -> bool {
-        match self.key_state.get(&raw_key_code) {
-            Some(is_down) => *is_down,
-            None => false,
-        }
-    }
-
-    pub fn was_key_down(&self, key_code: KeyCode) -> bool {
-        match self.was_down.get(&(key_code as u32)) {
-            Some(was_down) => *was_down,
-            None => false,
-        }
-    }
-
     pub fn mouse_move(&mut self, x: i32, y: i32) {
         self.mouse_x = x;
         self.mouse_y = y;
@@ -93,7 +105,6 @@ pub struct Transform {
     pub pos_x: f64,
     pub pos_y: f64,
     pub scale_x: f64,
-    #[allow(dead_code)]
     pub scale_y: f64,
 }
 
@@ -106,13 +117,15 @@ impl Transform {
             scale_y: sy,
         }
     }
+}
 
-    pub fn default() -> Transform {
+impl Default for Transform {
+    fn default() -> Transform {
         Transform::new(0_f64, 0_f64, 0_f64, 0_f64)
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Color {
     pub h: u16,
     pub s: u16,
@@ -124,8 +137,10 @@ impl Color {
     pub fn new(h: u16, s: u16, l: u16, a: f32) -> Color {
         Color { h, s, l, a }
     }
+}
 
-    pub fn default() -> Color {
+impl Default for Color {
+    fn default() -> Color {
         Color {
             h: 0,
             s: 100,
@@ -0,0 +1,86 @@
+use std::collections::VecDeque;
+
+// The simulation always advances in steps of this length, however often
+// the display refreshes.
+pub const FIXED_STEP_MS: f64 = 1000_f64 / 60_f64;
+// After a long stall (a background tab, a breakpoint) the missed steps are
+// dropped instead of all being run at once.
+pub const MAX_STEPS_PER_FRAME: u32 = 5;
+// Frames the statistics are taken over, about two seconds at 60 fps.
+const STATS_WINDOW: usize = 120;
+
+// Rolling timings of the last STATS_WINDOW frames, in milliseconds.
+// Averages are kept up to date as frames come in, the percentiles are only
+// worked out when a summary is asked for.
+#[derive(Default)]
+pub struct FrameStats {
+    frame_times: Samples,
+    search_times: Samples,
+}
+
+#[derive(Default)]
+struct Samples {
+    values: VecDeque<f64>,
+    sum: f64,
+}
+
+// What FrameStats reports, all in milliseconds. Zero until a frame was
+// recorded.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct FrameSummary {
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub p95_ms: f64,
+    // Average time spent searching for the player's path per frame.
+    pub search_ms: f64,
+}
+
+impl FrameStats {
+    pub fn new() -> FrameStats {
+        FrameStats::default()
+    }
+
+    // Time between the start of this frame and the last one.
+    pub fn record_frame(&mut self, ms: f64) {
+        self.frame_times.push(ms);
+    }
+
+    pub fn record_search(&mut self, ms: f64) {
+        self.search_times.push(ms);
+    }
+
+    // Cheap enough to call every frame, unlike summary.
+    pub fn avg_frame_ms(&self) -> f64 {
+        self.frame_times.average()
+    }
+
+    // Sorts the window, so only for when the numbers are shown.
+    pub fn summary(&self) -> FrameSummary {
+        let mut sorted: Vec<f64> = self.frame_times.values.iter().copied().collect();
+        sorted.sort_by(|a, b| a.total_cmp(b));
+        let p95_index = (sorted.len() * 95).div_ceil(100).saturating_sub(1);
+        FrameSummary {
+            min_ms: sorted.first().copied().unwrap_or(0_f64),
+            avg_ms: self.frame_times.average(),
+            p95_ms: sorted.get(p95_index).copied().unwrap_or(0_f64),
+            search_ms: self.search_times.average(),
+        }
+    }
+}
+
+impl Samples {
+    fn push(&mut self, ms: f64) {
+        if self.values.len() == STATS_WINDOW {
+            self.sum -= self.values.pop_front().unwrap_or(0_f64);
+        }
+        self.values.push_back(ms);
+        self.sum += ms;
+    }
+
+    fn average(&self) -> f64 {
+        if self.values.is_empty() {
+            return 0_f64;
+        }
+        self.sum / self.values.len() as f64
+    }
+}
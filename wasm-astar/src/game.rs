@@ -0,0 +1,451 @@
+use crate::engine::{Action, Color, EngineState, MouseButton};
+use crate::platform::{Layer, Platform};
+use crate::render::{RetainedLayer, Sprite};
+use crate::world::{Agent, NodeState, Tile, Topology, Visibility, WorldState, AGENT_STEP_MS};
+
+// One simulation: the world, its input state and the platform it draws to.
+// Everything goes through the Platform, so the same game runs in the
+// browser (see AstarGame) and under cargo test.
+pub struct Game<P: Platform> {
+    pub world: WorldState,
+    pub engine: EngineState,
+    pub platform: P,
+    // Path, agents and markers, redrawn only where they changed.
+    pub main_layer: RetainedLayer,
+    // The search numbers on their own layer, which is cleared whole when
+    // they change.
+    pub stats_layer: RetainedLayer,
+}
+
+impl<P: Platform> Game<P> {
+    pub fn new(platform: P) -> Game<P> {
+        Game {
+            world: WorldState::new(),
+            engine: EngineState::new(),
+            platform,
+            main_layer: RetainedLayer::new(Layer::Main),
+            stats_layer: RetainedLayer::new(Layer::Stats),
+        }
+    }
+
+    pub fn init(
+        &mut self,
+        debug: bool,
+        render_interval_ms: i32,
+        window_width: u32,
+        window_height: u32,
+    ) {
+        let (world, platform) = (&mut self.world, &mut self.platform);
+        platform.log("Initializing Rust/WASM");
+        platform.create_layer(Layer::TileBg);
+        platform.create_layer(Layer::Search);
+        platform.create_layer(Layer::Fog);
+        platform.create_layer(Layer::Debug);
+        platform.create_layer(Layer::Main);
+        platform.create_layer(Layer::Stats);
+        platform.create_layer(Layer::Fps);
+        world.window_width = window_width;
+        world.window_height = window_height;
+        world.debug = debug;
+        world.set_seed(platform.random_seed());
+        platform.log(&format!("Debug Mode: {}", world.debug));
+        if world.debug {
+            platform.start_interval_tick(render_interval_ms);
+        } else {
+            platform.request_next_tick();
+        }
+        initial_draw(world, platform);
+        self.main_layer.invalidate();
+        self.stats_layer.invalidate();
+        platform.present();
+    }
+
+    pub fn tick(&mut self, elapsed_time: f64) {
+        let (world, engine, platform) = (&mut self.world, &mut self.engine, &mut self.platform);
+        platform.clear_screen(Layer::Search);
+        update(world, engine, platform, elapsed_time);
+        draw(
+            world,
+            engine,
+            &mut self.main_layer,
+            &mut self.stats_layer,
+            platform,
+            elapsed_time,
+        );
+        platform.present();
+        platform.request_next_tick();
+    }
+
+    pub fn key_down(&mut self, key_code: u32) {
+        self.engine.input.key_down(key_code);
+    }
+
+    pub fn key_up(&mut self, key_code: u32) {
+        self.engine.input.key_up(key_code);
+    }
+
+    pub fn mouse_move(&mut self, x: i32, y: i32) {
+        self.engine.mouse_move(x, y);
+        // The start follows the mouse, except while painting over the map.
+        if self.world.is_painting() {
+            self.world.paint(x, y);
+        } else {
+            self.world.set_player_pos(x as f64, y as f64);
+        }
+    }
+
+    // Left button paints walls (or erases them when pressed on a wall) until
+    // released, right button moves the goal.
+    pub fn mouse_down(&mut self, x: i32, y: i32, button: u32) {
+        if button == MouseButton::Left as u32 {
+            self.world.begin_paint(x, y);
+        } else if button == MouseButton::Right as u32 {
+            self.world.place_goal(x, y);
+        }
+    }
+
+    pub fn mouse_up(&mut self) {
+        self.world.end_paint();
+    }
+
+    // After the map was replaced outside of the game loop.
+    pub fn draw_background(&mut self) {
+        draw_background(&self.world, &mut self.platform);
+        self.platform.present();
+    }
+
+    // After the map size changed, e.g. an imported map.
+    pub fn resize(&mut self) {
+        resize_layers(&self.world, &mut self.platform);
+        draw_background(&self.world, &mut self.platform);
+        // Resizing a canvas clears it.
+        self.main_layer.invalidate();
+        self.stats_layer.invalidate();
+        self.world.redraw_flow_field |= self.world.show_flow_field;
+        self.world.redraw_fog |= self.world.fog().is_some();
+        self.platform.present();
+    }
+}
+
+fn update(
+    world: &mut WorldState,
+    engine: &mut EngineState,
+    platform: &mut impl Platform,
+    elapsed_time: f64,
+) {
+    let steps = engine.update(elapsed_time);
+    engine.input.update();
+    let (x_dir, y_dir) = handle_input(world, engine, platform);
+    for _ in 0..steps {
+        world.update_player(x_dir, y_dir);
+    }
+    world.set_start_node();
+    world.update_fog();
+    let search_start = platform.now();
+    world.update_search();
+    engine.stats.record_search(platform.now() - search_start);
+    if world.show_flow_field {
+        world.update_flow_field();
+    }
+    world.update_agents(elapsed_time);
+    platform.update();
+}
+
+// Handles this frame's one-off actions and returns the direction to move
+// the player in on every fixed step.
+fn handle_input(
+    world: &mut WorldState,
+    engine: &EngineState,
+    platform: &mut impl Platform,
+) -> (i32, i32) {
+    let input = &engine.input;
+    if input.was_pressed(Action::Regenerate) {
+        world.reset();
+        draw_background(world, platform);
+    }
+
+    let mut x_dir = 0;
+    let mut y_dir = 0;
+    if input.is_down(Action::MoveUp) {
+        y_dir = -1;
+    } else if input.is_down(Action::MoveDown) {
+        y_dir = 1;
+    }
+    if input.is_down(Action::MoveLeft) {
+        x_dir = -1;
+    } else if input.is_down(Action::MoveRight) {
+        x_dir = 1;
+    }
+    (x_dir, y_dir)
+}
+
+fn initial_draw(world: &mut WorldState, platform: &mut impl Platform) {
+    if world.window_width < 600 {
+        world.width = 350 * world.quality;
+        world.height = 450 * world.quality;
+        world.regenerate();
+    }
+    resize_layers(world, platform);
+    draw_background(world, platform);
+}
+
+fn resize_layers(world: &WorldState, platform: &mut impl Platform) {
+    platform.set_screen_size(world.width, world.height, world.quality);
+    platform.set_layer_size(Layer::TileBg, world.width, world.height, world.quality);
+    platform.set_layer_size(Layer::Search, world.width, world.height, world.quality);
+    platform.set_layer_size(Layer::Fog, world.width, world.height, world.quality);
+    platform.set_layer_size(Layer::Debug, world.width, world.height, world.quality);
+    platform.set_layer_size(Layer::Main, world.width, world.height, world.quality);
+    platform.set_layer_size(Layer::Stats, world.width, world.height, world.quality);
+    // Four rows of frame stats, see js_draw_fps. The search stats on the
+    // Stats layer start below it.
+    platform.set_layer_size(Layer::Fps, 400, 180, world.quality);
+}
+
+fn draw(
+    world: &mut WorldState,
+    engine: &mut EngineState,
+    main_layer: &mut RetainedLayer,
+    stats_layer: &mut RetainedLayer,
+    platform: &mut impl Platform,
+    elapsed_time: f64,
+) {
+    draw_dirty_tiles(world, platform);
+    if world.redraw_fog {
+        draw_fog(world, platform);
+    }
+    if world.redraw_flow_field {
+        draw_flow_field(world, platform);
+    }
+    if world.step_mode {
+        draw_search_states(world, platform);
+    }
+    let mut sprites = Vec::new();
+    let path_color = Color::new(280, 100, 73, 1_f32);
+    if world.is_any_angle() {
+        draw_waypoints(world, &mut sprites, &world.path_waypoints(), &path_color);
+    } else {
+        draw_path(&mut sprites, world.path_tiles(), &path_color);
+    }
+    draw_agents(world, &mut sprites, elapsed_time);
+    sprites.push(tile_sprite(
+        world.topology,
+        &world.tiles[world.start_id as usize],
+        &Color::new(32, 100, 60, 0.3),
+    ));
+    sprites.push(tile_sprite(
+        world.topology,
+        &world.tiles[world.end_id as usize],
+        &Color::new(112, 89, 61, 1.0),
+    ));
+    draw_player(world, &mut sprites, engine.interpolation());
+    main_layer.present(sprites, platform);
+    let stats = Sprite::SearchStats {
+        path_count: world.path_tiles().count() as i32 - 1,
+        path_cost: world.path_cost(),
+        expanded_nodes: world.expanded_nodes,
+    };
+    stats_layer.present(vec![stats], platform);
+    draw_fps(engine, platform, elapsed_time);
+}
+
+fn draw_background(world: &WorldState, platform: &mut impl Platform) {
+    // Hex and isometric maps don't cover the whole canvas, and the last
+    // map may have been laid out differently.
+    if world.topology != Topology::Square {
+        platform.clear_screen(Layer::TileBg);
+    }
+    for t in world.tiles.iter() {
+        draw_tile(platform, Layer::TileBg, world.topology, t);
+    }
+}
+
+// Redraws just the background tiles changed by editing the map.
+fn draw_dirty_tiles(world: &mut WorldState, platform: &mut impl Platform) {
+    for id in world.dirty_tiles.iter() {
+        draw_tile(platform, Layer::TileBg, world.topology, &world.tiles[*id]);
+    }
+    world.dirty_tiles.clear();
+}
+
+fn draw_search_states(world: &WorldState, platform: &mut impl Platform) {
+    let open_color = Color::new(190, 70, 55, 0.5);
+    let closed_color = Color::new(340, 55, 45, 0.35);
+    for (t, state) in world.tiles.iter().zip(world.search_states()) {
+        match state {
+            NodeState::Open => {
+                draw_tile_with_color(platform, Layer::Search, world.topology, t, &open_color)
+            }
+            NodeState::Closed => {
+                draw_tile_with_color(platform, Layer::Search, world.topology, t, &closed_color)
+            }
+            NodeState::Unvisited => {}
+        }
+    }
+}
+
+// Hidden tiles are covered, the ones seen before but out of view dimmed.
+fn draw_fog(world: &mut WorldState, platform: &mut impl Platform) {
+    world.redraw_fog = false;
+    platform.clear_screen(Layer::Fog);
+    let unseen_color = Color::new(0, 0, 0, 0.85);
+    let seen_color = Color::new(0, 0, 0, 0.45);
+    for t in world.tiles.iter() {
+        let c = match world.visibility(t.node_id) {
+            Visibility::Unseen => &unseen_color,
+            Visibility::Seen => &seen_color,
+            Visibility::Visible => continue,
+        };
+        draw_tile_with_color(platform, Layer::Fog, world.topology, t, c);
+    }
+}
+
+// An arrow across every tile that has a way to the goal.
+fn draw_flow_field(world: &mut WorldState, platform: &mut impl Platform) {
+    world.redraw_flow_field = false;
+    platform.clear_screen(Layer::Debug);
+    let Some(field) = world.flow_field().filter(|_| world.show_flow_field) else {
+        return;
+    };
+    let color = Color::new(0, 0, 100, 0.6);
+    for (t, &(x, y)) in world.tiles.iter().zip(field.directions.iter()) {
+        if x == 0_f64 && y == 0_f64 {
+            continue;
+        }
+        let (cx, cy) = t.center();
+        let length = t.transform.scale_x * 0.35;
+        let tip = (cx + x * length, cy + y * length);
+        // The head's sides are the shaft turned back 30 degrees either way.
+        let (head_x, head_y) = (x * length * 0.5, y * length * 0.5);
+        let (cos, sin) = (0.866_f64, 0.5_f64);
+        let points = [
+            (cx - x * length, cy - y * length),
+            tip,
+            (
+                tip.0 - head_x * cos + head_y * sin,
+                tip.1 - head_x * sin - head_y * cos,
+            ),
+            tip,
+            (
+                tip.0 - head_x * cos - head_y * sin,
+                tip.1 + head_x * sin - head_y * cos,
+            ),
+        ];
+        platform.draw_polyline(Layer::Debug, &points, t.transform.scale_x / 12_f64, &color);
+    }
+}
+
+fn draw_path<'a>(sprites: &mut Vec<Sprite>, path: impl Iterator<Item = &'a Tile>, c: &Color) {
+    for t in path {
+        let (px, py) = t.center();
+        sprites.push(Sprite::Circle {
+            px,
+            py,
+            radius: t.transform.scale_x / 5_f64,
+            color: c.clone(),
+        });
+    }
+}
+
+// Straight lines between the centres of the path corners.
+fn draw_waypoints(world: &WorldState, sprites: &mut Vec<Sprite>, waypoints: &[usize], c: &Color) {
+    let points: Vec<(f64, f64)> = waypoints
+        .iter()
+        .map(|id| world.tiles[*id].center())
+        .collect();
+    sprites.push(Sprite::Polyline {
+        points,
+        width: world.tile_size as f64 / 5_f64,
+        color: c.clone(),
+    });
+}
+
+// Each agent's remaining path in its colour, and the agent itself part way
+// to its next tile.
+fn draw_agents(world: &WorldState, sprites: &mut Vec<Sprite>, elapsed_time: f64) {
+    let half_tile = (world.tile_size / 2) as f64;
+    let progress = ((elapsed_time - world.agent_step_time) / AGENT_STEP_MS).clamp(0_f64, 1_f64);
+    for agent in world.agents.iter() {
+        let path = agent.path[agent.step..].iter().map(|id| &world.tiles[*id]);
+        draw_path(sprites, path, &agent.color);
+        let (px, py) = agent_position(world, agent, progress);
+        sprites.push(Sprite::Circle {
+            px,
+            py,
+            radius: half_tile * 0.8,
+            color: agent.color.clone(),
+        });
+    }
+}
+
+// Centre of the agent.
+fn agent_position(world: &WorldState, agent: &Agent, progress: f64) -> (f64, f64) {
+    let (from_x, from_y) = world.tiles[agent.tile_id()].center();
+    let (to_x, to_y) = world.tiles[agent.next_tile_id()].center();
+    (
+        from_x + (to_x - from_x) * progress,
+        from_y + (to_y - from_y) * progress,
+    )
+}
+
+// Between its last two fixed steps, so movement looks smooth at any
+// refresh rate.
+fn draw_player(world: &WorldState, sprites: &mut Vec<Sprite>, interpolation: f64) {
+    let half_tile = (world.tile_size / 2) as f64;
+    let (x, y) = world.player_position(interpolation);
+    sprites.push(Sprite::Circle {
+        px: x + half_tile,
+        py: y + half_tile,
+        radius: half_tile * 0.5,
+        color: Color::new(32, 100, 60, 1.0),
+    });
+}
+
+fn tile_sprite(topology: Topology, t: &Tile, c: &Color) -> Sprite {
+    match topology {
+        Topology::Square => Sprite::Tile {
+            px: t.transform.pos_x,
+            py: t.transform.pos_y,
+            size: t.transform.scale_x,
+            color: c.clone(),
+        },
+        _ => Sprite::Polygon {
+            points: topology.outline(t),
+            color: c.clone(),
+        },
+    }
+}
+
+fn draw_tile(platform: &mut impl Platform, layer: Layer, topology: Topology, t: &Tile) {
+    draw_tile_with_color(platform, layer, topology, t, &t.color);
+}
+
+fn draw_tile_with_color(
+    platform: &mut impl Platform,
+    layer: Layer,
+    topology: Topology,
+    t: &Tile,
+    c: &Color,
+) {
+    match topology {
+        Topology::Square => platform.draw_tile(
+            layer,
+            t.transform.pos_x,
+            t.transform.pos_y,
+            t.transform.scale_x,
+            c,
+        ),
+        _ => platform.draw_polygon(layer, &topology.outline(t), c),
+    }
+}
+
+fn draw_fps(engine: &mut EngineState, platform: &mut impl Platform, elapsed_time: f64) {
+    // The summary sorts the frame times, so it's only worked out when the
+    // text is redrawn rather than every frame.
+    let mut redraw = false;
+    engine.render_fps(elapsed_time, 150, || redraw = true);
+    if redraw {
+        platform.clear_screen(Layer::Fps);
+        platform.draw_fps(Layer::Fps, engine.fps, &engine.stats.summary());
+    }
+}
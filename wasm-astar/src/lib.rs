@@ -1,260 +1,242 @@
 use wasm_bindgen::prelude::*;
-use std::sync::{LazyLock, Mutex};
 
 mod browser;
-mod engine;
+pub mod engine;
+pub mod game;
+pub mod platform;
+pub mod render;
 mod utils;
-mod world;
-use engine::EngineState;
-use world::{Tile, WorldState};
-
-// Imported js functions. Note, some are used in other modules (browser, utils).
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_name = "js_update")]
-    fn js_update();
-    
-    #[wasm_bindgen(js_name = "js_draw_fps")]
-    fn js_draw_fps(layer_id: i32, fps: f64);
-    
-    #[wasm_bindgen(js_name = "js_path_count")]
-    fn js_path_count(layer_id: i32, count: i32);
-    
-    #[wasm_bindgen(js_name = "js_draw_circle")]
-    fn js_draw_circle(
-        layer_id: i32,
-        px: f64,
-        py: f64,
-        radius: f64,
-        ch: i32,
-        cs: i32,
-        cl: i32,
-        ca: f32,
-    );
-    
-    #[wasm_bindgen(js_name = "js_draw_tile")]
-    fn js_draw_tile(
-        layer_id: i32,
-        px: f64,
-        py: f64,
-        size: f64,
-        ch: i32,
-        cs: i32,
-        cl: i32,
-        ca: f32,
-    );
-}
-
-// Learned about this pattern from rocket_wasm on github
-// https://github.com/aochagavia/rocket_wasm/blob/d0ca51beb9c7c351a1f0266206edfd553bf078d3/src/lib.rs
-// QUESTION: is there a better way/place to store state???
-static WORLD_STATE: LazyLock<Mutex<WorldState>> = LazyLock::new(|| Mutex::new(WorldState::new()));
-static ENGINE_STATE: LazyLock<Mutex<EngineState>> = LazyLock::new(|| Mutex::new(EngineState::new()));
-
-// Maps to WASM_ASTAR.layers on the client side
-enum Layer {
-    TileBg = 0,
-    Main = 1,
-    Fps = 2,
-}
+pub mod world;
+use browser::JsPlatform;
+use engine::Action;
+use game::Game;
+use platform::Platform;
+use world::{MapFormat, MapGenerator, SearchAlgorithm, Topology};
 
 #[wasm_bindgen(start)]
 pub fn init() {
     console_error_panic_hook::set_once();
 }
 
+// One simulation per handle, so several canvases can run side by side.
+// The client keeps the handle and forwards ticks and input events to it.
 #[wasm_bindgen]
-pub fn wasm_init(debug: i32, render_interval_ms: i32, window_width: u32, window_height: u32) {
-    utils::log("Initializing Rust/WASM");
-    // Requires block curlies so lifetime of world ends which causes unlock
-    // and allows initial_draw() to gain control of the lock.
-    // Otherwise, this generic client error occurs: "RuntimeError: unreachable executed"
-    // QUESTION: is there a better way to do this?
-    browser::create_layer("TileBg", Layer::TileBg as i32);
-    browser::create_layer("Main", Layer::Main as i32);
-    browser::create_layer("Fps", Layer::Fps as i32);
-    {
-        let world = &mut WORLD_STATE.lock().unwrap();
-        world.window_width = window_width;
-        world.window_height = window_height;
-        world.debug = if debug == 1 { true } else { false };
-        utils::log_fmt(format!("Debug Mode: {}", world.debug));
-        if world.debug {
-            browser::start_interval_tick(render_interval_ms);
-        } else {
-            browser::request_next_tick();
-        }
-    }
-    initial_draw();
+pub struct AstarGame {
+    game: Game<JsPlatform>,
 }
 
 #[wasm_bindgen]
-pub fn tick(elapsed_time: f64) {
-    browser::clear_screen(Layer::Main as i32);
-    update(elapsed_time);
-    draw(elapsed_time);
-    browser::request_next_tick();
-}
+impl AstarGame {
+    // game_id picks the canvas layers and wrapper element the game draws to,
+    // and is passed back to js_request_tick so the client knows which game
+    // to tick. Ids must be unique on the page.
+    #[wasm_bindgen(constructor)]
+    pub fn new(game_id: u32) -> AstarGame {
+        AstarGame {
+            game: Game::new(JsPlatform::new(game_id)),
+        }
+    }
 
-#[wasm_bindgen]
-pub fn key_down(key_code: u32) {
-    let engine = &mut ENGINE_STATE.lock().unwrap();
-    engine.set_key_down(key_code);
-}
+    pub fn init(
+        &mut self,
+        debug: i32,
+        render_interval_ms: i32,
+        window_width: u32,
+        window_height: u32,
+    ) {
+        self.game
+            .init(debug == 1, render_interval_ms, window_width, window_height);
+    }
 
-#[wasm_bindgen]
-pub fn key_up(key_code: u32) {
-    let engine = &mut ENGINE_STATE.lock().unwrap();
-    engine.set_key_up(key_code);
-}
+    pub fn tick(&mut self, elapsed_time: f64) {
+        self.game.tick(elapsed_time);
+    }
 
-#[wasm_bindgen]
-pub fn mouse_move(x: i32, y: i32) {
-    let engine = &mut ENGINE_STATE.lock().unwrap();
-    let world = &mut WORLD_STATE.lock().unwrap();
-    engine.mouse_move(x, y);
-    world.set_player_pos(x as f64, y as f64);
-}
+    pub fn key_down(&mut self, key_code: u32) {
+        self.game.key_down(key_code);
+    }
 
-fn update(elapsed_time: f64) {
-    handle_input();
-    let engine = &mut ENGINE_STATE.lock().unwrap();
-    engine.update(elapsed_time);
-    let world = &mut WORLD_STATE.lock().unwrap();
-    world.set_start_node();
-    world.calc_astar();
-    js_update();
-}
+    pub fn key_up(&mut self, key_code: u32) {
+        self.game.key_up(key_code);
+    }
 
-fn handle_input() {
-    let world = &mut WORLD_STATE.lock().unwrap();
-    let engine = &mut ENGINE_STATE.lock().unwrap();
-    if !engine.was_key_down(engine::KeyCode::Spacebar)
-        && engine.is_key_down(engine::KeyCode::Spacebar)
-        && !world.recent_regen
-    {
-        world.reset();
-        browser::clear_screen(Layer::Main as i32);
-        // Horrible check until i implement event callbacks for key presses
-        world.recent_regen = true;
-    } else if !engine.is_key_down(engine::KeyCode::Spacebar) {
-        world.recent_regen = false;
+    // Makes key_code (a KeyboardEvent.keyCode) trigger the action, see
+    // engine::Action for the ids. Arrows, WASD and space are bound by default.
+    pub fn bind_key(&mut self, action_id: i32, key_code: u32) {
+        match Action::from_id(action_id) {
+            Some(action) => self.game.engine.input.bind_key(key_code, action),
+            None => self
+                .game
+                .platform
+                .log(&format!("Unknown input action: {}", action_id)),
+        }
     }
 
-    let mut x_dir = 0;
-    let mut y_dir = 0;
-    if engine.is_key_down(engine::KeyCode::ArrowUp) {
-        y_dir = -1;
-    } else if engine.is_key_down(engine::KeyCode::ArrowDown) {
-        y_dir = 1;
+    pub fn unbind_key(&mut self, key_code: u32) {
+        self.game.engine.input.unbind_key(key_code);
     }
-    if engine.is_key_down(engine::KeyCode::ArrowLeft) {
-        x_dir = -1;
-    } else if engine.is_key_down(engine::KeyCode::ArrowRight) {
-        x_dir = 1;
+
+    // Latest Gamepad.axes value for the left stick, axis 0 is x and 1 is y.
+    pub fn set_gamepad_axis(&mut self, axis: u32, value: f64) {
+        self.game.engine.input.set_gamepad_axis(axis as usize, value);
     }
-    world.update_player(x_dir, y_dir);
-}
 
-fn initial_draw() {
-    let world = &mut WORLD_STATE.lock().unwrap();
-    if world.window_width < 600 {
-        world.width = 350 * world.quality;
-        world.height = 450 * world.quality;
-        world.reset();
+    pub fn mouse_move(&mut self, x: i32, y: i32) {
+        self.game.mouse_move(x, y);
     }
-    browser::set_screen_size(world.width, world.height, world.quality);
-    browser::set_layer_size(
-        Layer::TileBg as i32,
-        world.width,
-        world.height,
-        world.quality,
-    );
-    browser::set_layer_size(Layer::Main as i32, world.width, world.height, world.quality);
-    browser::set_layer_size(Layer::Fps as i32, 200, 70, world.quality);
-    draw_background(world);
-}
 
-fn draw(elapsed_time: f64) {
-    let world = &mut WORLD_STATE.lock().unwrap();
-    if world.recent_regen {
-        draw_background(world);
+    pub fn mouse_down(&mut self, x: i32, y: i32, button: u32) {
+        self.game.mouse_down(x, y, button);
     }
-    draw_path(world, &world.tiles[world.end_id as usize]);
-    draw_tile_with_color(
-        Layer::Main,
-        &world.tiles[world.start_id as usize],
-        &engine::Color::new(32, 100, 60, 0.3),
-    );
-    draw_tile_with_color(
-        Layer::Main,
-        &world.tiles[world.end_id as usize],
-        &engine::Color::new(112, 89, 61, 1.0),
-    );
-    let path_count = get_path_count(world, &world.tiles[world.end_id as usize], 0);
-    draw_path_count(path_count);
-    // draw_player(world);
-    draw_fps(elapsed_time);
-}
 
-fn draw_background(world: &WorldState) {
-    for t in world.tiles.iter() {
-        draw_tile(Layer::TileBg, &t);
+    pub fn mouse_up(&mut self) {
+        self.game.mouse_up();
     }
-}
 
-fn draw_path(world: &WorldState, t: &Tile) {
-    let half_tile = (world.tile_size / 2) as f64;
-    js_draw_circle(
-        Layer::Main as i32,
-        t.transform.pos_x + half_tile,
-        t.transform.pos_y + half_tile,
-        t.transform.scale_x / 5_f64,
-        280,
-        100,
-        73,
-        1_f32,
-    );
-    if t.parent_id >= 0 {
-        draw_path(world, &world.tiles[t.parent_id as usize]);
+    // Same seed, same map and endpoints. Regenerates the map right away.
+    pub fn set_seed(&mut self, seed: u32) {
+        self.game.world.set_seed(seed);
+        self.game.draw_background();
     }
-}
 
-fn get_path_count(world: &WorldState, t: &Tile, counter: i32) -> i32 {
-    if t.parent_id >= 0 {
-        get_path_count(world, &world.tiles[t.parent_id as usize], counter + 1)
-    } else {
-        counter
+    pub fn get_seed(&self) -> u32 {
+        self.game.world.seed
     }
-}
 
-fn draw_tile(layer: Layer, t: &Tile) {
-    draw_tile_with_color(layer, &t, &t.color);
-}
+    // Regenerates the map with the chosen generator, keeping the current seed.
+    pub fn set_map_generator(&mut self, generator_id: i32) {
+        match MapGenerator::from_id(generator_id) {
+            Some(generator) => {
+                self.game.world.generator = generator;
+                self.game.world.regenerate();
+                self.game.draw_background();
+            }
+            None => self
+                .game
+                .platform
+                .log(&format!("Unknown map generator: {}", generator_id)),
+        }
+    }
 
-fn draw_tile_with_color(layer: Layer, t: &Tile, c: &engine::Color) {
-    js_draw_tile(
-        layer as i32,
-        t.transform.pos_x,
-        t.transform.pos_y,
-        t.transform.scale_x,
-        c.h as i32,
-        c.s as i32,
-        c.l as i32,
-        c.a,
-    );
-}
+    // Lays the map out as squares (0), hexagons (1) or isometric diamonds
+    // (2). Walls and endpoints stay in the same rows and columns.
+    pub fn set_topology(&mut self, topology_id: i32) {
+        match Topology::from_id(topology_id) {
+            Some(topology) => {
+                self.game.world.set_topology(topology);
+                self.game.draw_background();
+            }
+            None => self
+                .game
+                .platform
+                .log(&format!("Unknown topology: {}", topology_id)),
+        }
+    }
 
-fn draw_path_count(path_count: i32) {
-    js_path_count(Layer::Main as i32, path_count);
-}
+    // Replaces the map with one from export_map (format "csv" or "json").
+    // The canvas is resized to fit the imported map.
+    pub fn import_map(&mut self, data: &str, format: &str) -> Result<(), JsValue> {
+        let format = parse_map_format(format)?;
+        self.game
+            .world
+            .import_map(data, format)
+            .map_err(|e| JsValue::from_str(&e))?;
+        self.game.resize();
+        Ok(())
+    }
+
+    pub fn export_map(&self, format: &str) -> Result<String, JsValue> {
+        let format = parse_map_format(format)?;
+        Ok(self.game.world.export_map(format))
+    }
+
+    // Spawns agents that walk to random goals without ever sharing a tile.
+    // 0 turns them off, counts above world::MAX_AGENTS are capped to it.
+    pub fn set_agent_count(&mut self, count: u32) {
+        self.game.world.set_agent_count(count as usize);
+    }
+
+    pub fn set_diagonal_movement(&mut self, enabled: bool) {
+        self.game.world.allow_diagonal = enabled;
+    }
+
+    pub fn set_search_algorithm(&mut self, algorithm_id: i32) {
+        match SearchAlgorithm::from_id(algorithm_id) {
+            Some(algorithm) => self.game.world.algorithm = algorithm,
+            None => self
+                .game
+                .platform
+                .log(&format!("Unknown search algorithm: {}", algorithm_id)),
+        }
+    }
+
+    // Shows the flow field towards the goal as arrows over the map.
+    pub fn set_flow_field(&mut self, enabled: bool) {
+        self.game.world.set_flow_field_visible(enabled);
+    }
+
+    // Unit vector [x, y] pointing along the flow field towards the goal at
+    // a canvas position (same coordinates as mouse_move). [0, 0] on the
+    // goal, on walls, off the map and where the goal can't be reached.
+    pub fn sample_flow_field(&mut self, x: f64, y: f64) -> Vec<f64> {
+        let (dx, dy) = self.game.world.flow_direction_at(x, y);
+        vec![dx, dy]
+    }
+
+    // Hides the map beyond what the player has seen. The path is planned as
+    // if hidden tiles were open and planned again as walls come into view.
+    pub fn set_fog_of_war(&mut self, enabled: bool) {
+        self.game.world.set_fog_of_war(enabled);
+    }
 
-fn draw_fps(elapsed_time: f64) {
-    let engine = &mut ENGINE_STATE.lock().unwrap();
-    let fps = engine.fps;
-    engine.render_fps(elapsed_time, 150, || {
-        browser::clear_screen(Layer::Fps as i32);
-        js_draw_fps(Layer::Fps as i32, fps);
-    });
+    // One byte per tile in row order: 0 never seen, 1 seen before but out
+    // of view, 2 in view. Everything is in view without fog of war.
+    pub fn get_visibility_mask(&self) -> Vec<u8> {
+        let world = &self.game.world;
+        (0..world.tiles.len())
+            .map(|id| world.visibility(id) as u8)
+            .collect()
+    }
+
+    // Draws the path pulled taut around corners instead of tile by tile.
+    pub fn set_path_smoothing(&mut self, enabled: bool) {
+        self.game.world.smooth_path = enabled;
+    }
+
+    pub fn set_step_mode(&mut self, enabled: bool, steps_per_frame: u32) {
+        let world = &mut self.game.world;
+        world.step_mode = enabled;
+        world.steps_per_frame = steps_per_frame.max(1);
+        if enabled {
+            world.begin_search();
+        }
+    }
+
+    pub fn pause_search(&mut self) {
+        self.game.world.step_paused = true;
+    }
+
+    pub fn resume_search(&mut self) {
+        self.game.world.step_paused = false;
+    }
+
+    pub fn step_search(&mut self, count: u32) {
+        self.game.world.queue_search_steps(count);
+    }
+
+    pub fn get_expanded_nodes(&self) -> u32 {
+        self.game.world.expanded_nodes
+    }
+
+    // Frame times over the last couple of seconds in milliseconds:
+    // [min, average, 95th percentile, average time searching per frame].
+    pub fn get_frame_stats(&self) -> Vec<f64> {
+        let stats = self.game.engine.stats.summary();
+        vec![stats.min_ms, stats.avg_ms, stats.p95_ms, stats.search_ms]
+    }
 }
 
+fn parse_map_format(format: &str) -> Result<MapFormat, JsValue> {
+    MapFormat::from_name(format)
+        .ok_or_else(|| JsValue::from_str(&format!("Unknown map format: {}", format)))
+}
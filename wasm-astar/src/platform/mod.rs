@@ -0,0 +1,73 @@
+use crate::engine::{Color, FrameSummary};
+
+mod recording;
+pub use self::recording::{DrawCommand, RecordingPlatform};
+
+// Layers of a single game, see JsPlatform for how they map to
+// WASM_ASTAR.layers on the client side.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Layer {
+    TileBg = 0,
+    Main = 1,
+    Fps = 2,
+    Search = 3,
+    // Overlays like the flow field, drawn only when they change.
+    Debug = 4,
+    // Covers what the player hasn't seen, see WorldState::fog.
+    Fog = 5,
+    // The path and search numbers, see Sprite::SearchStats.
+    Stats = 6,
+}
+
+pub const NUM_LAYERS: u32 = 7;
+
+impl Layer {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layer::TileBg => "TileBg",
+            Layer::Main => "Main",
+            Layer::Fps => "Fps",
+            Layer::Search => "Search",
+            Layer::Debug => "Debug",
+            Layer::Fog => "Fog",
+            Layer::Stats => "Stats",
+        }
+    }
+}
+
+// Everything the game needs from its host. browser::JsPlatform forwards to
+// the JS imports, RecordingPlatform keeps the calls so the game can run
+// under cargo test.
+pub trait Platform {
+    fn create_layer(&mut self, layer: Layer);
+    fn clear_screen(&mut self, layer: Layer);
+    fn clear_rect(&mut self, layer: Layer, x: f64, y: f64, width: f64, height: f64);
+    fn set_screen_size(&mut self, width: u32, height: u32, quality: u32);
+    fn set_layer_size(&mut self, layer: Layer, width: u32, height: u32, quality: u32);
+    fn request_next_tick(&mut self);
+    fn start_interval_tick(&mut self, ms: i32);
+    fn draw_tile(&mut self, layer: Layer, px: f64, py: f64, size: f64, c: &Color);
+    fn draw_circle(&mut self, layer: Layer, px: f64, py: f64, radius: f64, c: &Color);
+    // Connected line segments through the points, in canvas pixels.
+    fn draw_polyline(&mut self, layer: Layer, points: &[(f64, f64)], width: f64, c: &Color);
+    // Filled shape with these corners, for tiles that aren't squares.
+    fn draw_polygon(&mut self, layer: Layer, points: &[(f64, f64)], c: &Color);
+    fn draw_fps(&mut self, layer: Layer, fps: f64, stats: &FrameSummary);
+    fn draw_search_stats(
+        &mut self,
+        layer: Layer,
+        path_count: i32,
+        path_cost: i32,
+        expanded_nodes: u32,
+    );
+    // Called once per tick after the world updated.
+    fn update(&mut self);
+    // Called after the last draw of a frame. Draws may be buffered until
+    // then, they must be shown in the order they were made.
+    fn present(&mut self);
+    // Milliseconds from some fixed point, only differences are used.
+    fn now(&mut self) -> f64;
+    // Only used to pick the first seed, everything after that comes from Rng.
+    fn random_seed(&mut self) -> u32;
+    fn log(&mut self, msg: &str);
+}
@@ -0,0 +1,228 @@
+use crate::engine::{Color, FrameSummary};
+
+use super::{Layer, Platform};
+
+// One call made on the platform, in the order the game made it.
+#[derive(Clone, Debug, PartialEq)]
+pub enum DrawCommand {
+    CreateLayer(Layer),
+    ClearScreen(Layer),
+    ClearRect {
+        layer: Layer,
+        x: f64,
+        y: f64,
+        width: f64,
+        height: f64,
+    },
+    SetScreenSize {
+        width: u32,
+        height: u32,
+        quality: u32,
+    },
+    SetLayerSize {
+        layer: Layer,
+        width: u32,
+        height: u32,
+        quality: u32,
+    },
+    RequestNextTick,
+    StartIntervalTick(i32),
+    Tile {
+        layer: Layer,
+        px: f64,
+        py: f64,
+        size: f64,
+        color: Color,
+    },
+    Circle {
+        layer: Layer,
+        px: f64,
+        py: f64,
+        radius: f64,
+        color: Color,
+    },
+    Polyline {
+        layer: Layer,
+        points: Vec<(f64, f64)>,
+        width: f64,
+        color: Color,
+    },
+    Polygon {
+        layer: Layer,
+        points: Vec<(f64, f64)>,
+        color: Color,
+    },
+    Fps {
+        layer: Layer,
+        fps: f64,
+        stats: FrameSummary,
+    },
+    SearchStats {
+        layer: Layer,
+        path_count: i32,
+        path_cost: i32,
+        expanded_nodes: u32,
+    },
+    Update,
+    Present,
+    Log(String),
+}
+
+// In-memory platform for native tests. Nothing is drawn, the commands are
+// kept so they can be checked or compared against a snapshot.
+pub struct RecordingPlatform {
+    pub commands: Vec<DrawCommand>,
+    // What now() returns. Time only moves when a test moves it.
+    pub now_ms: f64,
+    seed: u32,
+}
+
+impl RecordingPlatform {
+    // random_seed always returns seed, so runs are reproducible.
+    pub fn new(seed: u32) -> RecordingPlatform {
+        RecordingPlatform {
+            commands: Vec::new(),
+            now_ms: 0_f64,
+            seed,
+        }
+    }
+
+    // Returns the commands recorded so far and starts a new recording.
+    pub fn take(&mut self) -> Vec<DrawCommand> {
+        std::mem::take(&mut self.commands)
+    }
+
+    // One command per line, stable enough to diff against a golden file.
+    pub fn snapshot(&self) -> String {
+        let mut out = String::new();
+        for command in self.commands.iter() {
+            out.push_str(&format!("{:?}\n", command));
+        }
+        out
+    }
+}
+
+impl Platform for RecordingPlatform {
+    fn create_layer(&mut self, layer: Layer) {
+        self.commands.push(DrawCommand::CreateLayer(layer));
+    }
+
+    fn clear_screen(&mut self, layer: Layer) {
+        self.commands.push(DrawCommand::ClearScreen(layer));
+    }
+
+    fn clear_rect(&mut self, layer: Layer, x: f64, y: f64, width: f64, height: f64) {
+        self.commands.push(DrawCommand::ClearRect {
+            layer,
+            x,
+            y,
+            width,
+            height,
+        });
+    }
+
+    fn set_screen_size(&mut self, width: u32, height: u32, quality: u32) {
+        self.commands.push(DrawCommand::SetScreenSize {
+            width,
+            height,
+            quality,
+        });
+    }
+
+    fn set_layer_size(&mut self, layer: Layer, width: u32, height: u32, quality: u32) {
+        self.commands.push(DrawCommand::SetLayerSize {
+            layer,
+            width,
+            height,
+            quality,
+        });
+    }
+
+    fn request_next_tick(&mut self) {
+        self.commands.push(DrawCommand::RequestNextTick);
+    }
+
+    fn start_interval_tick(&mut self, ms: i32) {
+        self.commands.push(DrawCommand::StartIntervalTick(ms));
+    }
+
+    fn draw_tile(&mut self, layer: Layer, px: f64, py: f64, size: f64, c: &Color) {
+        self.commands.push(DrawCommand::Tile {
+            layer,
+            px,
+            py,
+            size,
+            color: c.clone(),
+        });
+    }
+
+    fn draw_circle(&mut self, layer: Layer, px: f64, py: f64, radius: f64, c: &Color) {
+        self.commands.push(DrawCommand::Circle {
+            layer,
+            px,
+            py,
+            radius,
+            color: c.clone(),
+        });
+    }
+
+    fn draw_polyline(&mut self, layer: Layer, points: &[(f64, f64)], width: f64, c: &Color) {
+        self.commands.push(DrawCommand::Polyline {
+            layer,
+            points: points.to_vec(),
+            width,
+            color: c.clone(),
+        });
+    }
+
+    fn draw_polygon(&mut self, layer: Layer, points: &[(f64, f64)], c: &Color) {
+        self.commands.push(DrawCommand::Polygon {
+            layer,
+            points: points.to_vec(),
+            color: c.clone(),
+        });
+    }
+
+    fn draw_fps(&mut self, layer: Layer, fps: f64, stats: &FrameSummary) {
+        self.commands.push(DrawCommand::Fps {
+            layer,
+            fps,
+            stats: *stats,
+        });
+    }
+
+    fn draw_search_stats(
+        &mut self,
+        layer: Layer,
+        path_count: i32,
+        path_cost: i32,
+        expanded_nodes: u32,
+    ) {
+        self.commands.push(DrawCommand::SearchStats {
+            layer,
+            path_count,
+            path_cost,
+            expanded_nodes,
+        });
+    }
+
+    fn update(&mut self) {
+        self.commands.push(DrawCommand::Update);
+    }
+
+    fn present(&mut self) {
+        self.commands.push(DrawCommand::Present);
+    }
+
+    fn now(&mut self) -> f64 {
+        self.now_ms
+    }
+
+    fn random_seed(&mut self) -> u32 {
+        self.seed
+    }
+
+    fn log(&mut self, msg: &str) {
+        self.commands.push(DrawCommand::Log(String::from(msg)));
+    }
+}
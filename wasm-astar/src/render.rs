@@ -0,0 +1,314 @@
+use std::collections::HashMap;
+
+use crate::engine::Color;
+use crate::platform::{Layer, Platform};
+
+// Above this many changed areas a frame just clears the layer and draws
+// everything again, it's cheaper than clearing lots of small rectangles.
+const MAX_DIRTY_RECTS: usize = 256;
+// Canvas pixels around a sprite that antialiasing may touch.
+const EDGE_MARGIN: f64 = 1_f64;
+
+// Something drawn on a retained layer, see RetainedLayer.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Sprite {
+    Tile {
+        px: f64,
+        py: f64,
+        size: f64,
+        color: Color,
+    },
+    Circle {
+        px: f64,
+        py: f64,
+        radius: f64,
+        color: Color,
+    },
+    Polyline {
+        points: Vec<(f64, f64)>,
+        width: f64,
+        color: Color,
+    },
+    Polygon {
+        points: Vec<(f64, f64)>,
+        color: Color,
+    },
+    // Text, how much room it takes is only known on the client side.
+    SearchStats {
+        path_count: i32,
+        path_cost: i32,
+        expanded_nodes: u32,
+    },
+}
+
+// Sprite with its floats as bit patterns, so equal keys hash the same and
+// sprites can be counted in a HashMap.
+#[derive(PartialEq, Eq, Hash)]
+enum SpriteKey {
+    Tile([u64; 3], ColorKey),
+    Circle([u64; 3], ColorKey),
+    Polyline(Vec<(u64, u64)>, u64, ColorKey),
+    Polygon(Vec<(u64, u64)>, ColorKey),
+    SearchStats(i32, i32, u32),
+}
+
+type ColorKey = (u16, u16, u16, u32);
+
+fn color_key(c: &Color) -> ColorKey {
+    (c.h, c.s, c.l, c.a.to_bits())
+}
+
+fn points_key(points: &[(f64, f64)]) -> Vec<(u64, u64)> {
+    points
+        .iter()
+        .map(|&(x, y)| (x.to_bits(), y.to_bits()))
+        .collect()
+}
+
+impl Sprite {
+    fn key(&self) -> SpriteKey {
+        match self {
+            Sprite::Tile {
+                px,
+                py,
+                size,
+                color,
+            } => SpriteKey::Tile(
+                [px.to_bits(), py.to_bits(), size.to_bits()],
+                color_key(color),
+            ),
+            Sprite::Circle {
+                px,
+                py,
+                radius,
+                color,
+            } => SpriteKey::Circle(
+                [px.to_bits(), py.to_bits(), radius.to_bits()],
+                color_key(color),
+            ),
+            Sprite::Polyline {
+                points,
+                width,
+                color,
+            } => SpriteKey::Polyline(points_key(points), width.to_bits(), color_key(color)),
+            Sprite::Polygon { points, color } => {
+                SpriteKey::Polygon(points_key(points), color_key(color))
+            }
+            Sprite::SearchStats {
+                path_count,
+                path_cost,
+                expanded_nodes,
+            } => SpriteKey::SearchStats(*path_count, *path_cost, *expanded_nodes),
+        }
+    }
+
+    // Canvas pixels the sprite may draw on, None when that isn't known.
+    pub fn bounds(&self) -> Option<Rect> {
+        let bounds = match self {
+            Sprite::Tile { px, py, size, .. } => Rect {
+                x: *px,
+                y: *py,
+                width: *size,
+                height: *size,
+            }
+            .grow(EDGE_MARGIN),
+            Sprite::Circle { px, py, radius, .. } => Rect {
+                x: px - radius,
+                y: py - radius,
+                width: radius * 2_f64,
+                height: radius * 2_f64,
+            }
+            .grow(EDGE_MARGIN),
+            Sprite::Polyline { points, width, .. } => {
+                points_bounds(points).grow(width / 2_f64 + EDGE_MARGIN)
+            }
+            Sprite::Polygon { points, .. } => points_bounds(points).grow(EDGE_MARGIN),
+            Sprite::SearchStats { .. } => return None,
+        };
+        Some(bounds)
+    }
+
+    pub fn draw(&self, platform: &mut impl Platform, layer: Layer) {
+        match self {
+            Sprite::Tile {
+                px,
+                py,
+                size,
+                color,
+            } => platform.draw_tile(layer, *px, *py, *size, color),
+            Sprite::Circle {
+                px,
+                py,
+                radius,
+                color,
+            } => platform.draw_circle(layer, *px, *py, *radius, color),
+            Sprite::Polyline {
+                points,
+                width,
+                color,
+            } => platform.draw_polyline(layer, points, *width, color),
+            Sprite::Polygon { points, color } => platform.draw_polygon(layer, points, color),
+            Sprite::SearchStats {
+                path_count,
+                path_cost,
+                expanded_nodes,
+            } => platform.draw_search_stats(layer, *path_count, *path_cost, *expanded_nodes),
+        }
+    }
+}
+
+// Smallest rectangle around the points.
+fn points_bounds(points: &[(f64, f64)]) -> Rect {
+    if points.is_empty() {
+        return Rect::default();
+    }
+    let (mut min_x, mut min_y) = (f64::MAX, f64::MAX);
+    let (mut max_x, mut max_y) = (f64::MIN, f64::MIN);
+    for &(x, y) in points.iter() {
+        min_x = min_x.min(x);
+        min_y = min_y.min(y);
+        max_x = max_x.max(x);
+        max_y = max_y.max(y);
+    }
+    Rect {
+        x: min_x,
+        y: min_y,
+        width: max_x - min_x,
+        height: max_y - min_y,
+    }
+}
+
+// Axis-aligned area in canvas pixels.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Rect {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Rect {
+    fn grow(self, by: f64) -> Rect {
+        Rect {
+            x: self.x - by,
+            y: self.y - by,
+            width: self.width + by * 2_f64,
+            height: self.height + by * 2_f64,
+        }
+    }
+
+    // Touching edges don't count, nothing is drawn on both.
+    pub fn intersects(&self, other: &Rect) -> bool {
+        self.x < other.x + other.width
+            && other.x < self.x + self.width
+            && self.y < other.y + other.height
+            && other.y < self.y + self.height
+    }
+}
+
+// A layer that keeps what it drew last frame. Each frame the sprites that
+// appeared or went away mark their area dirty, only those areas are cleared
+// and only the sprites overlapping them are drawn again.
+pub struct RetainedLayer {
+    pub layer: Layer,
+    sprites: Vec<Sprite>,
+    // The canvas lost its content, e.g. because it was resized.
+    needs_full_redraw: bool,
+}
+
+impl RetainedLayer {
+    pub fn new(layer: Layer) -> RetainedLayer {
+        RetainedLayer {
+            layer,
+            sprites: Vec::new(),
+            needs_full_redraw: true,
+        }
+    }
+
+    // Draws everything on the next present, for when the canvas was cleared
+    // or resized behind the layer's back.
+    pub fn invalidate(&mut self) {
+        self.needs_full_redraw = true;
+    }
+
+    // Brings the canvas from last frame's sprites to these, drawn in order.
+    pub fn present(&mut self, sprites: Vec<Sprite>, platform: &mut impl Platform) {
+        match self.redraw_areas(&sprites) {
+            Some((dirty, redraw)) => {
+                for r in dirty.iter() {
+                    platform.clear_rect(self.layer, r.x, r.y, r.width, r.height);
+                }
+                for (s, _) in sprites.iter().zip(redraw).filter(|&(_, r)| r) {
+                    s.draw(platform, self.layer);
+                }
+            }
+            None => {
+                platform.clear_screen(self.layer);
+                for s in sprites.iter() {
+                    s.draw(platform, self.layer);
+                }
+            }
+        }
+        self.sprites = sprites;
+        self.needs_full_redraw = false;
+    }
+
+    // The areas to clear and which of the new sprites to draw again, None
+    // if the whole layer should be redrawn instead. That includes any
+    // change on a layer with a sprite of unknown bounds.
+    fn redraw_areas(&self, sprites: &[Sprite]) -> Option<(Vec<Rect>, Vec<bool>)> {
+        if self.needs_full_redraw {
+            return None;
+        }
+        let old_keys: Vec<SpriteKey> = self.sprites.iter().map(Sprite::key).collect();
+        let new_keys: Vec<SpriteKey> = sprites.iter().map(Sprite::key).collect();
+        let mut counts: HashMap<&SpriteKey, i32> = HashMap::new();
+        for k in old_keys.iter() {
+            *counts.entry(k).or_insert(0) += 1;
+        }
+        for k in new_keys.iter() {
+            *counts.entry(k).or_insert(0) -= 1;
+        }
+        // Walked in order rather than through the map, so the commands
+        // come out the same every run.
+        let mut dirty: Vec<Rect> = Vec::new();
+        let old = self.sprites.iter().zip(old_keys.iter());
+        for (s, k) in old.chain(sprites.iter().zip(new_keys.iter())) {
+            if counts[k] == 0 {
+                continue;
+            }
+            let bounds = s.bounds()?;
+            if !dirty.contains(&bounds) {
+                dirty.push(bounds);
+            }
+            if dirty.len() > MAX_DIRTY_RECTS {
+                return None;
+            }
+        }
+
+        // Clearing an area erases every sprite on it, so those are drawn
+        // again, and their whole area is cleared so nothing is drawn twice.
+        let mut redraw = vec![false; sprites.len()];
+        let mut grew = !dirty.is_empty();
+        while grew {
+            grew = false;
+            for (i, s) in sprites.iter().enumerate() {
+                if redraw[i] {
+                    continue;
+                }
+                let bounds = s.bounds()?;
+                if dirty.iter().any(|r| r.intersects(&bounds)) {
+                    redraw[i] = true;
+                    if !dirty.contains(&bounds) {
+                        dirty.push(bounds);
+                        grew = true;
+                    }
+                }
+            }
+            if dirty.len() > MAX_DIRTY_RECTS {
+                return None;
+            }
+        }
+        Some((dirty, redraw))
+    }
+}
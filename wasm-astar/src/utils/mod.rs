@@ -1,32 +1,30 @@
-use wasm_bindgen::prelude::*;
-
-#[wasm_bindgen]
-extern "C" {
-    #[wasm_bindgen(js_name = "js_random")]
-    fn js_random() -> f32;
-    
-    #[wasm_bindgen(js_name = "js_random_range")]
-    fn js_random_range(min: i32, max: i32) -> i32;
-    
-    #[wasm_bindgen(js_name = "js_log")]
-    fn js_log(msg: &str);
+// Small seeded PRNG (SplitMix64). Not for anything security related, but
+// fast, tiny and good enough for map generation.
+pub struct Rng {
+    state: u64,
 }
 
-// TODO: apparently the rand crate now works with wasm.
-// Switch to that!
+impl Rng {
+    pub fn new(seed: u32) -> Rng {
+        Rng { state: seed as u64 }
+    }
 
-pub fn random_range(min: i32, max: i32) -> i32 {
-    js_random_range(min, max)
-}
+    pub fn next_u32(&mut self) -> u32 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        ((z ^ (z >> 31)) >> 32) as u32
+    }
 
-pub fn random() -> f32 {
-    js_random()
-}
-
-pub fn log(msg: &str) {
-    js_log(msg);
-}
+    // Value in [0, 1), same as Math.random().
+    pub fn random(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
 
-pub fn log_fmt(msg: String) {
-    js_log(&msg);
+    // Inclusive on both ends, same as js_random_range.
+    pub fn random_range(&mut self, min: i32, max: i32) -> i32 {
+        let span = (max as i64 - min as i64 + 1) as u64;
+        (min as i64 + (self.next_u32() as u64 % span) as i64) as i32
+    }
 }
@@ -0,0 +1,337 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use crate::engine::Color;
+use crate::utils::Rng;
+
+use super::astar::OpenNode;
+use super::generator::largest_region;
+use super::tile::MOVE_COST;
+use super::WorldState;
+
+// Time an agent takes to move one tile, which is one planner timestep.
+pub const AGENT_STEP_MS: f64 = 200_f64;
+// Every agent is planned in turn with its own distance map, so the count
+// is capped to keep replanning from stalling the page.
+pub const MAX_AGENTS: usize = 64;
+// Space-time nodes a single agent may expand before it gives up and waits.
+const MAX_PLAN_EXPANSIONS: usize = 10_000;
+// Standing still costs as much as a step on normal ground.
+const WAIT_COST: i32 = MOVE_COST * 10;
+
+pub struct Agent {
+    pub goal_id: usize,
+    // Tile at every timestep, path[0] is where the agent was when planned.
+    pub path: Vec<usize>,
+    // Index into path of the tile the agent is on.
+    pub step: usize,
+    pub color: Color,
+}
+
+impl Agent {
+    pub fn tile_id(&self) -> usize {
+        self.path[self.step]
+    }
+
+    pub fn next_tile_id(&self) -> usize {
+        self.path[(self.step + 1).min(self.path.len() - 1)]
+    }
+
+    pub fn is_done(&self) -> bool {
+        self.step + 1 >= self.path.len()
+    }
+}
+
+// Reservation table: the tiles and moves claimed by agents planned so far.
+struct Reservations {
+    // (tile, timestep)
+    tiles: HashSet<(usize, usize)>,
+    // (from, to, timestep), to stop two agents swapping tiles.
+    moves: HashSet<(usize, usize, usize)>,
+    // Agents stay on their last tile, so it's taken from that timestep on.
+    parked: HashMap<usize, usize>,
+    // Last timestep each tile is reserved at.
+    last: HashMap<usize, usize>,
+}
+
+impl Reservations {
+    fn new() -> Reservations {
+        Reservations {
+            tiles: HashSet::new(),
+            moves: HashSet::new(),
+            parked: HashMap::new(),
+            last: HashMap::new(),
+        }
+    }
+
+    fn is_free(&self, id: usize, t: usize) -> bool {
+        !self.tiles.contains(&(id, t)) && self.parked.get(&id).is_none_or(|&from| t < from)
+    }
+
+    fn can_move(&self, from: usize, to: usize, t: usize) -> bool {
+        self.is_free(to, t + 1) && !self.moves.contains(&(to, from, t))
+    }
+
+    // Whether an agent can stay on a tile for good from timestep t.
+    fn can_park(&self, id: usize, t: usize) -> bool {
+        !self.parked.contains_key(&id) && self.last.get(&id).is_none_or(|&last| last < t)
+    }
+
+    fn reserve(&mut self, path: &[usize]) {
+        for (t, &id) in path.iter().enumerate() {
+            self.tiles.insert((id, t));
+            let last = self.last.entry(id).or_insert(t);
+            *last = (*last).max(t);
+        }
+        for (t, pair) in path.windows(2).enumerate() {
+            self.moves.insert((pair[0], pair[1], t));
+        }
+        self.parked.insert(path[path.len() - 1], path.len() - 1);
+    }
+}
+
+// Node in the space-time search, parent is an index into the node list.
+struct PlanNode {
+    id: usize,
+    t: usize,
+    g: i32,
+    parent: usize,
+}
+
+impl WorldState {
+    // Drops the current agents and places new ones at random on the largest
+    // open region, at most MAX_AGENTS. Placement follows the seed like the
+    // map itself.
+    pub fn set_agent_count(&mut self, count: usize) {
+        self.agents.clear();
+        self.agent_rng = Rng::new(self.seed);
+        let starts = self.random_open_tiles(count.min(MAX_AGENTS));
+        for (i, start_id) in starts.into_iter().enumerate() {
+            // Golden angle steps keep neighbouring hues far apart.
+            let hue = (i * 137 % 360) as u16;
+            self.agents.push(Agent {
+                goal_id: start_id,
+                path: vec![start_id],
+                step: 0,
+                color: Color::new(hue, 85, 60, 1_f32),
+            });
+        }
+        self.set_agent_goals();
+        self.plan_agents();
+    }
+
+    // Moves every agent one tile per AGENT_STEP_MS. Once they all arrived
+    // they get new goals.
+    pub fn update_agents(&mut self, elapsed_time: f64) {
+        if self.agents.is_empty() {
+            return;
+        }
+        if self.agents_stale {
+            self.plan_agents();
+        }
+        // Don't replay every missed step after the tab was in the background.
+        if elapsed_time - self.agent_step_time > AGENT_STEP_MS * 10_f64 {
+            self.agent_step_time = elapsed_time - AGENT_STEP_MS;
+        }
+        while elapsed_time - self.agent_step_time >= AGENT_STEP_MS {
+            self.agent_step_time += AGENT_STEP_MS;
+            for agent in self.agents.iter_mut() {
+                if !agent.is_done() {
+                    agent.step += 1;
+                }
+            }
+            if self.agents.iter().all(|a| a.is_done()) {
+                self.set_agent_goals();
+                self.plan_agents();
+            }
+        }
+    }
+
+    // Cooperative A*: agents are planned one after another through space and
+    // time, each avoiding the tiles and moves the earlier ones reserved.
+    // An agent that finds no way through is parked where it stands and
+    // everyone is planned again around it.
+    pub fn plan_agents(&mut self) {
+        self.agents_stale = false;
+        let starts: Vec<usize> = self.agents.iter().map(|a| a.tile_id()).collect();
+        let dists: Vec<Vec<i32>> = self
+            .agents
+            .iter()
+            .map(|a| self.distances_to(a.goal_id))
+            .collect();
+        let mut stuck = vec![false; self.agents.len()];
+        let paths = loop {
+            let mut reservations = Reservations::new();
+            for (i, &start_id) in starts.iter().enumerate() {
+                if stuck[i] {
+                    reservations.reserve(&[start_id]);
+                }
+            }
+            let mut paths = Vec::with_capacity(starts.len());
+            let mut failed = None;
+            for (i, &start_id) in starts.iter().enumerate() {
+                if stuck[i] {
+                    paths.push(vec![start_id]);
+                    continue;
+                }
+                let goal_id = self.agents[i].goal_id;
+                match self.plan_agent(start_id, goal_id, &dists[i], &reservations) {
+                    Some(path) => {
+                        reservations.reserve(&path);
+                        paths.push(path);
+                    }
+                    // Nobody planned so far walks over its tile, so it can
+                    // wait there without planning them again.
+                    None if reservations.can_park(start_id, 1) => {
+                        stuck[i] = true;
+                        reservations.reserve(&[start_id]);
+                        paths.push(vec![start_id]);
+                    }
+                    None => {
+                        failed = Some(i);
+                        break;
+                    }
+                }
+            }
+            match failed {
+                Some(i) => stuck[i] = true,
+                None => break paths,
+            }
+        };
+        for (agent, path) in self.agents.iter_mut().zip(paths) {
+            agent.path = path;
+            agent.step = 0;
+        }
+    }
+
+    // Space-time A* from start to goal. Waiting in place is a move too, so
+    // an agent can let another one pass. dist_to_goal is the exact heuristic
+    // from distances_to, so only detours around other agents cost expansions.
+    fn plan_agent(
+        &self,
+        start_id: usize,
+        goal_id: usize,
+        dist_to_goal: &[i32],
+        reservations: &Reservations,
+    ) -> Option<Vec<usize>> {
+        if dist_to_goal[start_id] == i32::MAX || reservations.parked.contains_key(&goal_id) {
+            return None;
+        }
+        let mut nodes = vec![PlanNode {
+            id: start_id,
+            t: 0,
+            g: 0,
+            parent: usize::MAX,
+        }];
+        let mut best_g: HashMap<(usize, usize), i32> = HashMap::new();
+        let mut closed: HashSet<(usize, usize)> = HashSet::new();
+        let mut open = BinaryHeap::new();
+        let h = dist_to_goal[start_id];
+        open.push(OpenNode { f: h, h, id: 0 });
+
+        while let Some(node) = open.pop() {
+            let (id, t, g) = (nodes[node.id].id, nodes[node.id].t, nodes[node.id].g);
+            if !closed.insert((id, t)) {
+                continue;
+            }
+            if closed.len() > MAX_PLAN_EXPANSIONS {
+                return None;
+            }
+            if id == goal_id && reservations.can_park(id, t) {
+                let mut path = Vec::with_capacity(t + 1);
+                let mut n = node.id;
+                while n != usize::MAX {
+                    path.push(nodes[n].id);
+                    n = nodes[n].parent;
+                }
+                path.reverse();
+                return Some(path);
+            }
+
+            let moves = self
+                .open_sides(id)
+                .map(|(s, move_cost)| (s, self.tiles[s].step_cost(move_cost)))
+                .chain(std::iter::once((id, WAIT_COST)));
+            for (next_id, cost) in moves {
+                if !reservations.can_move(id, next_id, t) || closed.contains(&(next_id, t + 1)) {
+                    continue;
+                }
+                let next_g = g + cost;
+                if best_g
+                    .get(&(next_id, t + 1))
+                    .is_some_and(|&best| best <= next_g)
+                {
+                    continue;
+                }
+                best_g.insert((next_id, t + 1), next_g);
+                nodes.push(PlanNode {
+                    id: next_id,
+                    t: t + 1,
+                    g: next_g,
+                    parent: node.id,
+                });
+                let h = dist_to_goal[next_id];
+                open.push(OpenNode {
+                    f: next_g + h,
+                    h,
+                    id: nodes.len() - 1,
+                });
+            }
+        }
+        None
+    }
+
+    // Cost of the cheapest path from every tile to the goal, ignoring the
+    // other agents. Dijkstra run backwards from the goal, i32::MAX where the
+    // goal can't be reached.
+    pub(super) fn distances_to(&self, goal_id: usize) -> Vec<i32> {
+        let mut dist = vec![i32::MAX; self.tiles.len()];
+        let mut open = BinaryHeap::new();
+        dist[goal_id] = 0;
+        open.push(OpenNode {
+            f: 0,
+            h: 0,
+            id: goal_id,
+        });
+        while let Some(node) = open.pop() {
+            if node.f > dist[node.id] {
+                continue;
+            }
+            // Links between open tiles go both ways, and moving onto a tile
+            // costs the same from every side with the same move cost.
+            let to = &self.tiles[node.id];
+            for (s, move_cost) in self.open_sides(node.id) {
+                let d = node.f + to.step_cost(move_cost);
+                if d < dist[s] {
+                    dist[s] = d;
+                    open.push(OpenNode { f: d, h: 0, id: s });
+                }
+            }
+        }
+        dist
+    }
+
+    fn set_agent_goals(&mut self) {
+        let goals = self.random_open_tiles(self.agents.len());
+        for (agent, goal_id) in self.agents.iter_mut().zip(goals) {
+            agent.goal_id = goal_id;
+        }
+    }
+
+    // Distinct tiles from the largest open region, at most as many as it has.
+    fn random_open_tiles(&mut self, count: usize) -> Vec<usize> {
+        let num_x_tiles = (self.width / self.tile_size) as usize;
+        let num_y_tiles = (self.height / self.tile_size) as usize;
+        let is_wall: Vec<bool> = self.tiles.iter().map(|t| t.is_wall).collect();
+        let mut region = largest_region(&is_wall, num_x_tiles, num_y_tiles);
+        let count = count.min(region.len());
+        // Partial Fisher-Yates shuffle.
+        for i in 0..count {
+            let j = self
+                .agent_rng
+                .random_range(i as i32, region.len() as i32 - 1) as usize;
+            region.swap(i, j);
+        }
+        region.truncate(count);
+        region
+    }
+}
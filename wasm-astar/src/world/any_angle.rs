@@ -0,0 +1,136 @@
+use super::search::SearchAlgorithm;
+use super::tile::MOVE_COST;
+use super::{Topology, WorldState};
+
+impl WorldState {
+    // Whether something can walk in a straight line between the centres of
+    // two tiles without touching a wall.
+    pub fn line_of_sight(&self, from_id: usize, to_id: usize) -> bool {
+        self.trace_line(from_id, to_id).is_some()
+    }
+
+    // Whether the path is drawn as a polyline through path_waypoints
+    // instead of tile by tile.
+    pub fn is_any_angle(&self) -> bool {
+        // There is no line of sight to pull a hex path taut with.
+        self.topology != Topology::Hex
+            && (self.smooth_path || self.active_algorithm() == SearchAlgorithm::ThetaStar)
+    }
+
+    // Corners of the current path from the start to its tail. Theta* paths
+    // are made of straight segments already, grid paths are pulled taut
+    // first when smooth_path is set.
+    pub fn path_waypoints(&self) -> Vec<usize> {
+        let mut path: Vec<usize> = self.path_tiles().map(|t| t.node_id).collect();
+        path.reverse();
+        if self.smooth_path && self.topology != Topology::Hex {
+            self.pull_string(&path)
+        } else {
+            path
+        }
+    }
+
+    // String pulling: from each corner, follow the path as long as the next
+    // tile can still be seen and turn on the last one that could.
+    fn pull_string(&self, path: &[usize]) -> Vec<usize> {
+        let (Some(&first), Some(&last)) = (path.first(), path.last()) else {
+            return Vec::new();
+        };
+        let mut waypoints = vec![first];
+        let mut anchor = first;
+        for pair in path.windows(2) {
+            if !self.line_of_sight(anchor, pair[1]) {
+                anchor = pair[0];
+                waypoints.push(anchor);
+            }
+        }
+        if path.len() > 1 {
+            waypoints.push(last);
+        }
+        waypoints
+    }
+
+    // Theta*: A* where a neighbour the current node's parent can see is
+    // linked straight to that parent, so paths take any angle instead of
+    // following grid edges.
+    pub(super) fn expand_theta_star(&mut self, current_node: usize) {
+        let parent_id = self.tiles[current_node].parent_id;
+        for (id, move_cost) in self.linked_sides(current_node) {
+            if self.scratch.is_closed(id) {
+                continue;
+            }
+            let mut from = current_node;
+            let mut g = self.tiles[current_node].g + self.tiles[id].step_cost(move_cost);
+            if parent_id >= 0 {
+                // The straight line can cost more when it crosses bad terrain
+                // the grid path goes around.
+                if let Some(cost) = self.segment_cost(parent_id as usize, id) {
+                    let shortcut_g = self.tiles[parent_id as usize].g + cost;
+                    if shortcut_g <= g {
+                        from = parent_id as usize;
+                        g = shortcut_g;
+                    }
+                }
+            }
+            if !self.scratch.is_open(id) || g < self.tiles[id].g {
+                let t = &mut self.tiles[id];
+                t.parent_id = from as i32;
+                t.g = g;
+                t.f = g + t.h;
+                let (f, h) = (t.f, t.h);
+                self.scratch.push(id, f, h);
+            }
+        }
+    }
+
+    // Cost of walking straight between two tiles: the distance between their
+    // centres, weighted by the most expensive terrain the line crosses.
+    // None if there is no line of sight.
+    fn segment_cost(&self, from_id: usize, to_id: usize) -> Option<i32> {
+        let weight = self.trace_line(from_id, to_id)?;
+        let dx = (self.tiles[to_id].x_id - self.tiles[from_id].x_id) as f64;
+        let dy = (self.tiles[to_id].y_id - self.tiles[from_id].y_id) as f64;
+        Some(((dx * dx + dy * dy).sqrt() * (MOVE_COST * weight) as f64).round() as i32)
+    }
+
+    // Visits every tile a line between two tile centres passes through,
+    // leaving out the first. Returns the highest terrain weight on the way,
+    // None if the line hits a wall. Passing exactly through a corner is
+    // only blocked when the tiles on both sides are walls, like a diagonal
+    // move.
+    fn trace_line(&self, from_id: usize, to_id: usize) -> Option<i32> {
+        let (mut x, mut y) = (self.tiles[from_id].x_id, self.tiles[from_id].y_id);
+        let (to_x, to_y) = (self.tiles[to_id].x_id, self.tiles[to_id].y_id);
+        let (num_x, num_y) = ((to_x - x).abs(), (to_y - y).abs());
+        let (x_dir, y_dir) = ((to_x - x).signum(), (to_y - y).signum());
+        let mut weight = 0;
+        let (mut ix, mut iy) = (0, 0);
+        while ix < num_x || iy < num_y {
+            // Which tile border the line crosses next, compared without
+            // dividing: the vertical one at (ix + 0.5) / num_x of the way
+            // or the horizontal one at (iy + 0.5) / num_y.
+            let decision = (1 + 2 * ix) * num_y - (1 + 2 * iy) * num_x;
+            if decision == 0 {
+                if !self.is_walkable(x + x_dir, y) && !self.is_walkable(x, y + y_dir) {
+                    return None;
+                }
+                x += x_dir;
+                y += y_dir;
+                ix += 1;
+                iy += 1;
+            } else if decision < 0 {
+                x += x_dir;
+                ix += 1;
+            } else {
+                y += y_dir;
+                iy += 1;
+            }
+            if !self.is_walkable(x, y) {
+                return None;
+            }
+            let t = &self.tiles[self.get_tile_id_at(x as u32, y as u32)];
+            weight = weight.max(t.terrain.weight());
+        }
+        Some(weight)
+    }
+}
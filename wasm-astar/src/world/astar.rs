@@ -0,0 +1,112 @@
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, VecDeque};
+
+// Where a tile currently sits in the search, indexed by node_id.
+#[derive(Clone, Copy, PartialEq)]
+pub enum NodeState {
+    Unvisited,
+    Open,
+    Closed,
+}
+
+// Entry on the open heap. A node can be pushed more than once when a cheaper
+// path to it is found, stale entries are skipped when popped (lazy deletion).
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct OpenNode {
+    pub f: i32,
+    pub h: i32,
+    pub id: usize,
+}
+
+impl Ord for OpenNode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // BinaryHeap is a max heap, so reverse to pop the lowest F first.
+        // Ties go to the node closest to the target (lowest H).
+        other
+            .f
+            .cmp(&self.f)
+            .then_with(|| other.h.cmp(&self.h))
+            .then_with(|| other.id.cmp(&self.id))
+    }
+}
+
+impl PartialOrd for OpenNode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+// Buffers reused by every search so a frame doesn't allocate once the
+// capacity has grown to fit the current map.
+pub struct SearchScratch {
+    pub open: BinaryHeap<OpenNode>,
+    // FIFO open list for breadth-first search.
+    pub queue: VecDeque<usize>,
+    pub state: Vec<NodeState>,
+    // Path tiles from the target back to the start, filled on demand.
+    pub path: Vec<usize>,
+    pub last_expanded: i32,
+    pub done: bool,
+}
+
+impl SearchScratch {
+    pub fn new() -> SearchScratch {
+        SearchScratch {
+            open: BinaryHeap::new(),
+            queue: VecDeque::new(),
+            state: Vec::new(),
+            path: Vec::new(),
+            last_expanded: -1,
+            done: false,
+        }
+    }
+
+    pub fn reset(&mut self, num_nodes: usize) {
+        self.open.clear();
+        self.queue.clear();
+        self.state.clear();
+        self.state.resize(num_nodes, NodeState::Unvisited);
+        self.last_expanded = -1;
+        self.done = false;
+    }
+
+    pub fn push(&mut self, id: usize, f: i32, h: i32) {
+        self.state[id] = NodeState::Open;
+        self.open.push(OpenNode { f, h, id });
+    }
+
+    // Pops the open node with the lowest F, skipping stale heap entries for
+    // nodes that were already closed through a cheaper path.
+    pub fn pop(&mut self) -> Option<usize> {
+        while let Some(node) = self.open.pop() {
+            if self.state[node.id] != NodeState::Closed {
+                self.state[node.id] = NodeState::Closed;
+                return Some(node.id);
+            }
+        }
+        None
+    }
+
+    pub fn enqueue(&mut self, id: usize) {
+        self.state[id] = NodeState::Open;
+        self.queue.push_back(id);
+    }
+
+    pub fn dequeue(&mut self) -> Option<usize> {
+        let id = self.queue.pop_front()?;
+        self.state[id] = NodeState::Closed;
+        Some(id)
+    }
+
+    pub fn is_visited(&self, id: usize) -> bool {
+        self.state[id] != NodeState::Unvisited
+    }
+
+    pub fn is_open(&self, id: usize) -> bool {
+        self.state[id] == NodeState::Open
+    }
+
+    pub fn is_closed(&self, id: usize) -> bool {
+        self.state[id] == NodeState::Closed
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::BinaryHeap;
+
+use super::astar::OpenNode;
+use super::WorldState;
+
+const INF: i32 = i32::MAX;
+
+// D* Lite, kept between searches. It plans from the goal back to the start,
+// so when the start moves or a wall changes only the tiles whose cost to the
+// goal changed are expanded again. The queue is the usual open heap with
+// (k1, k2) stored as (f, h), stale entries are skipped like in SearchScratch.
+pub struct DStarLite {
+    goal: usize,
+    allow_diagonal: bool,
+    // Cost from each tile to the goal as of its last expansion.
+    g: Vec<i32>,
+    // Cost through the best neighbour, differs from g while queued.
+    rhs: Vec<i32>,
+    open: BinaryHeap<OpenNode>,
+    // Key each tile is queued with, None when it isn't.
+    queued: Vec<Option<(i32, i32)>>,
+    // Added to new keys as the start moves, so old keys stay comparable
+    // without requeueing everything.
+    km: i32,
+    last_start: usize,
+}
+
+impl WorldState {
+    // Plans to end_id reusing the previous search where possible. Leaves the
+    // path in the parent_id chain like the other algorithms, but only
+    // touches the tiles on it.
+    pub(super) fn search_dstar_lite(&mut self) {
+        let mut d = match self.dstar.take() {
+            Some(d)
+                if d.goal == self.end_id as usize && d.allow_diagonal == self.allow_diagonal =>
+            {
+                d
+            }
+            _ => self.new_dstar_lite(),
+        };
+        self.move_dstar_start(&mut d);
+        self.expanded_nodes = self.compute_dstar_path(&mut d);
+        self.link_dstar_path(&d);
+        self.dstar = Some(d);
+        self.scratch.done = true;
+    }
+
+    // Queues the tiles whose links were rebuilt around a changed wall.
+    pub(super) fn update_dstar_lite(&mut self, id: usize) {
+        let Some(mut d) = self.dstar.take() else {
+            return;
+        };
+        self.move_dstar_start(&mut d);
+        let num_x_tiles = (self.width / self.tile_size) as i32;
+        let num_y_tiles = (self.height / self.tile_size) as i32;
+        let (x_id, y_id) = (self.tiles[id].x_id, self.tiles[id].y_id);
+        for y in (y_id - 1).max(0)..=(y_id + 1).min(num_y_tiles - 1) {
+            for x in (x_id - 1).max(0)..=(x_id + 1).min(num_x_tiles - 1) {
+                self.update_dstar_tile(&mut d, (y * num_x_tiles + x) as usize);
+            }
+        }
+        self.dstar = Some(d);
+    }
+
+    fn new_dstar_lite(&self) -> DStarLite {
+        let goal = self.end_id as usize;
+        let mut d = DStarLite {
+            goal,
+            allow_diagonal: self.allow_diagonal,
+            g: vec![INF; self.tiles.len()],
+            rhs: vec![INF; self.tiles.len()],
+            open: BinaryHeap::new(),
+            queued: vec![None; self.tiles.len()],
+            km: 0,
+            last_start: self.start_id as usize,
+        };
+        d.rhs[goal] = 0;
+        let key = self.dstar_key(&d, goal);
+        d.queued[goal] = Some(key);
+        d.open.push(OpenNode {
+            f: key.0,
+            h: key.1,
+            id: goal,
+        });
+        d
+    }
+
+    fn move_dstar_start(&self, d: &mut DStarLite) {
+        let start = self.start_id as usize;
+        if d.last_start != start {
+            d.km += self.dstar_estimate(d.last_start, start);
+            d.last_start = start;
+        }
+    }
+
+    // Expands until the start is consistent and nothing queued could still
+    // lower its cost. Returns the number of tiles expanded.
+    fn compute_dstar_path(&self, d: &mut DStarLite) -> u32 {
+        let start = self.start_id as usize;
+        let mut expanded = 0;
+        loop {
+            while let Some(top) = d.open.peek() {
+                if d.queued[top.id] == Some((top.f, top.h)) {
+                    break;
+                }
+                d.open.pop();
+            }
+            let Some(top) = d.open.pop() else {
+                break;
+            };
+            let old_key = (top.f, top.h);
+            if old_key >= self.dstar_key(d, start) && d.rhs[start] == d.g[start] {
+                d.open.push(top);
+                break;
+            }
+            expanded += 1;
+            let id = top.id;
+            d.queued[id] = None;
+            let new_key = self.dstar_key(d, id);
+            if old_key < new_key {
+                d.queued[id] = Some(new_key);
+                d.open.push(OpenNode {
+                    f: new_key.0,
+                    h: new_key.1,
+                    id,
+                });
+            } else if d.g[id] > d.rhs[id] {
+                d.g[id] = d.rhs[id];
+                self.update_dstar_sides(d, id);
+            } else {
+                d.g[id] = INF;
+                self.update_dstar_tile(d, id);
+                self.update_dstar_sides(d, id);
+            }
+        }
+        expanded
+    }
+
+    // Links between open tiles go both ways, so the tiles that can step onto
+    // this one are its own sides.
+    fn update_dstar_sides(&self, d: &mut DStarLite, id: usize) {
+        for (s, _) in self.linked_sides(id) {
+            self.update_dstar_tile(d, s);
+        }
+    }
+
+    fn update_dstar_tile(&self, d: &mut DStarLite, id: usize) {
+        if id != d.goal {
+            d.rhs[id] = if self.is_blocked(id) {
+                INF
+            } else {
+                self.best_dstar_side(d, id).map_or(INF, |(_, cost)| cost)
+            };
+        }
+        if d.g[id] == d.rhs[id] {
+            d.queued[id] = None;
+        } else {
+            let key = self.dstar_key(d, id);
+            d.queued[id] = Some(key);
+            d.open.push(OpenNode {
+                f: key.0,
+                h: key.1,
+                id,
+            });
+        }
+    }
+
+    // Side with the cheapest way to the goal and that cost, including the
+    // step onto the side.
+    fn best_dstar_side(&self, d: &DStarLite, id: usize) -> Option<(usize, i32)> {
+        self.linked_sides(id)
+            .filter(|&(s, _)| d.g[s] != INF)
+            .map(|(s, move_cost)| (s, self.tiles[s].step_cost(move_cost) + d.g[s]))
+            .min_by_key(|&(_, cost)| cost)
+    }
+
+    fn dstar_key(&self, d: &DStarLite, id: usize) -> (i32, i32) {
+        let cost = d.g[id].min(d.rhs[id]);
+        let estimate = self.dstar_estimate(self.start_id as usize, id);
+        (cost.saturating_add(estimate).saturating_add(d.km), cost)
+    }
+
+    fn dstar_estimate(&self, from: usize, to: usize) -> i32 {
+        self.tiles[from].estimate_cost(&self.tiles[to], self.side_heuristic())
+    }
+
+    // Follows the cheapest sides from the start down to the goal.
+    fn link_dstar_path(&mut self, d: &DStarLite) {
+        let (start, goal) = (self.start_id as usize, self.end_id as usize);
+        self.tiles[goal].parent_id = -1;
+        self.tiles[start].parent_id = -1;
+        self.tiles[start].g = 0;
+        if d.g[start] == INF {
+            return;
+        }
+        let mut id = start;
+        // g falls on every step, the limit only guards against a bad table.
+        for _ in 0..self.tiles.len() {
+            if id == goal {
+                break;
+            }
+            let Some((next, _)) = self.best_dstar_side(d, id) else {
+                break;
+            };
+            let parent_g = self.tiles[id].g;
+            let step_cost = self.tiles[next].step_cost(self.side_move_cost(id, next));
+            self.tiles[next].parent_id = id as i32;
+            self.tiles[next].calc_f_g(parent_g, step_cost);
+            id = next;
+        }
+    }
+}
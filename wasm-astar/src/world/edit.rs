@@ -0,0 +1,106 @@
+use super::{tile_color, WorldState};
+
+// What a mouse stroke does to the tiles it passes over.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Brush {
+    None,
+    Wall,
+    Erase,
+}
+
+impl WorldState {
+    // Starts a stroke at a canvas position (same coordinates as
+    // set_player_pos). Starting on a wall erases, anywhere else paints.
+    pub fn begin_paint(&mut self, x: i32, y: i32) {
+        let id = match self.get_tile_id_at_pixel(x, y) {
+            Some(id) => id,
+            None => return,
+        };
+        self.brush = if self.tiles[id].is_wall {
+            Brush::Erase
+        } else {
+            Brush::Wall
+        };
+        self.paint(x, y);
+    }
+
+    pub fn paint(&mut self, x: i32, y: i32) {
+        let is_wall = match self.brush {
+            Brush::None => return,
+            Brush::Wall => true,
+            Brush::Erase => false,
+        };
+        if let Some(id) = self.get_tile_id_at_pixel(x, y) {
+            self.set_wall(id, is_wall);
+        }
+    }
+
+    pub fn end_paint(&mut self) {
+        self.brush = Brush::None;
+    }
+
+    pub fn is_painting(&self) -> bool {
+        self.brush != Brush::None
+    }
+
+    pub fn place_goal(&mut self, x: i32, y: i32) {
+        if let Some(id) = self.get_tile_id_at_pixel(x, y) {
+            if !self.tiles[id].is_wall {
+                self.end_id = id as i32;
+            }
+        }
+    }
+
+    fn get_tile_id_at_pixel(&self, x: i32, y: i32) -> Option<usize> {
+        let px = x * self.quality as i32;
+        let py = y * self.quality as i32;
+        self.get_tile_id_at_point(px as f64, py as f64)
+    }
+
+    // The endpoints and agents can't be walled in. Only the links around
+    // the tile change, so only those are rebuilt and only the tile is redrawn.
+    fn set_wall(&mut self, id: usize, is_wall: bool) {
+        if self.tiles[id].is_wall == is_wall
+            || (is_wall && (id as i32 == self.start_id || id as i32 == self.end_id))
+            || (is_wall && self.agents.iter().any(|a| a.tile_id() == id || a.goal_id == id))
+        {
+            return;
+        }
+        let t = &mut self.tiles[id];
+        t.is_wall = is_wall;
+        t.color = tile_color(t);
+        self.dirty_tiles.push(id);
+        self.relink_around(id);
+        // The wall may hide or open up part of the view.
+        self.refresh_fog();
+        self.replan_after_edit();
+    }
+
+    // Rebuilds the links around a tile that became a wall or stopped being
+    // one, as far as the searches know, see is_blocked.
+    pub(super) fn relink_around(&mut self, id: usize) {
+        // Diagonal links depend on the tiles beside them, so every tile in
+        // the 3x3 block around the change can be affected. Hex sides are all
+        // inside that block too.
+        let num_x_tiles = (self.width / self.tile_size) as i32;
+        let num_y_tiles = (self.height / self.tile_size) as i32;
+        let (x_id, y_id) = (self.tiles[id].x_id, self.tiles[id].y_id);
+        for y in (y_id - 1).max(0)..=(y_id + 1).min(num_y_tiles - 1) {
+            for x in (x_id - 1).max(0)..=(x_id + 1).min(num_x_tiles - 1) {
+                self.set_tile_sides((y * num_x_tiles + x) as usize);
+            }
+        }
+        self.update_hierarchy(id);
+        self.update_dstar_lite(id);
+    }
+
+    // The paths in use may cross the changed tiles.
+    pub(super) fn replan_after_edit(&mut self) {
+        self.flow_field = None;
+        self.map_changed = true;
+        if self.step_mode {
+            self.begin_search();
+        }
+        self.agents_stale = !self.agents.is_empty();
+    }
+}
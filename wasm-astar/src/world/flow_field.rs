@@ -0,0 +1,84 @@
+use super::WorldState;
+
+// Directions towards one goal from every tile at once. Any number of units
+// can head for the goal by looking up the tile they're on, instead of each
+// searching for its own path.
+pub struct FlowField {
+    pub goal: usize,
+    // Diagonal moves change every cost, so the field is only good for the
+    // setting it was built with.
+    pub allow_diagonal: bool,
+    // Integration field: cost of the cheapest path from every tile to the
+    // goal, i32::MAX where the goal can't be reached.
+    pub costs: Vec<i32>,
+    // Unit vector in canvas pixels from the centre of every tile towards
+    // the centre of the side to step onto next. (0, 0) on the goal and
+    // where it can't be reached.
+    pub directions: Vec<(f64, f64)>,
+}
+
+impl WorldState {
+    // Field towards end_id, None until update_flow_field built it.
+    pub fn flow_field(&self) -> Option<&FlowField> {
+        self.flow_field.as_ref()
+    }
+
+    // Rebuilds the field when the goal moved, the map changed or diagonal
+    // moves were switched since the last one.
+    pub fn update_flow_field(&mut self) {
+        let goal = self.end_id as usize;
+        if self
+            .flow_field
+            .as_ref()
+            .is_some_and(|f| f.goal == goal && f.allow_diagonal == self.allow_diagonal)
+        {
+            return;
+        }
+        let costs = self.distances_to(goal);
+        let directions = (0..self.tiles.len())
+            .map(|id| self.flow_direction(&costs, id))
+            .collect();
+        self.flow_field = Some(FlowField {
+            goal,
+            allow_diagonal: self.allow_diagonal,
+            costs,
+            directions,
+        });
+        self.redraw_flow_field |= self.show_flow_field;
+    }
+
+    pub fn set_flow_field_visible(&mut self, visible: bool) {
+        self.show_flow_field = visible;
+        self.redraw_flow_field = true;
+    }
+
+    // Direction at a canvas position (same coordinates as set_player_pos),
+    // (0, 0) off the map.
+    pub fn flow_direction_at(&mut self, x: f64, y: f64) -> (f64, f64) {
+        let quality = self.quality as f64;
+        let Some(id) = self.get_tile_id_at_point(x * quality, y * quality) else {
+            return (0_f64, 0_f64);
+        };
+        self.update_flow_field();
+        self.flow_field.as_ref().unwrap().directions[id]
+    }
+
+    // Towards the side with the cheapest way on, including the step onto it.
+    fn flow_direction(&self, costs: &[i32], id: usize) -> (f64, f64) {
+        if id == self.end_id as usize || costs[id] == i32::MAX {
+            return (0_f64, 0_f64);
+        }
+        let best = self
+            .open_sides(id)
+            .filter(|&(s, _)| costs[s] != i32::MAX)
+            .min_by_key(|&(s, move_cost)| self.tiles[s].step_cost(move_cost) + costs[s]);
+        let Some((next, _)) = best else {
+            return (0_f64, 0_f64);
+        };
+        let (from_x, from_y) = self.tiles[id].center();
+        let (to_x, to_y) = self.tiles[next].center();
+        let (x, y) = (to_x - from_x, to_y - from_y);
+        let length = (x * x + y * y).sqrt();
+        (x / length, y / length)
+    }
+}
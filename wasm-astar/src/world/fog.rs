@@ -0,0 +1,236 @@
+use super::{Topology, WorldState};
+
+// How far the player sees, in tiles.
+pub const VIEW_RADIUS: i32 = 10;
+
+// Multipliers turning the first octant's dx and dy into each of the eight
+// octants: x = dx * xx + dy * xy, y = dx * yx + dy * yy.
+const OCTANTS: [(i32, i32, i32, i32); 8] = [
+    (1, 0, 0, 1),
+    (0, 1, 1, 0),
+    (0, -1, 1, 0),
+    (-1, 0, 0, 1),
+    (-1, 0, 0, -1),
+    (0, -1, -1, 0),
+    (0, 1, -1, 0),
+    (1, 0, 0, -1),
+];
+
+// What the player knows about the map. Searches only avoid walls that were
+// seen, everything still hidden is assumed open, so a path is planned
+// optimistically and planned again as walls come into view.
+pub struct Fog {
+    // Seen at some point. A wall stays known once it was seen.
+    pub seen: Vec<bool>,
+    // In view of the player right now.
+    pub visible: Vec<bool>,
+    // Tile the view was last worked out from, -1 to work it out again.
+    origin: i32,
+}
+
+impl Fog {
+    fn new(num_tiles: usize) -> Fog {
+        Fog {
+            seen: vec![false; num_tiles],
+            visible: vec![false; num_tiles],
+            origin: -1,
+        }
+    }
+}
+
+// Shown to the client in get_visibility_mask, one per tile.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Visibility {
+    Unseen = 0,
+    // Seen before but out of view now.
+    Seen = 1,
+    Visible = 2,
+}
+
+impl WorldState {
+    pub fn fog(&self) -> Option<&Fog> {
+        self.fog.as_ref()
+    }
+
+    // Turning fog on forgets the whole map, turning it off reveals it.
+    pub fn set_fog_of_war(&mut self, enabled: bool) {
+        self.fog = if enabled {
+            Some(Fog::new(self.tiles.len()))
+        } else {
+            None
+        };
+        self.set_all_tile_sides();
+        self.hierarchy = None;
+        self.dstar = None;
+        self.replan_after_edit();
+        self.redraw_fog = true;
+        self.update_fog();
+    }
+
+    // Whether searches treat the tile as a wall.
+    pub(super) fn is_blocked(&self, id: usize) -> bool {
+        self.tiles[id].is_wall && self.fog.as_ref().is_none_or(|f| f.seen[id])
+    }
+
+    // Sides that are open for real. Under fog linked_sides also links walls
+    // the player hasn't seen, which only the player's path may plan through;
+    // agents and the flow field use these.
+    pub(super) fn open_sides(&self, id: usize) -> impl Iterator<Item = (usize, i32)> + '_ {
+        let fog = self.fog.is_some();
+        self.linked_sides(id)
+            .filter(move |&(s, _)| !fog || self.is_open_link(id, s))
+    }
+
+    fn is_open_link(&self, from: usize, to: usize) -> bool {
+        if self.tiles[to].is_wall {
+            return false;
+        }
+        let (a, b) = (&self.tiles[from], &self.tiles[to]);
+        if self.topology == Topology::Hex || a.x_id == b.x_id || a.y_id == b.y_id {
+            return true;
+        }
+        // Same corner rule as set_tile_sides.
+        let beside = self.get_tile_id_at(b.x_id as u32, a.y_id as u32);
+        let above_or_below = self.get_tile_id_at(a.x_id as u32, b.y_id as u32);
+        !(self.tiles[beside].is_wall && self.tiles[above_or_below].is_wall)
+    }
+
+    pub fn visibility(&self, id: usize) -> Visibility {
+        match &self.fog {
+            Some(f) if !f.visible[id] && f.seen[id] => Visibility::Seen,
+            Some(f) if !f.visible[id] => Visibility::Unseen,
+            _ => Visibility::Visible,
+        }
+    }
+
+    // Works out the view again after the player moved to another tile or
+    // the walls changed, and replans if it revealed walls.
+    pub fn update_fog(&mut self) {
+        let Some(mut fog) = self.fog.take() else {
+            return;
+        };
+        if fog.origin == self.start_id {
+            self.fog = Some(fog);
+            return;
+        }
+        fog.origin = self.start_id;
+        fog.visible = self.field_of_view(self.start_id as usize);
+        let mut revealed_walls = Vec::new();
+        for (id, &visible) in fog.visible.iter().enumerate() {
+            if visible && !fog.seen[id] {
+                fog.seen[id] = true;
+                if self.tiles[id].is_wall {
+                    revealed_walls.push(id);
+                }
+            }
+        }
+        self.fog = Some(fog);
+        self.redraw_fog = true;
+        for id in revealed_walls.iter() {
+            self.relink_around(*id);
+        }
+        if !revealed_walls.is_empty() {
+            self.replan_after_edit();
+        }
+    }
+
+    // The whole map is hidden again, e.g. because it was replaced.
+    pub(super) fn reset_fog(&mut self) {
+        if let Some(fog) = self.fog.as_mut() {
+            *fog = Fog::new(self.tiles.len());
+            self.redraw_fog = true;
+        }
+    }
+
+    // The view has to be worked out again, e.g. because a wall changed.
+    pub(super) fn refresh_fog(&mut self) {
+        if let Some(fog) = self.fog.as_mut() {
+            fog.origin = -1;
+        }
+    }
+
+    // Recursive shadow casting: each octant is scanned row by row moving
+    // away from the player, and walls narrow the range of slopes that can
+    // still be seen in the rows behind them. Walls themselves are visible.
+    fn field_of_view(&self, from_id: usize) -> Vec<bool> {
+        let mut visible = vec![false; self.tiles.len()];
+        visible[from_id] = true;
+        let origin = (self.tiles[from_id].x_id, self.tiles[from_id].y_id);
+        for octant in OCTANTS.iter() {
+            self.cast_light(&mut visible, origin, 1, 1_f64, 0_f64, *octant);
+        }
+        visible
+    }
+
+    fn cast_light(
+        &self,
+        visible: &mut [bool],
+        origin: (i32, i32),
+        row: i32,
+        mut start_slope: f64,
+        end_slope: f64,
+        (xx, xy, yx, yy): (i32, i32, i32, i32),
+    ) {
+        if start_slope < end_slope {
+            return;
+        }
+        let mut next_start_slope = start_slope;
+        for distance in row..=VIEW_RADIUS {
+            let dy = -distance;
+            let mut blocked = false;
+            for dx in -distance..=0 {
+                // Slopes through the tile's outer corners.
+                let left_slope = (dx as f64 - 0.5) / (dy as f64 + 0.5);
+                let right_slope = (dx as f64 + 0.5) / (dy as f64 - 0.5);
+                if start_slope < right_slope {
+                    continue;
+                }
+                if end_slope > left_slope {
+                    break;
+                }
+                let x = origin.0 + dx * xx + dy * xy;
+                let y = origin.1 + dx * yx + dy * yy;
+                // The map edge blocks the view like a wall.
+                let on_map = self.is_on_map(x, y);
+                let id = if on_map {
+                    self.get_tile_id_at(x as u32, y as u32)
+                } else {
+                    0
+                };
+                if on_map && dx * dx + dy * dy <= VIEW_RADIUS * VIEW_RADIUS {
+                    visible[id] = true;
+                }
+                let opaque = !on_map || self.tiles[id].is_wall;
+                if blocked {
+                    if opaque {
+                        next_start_slope = right_slope;
+                    } else {
+                        blocked = false;
+                        start_slope = next_start_slope;
+                    }
+                } else if opaque && distance < VIEW_RADIUS {
+                    blocked = true;
+                    let octant = (xx, xy, yx, yy);
+                    self.cast_light(
+                        visible,
+                        origin,
+                        distance + 1,
+                        start_slope,
+                        left_slope,
+                        octant,
+                    );
+                    next_start_slope = right_slope;
+                }
+            }
+            if blocked {
+                break;
+            }
+        }
+    }
+
+    fn is_on_map(&self, x: i32, y: i32) -> bool {
+        let num_x_tiles = (self.width / self.tile_size) as i32;
+        let num_y_tiles = (self.height / self.tile_size) as i32;
+        x >= 0 && y >= 0 && x < num_x_tiles && y < num_y_tiles
+    }
+}
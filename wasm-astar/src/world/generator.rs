@@ -0,0 +1,354 @@
+use crate::utils::Rng;
+
+use super::terrain::Terrain;
+
+// Maps to the ids passed to set_map_generator on the client side
+#[derive(Clone, Copy, PartialEq)]
+pub enum MapGenerator {
+    Scatter = 0,
+    Maze = 1,
+    Cave = 2,
+    Dungeon = 3,
+    Noise = 4,
+}
+
+#[derive(Clone, Copy)]
+pub struct Cell {
+    pub is_wall: bool,
+    pub terrain: Terrain,
+}
+
+impl Cell {
+    pub fn wall() -> Cell {
+        Cell {
+            is_wall: true,
+            terrain: Terrain::Ground,
+        }
+    }
+
+    pub fn open(terrain: Terrain) -> Cell {
+        Cell {
+            is_wall: false,
+            terrain,
+        }
+    }
+}
+
+impl MapGenerator {
+    pub fn from_id(id: i32) -> Option<MapGenerator> {
+        match id {
+            0 => Some(MapGenerator::Scatter),
+            1 => Some(MapGenerator::Maze),
+            2 => Some(MapGenerator::Cave),
+            3 => Some(MapGenerator::Dungeon),
+            4 => Some(MapGenerator::Noise),
+            _ => None,
+        }
+    }
+
+    // Cells in row order, num_x * num_y long. Not every open cell has to be
+    // reachable, the endpoints are picked inside the largest region.
+    pub fn generate(&self, num_x: usize, num_y: usize, rng: &mut Rng) -> Vec<Cell> {
+        match self {
+            MapGenerator::Scatter => scatter(num_x, num_y, rng),
+            MapGenerator::Maze => maze(num_x, num_y, rng),
+            MapGenerator::Cave => cave(num_x, num_y, rng),
+            MapGenerator::Dungeon => dungeon(num_x, num_y, rng),
+            MapGenerator::Noise => noise(num_x, num_y, rng),
+        }
+    }
+}
+
+// Ids of the biggest group of open cells connected through their sides.
+// Sides only, so anything in it is reachable with or without diagonals.
+pub fn largest_region(is_wall: &[bool], num_x: usize, num_y: usize) -> Vec<usize> {
+    let mut visited = vec![false; is_wall.len()];
+    let mut largest = Vec::new();
+    let mut region = Vec::new();
+    let mut stack = Vec::new();
+
+    for first in 0..is_wall.len() {
+        if is_wall[first] || visited[first] {
+            continue;
+        }
+        region.clear();
+        visited[first] = true;
+        stack.push(first);
+        while let Some(id) = stack.pop() {
+            region.push(id);
+            let (x, y) = (id % num_x, id / num_x);
+            let mut visit = |side: usize| {
+                if !is_wall[side] && !visited[side] {
+                    visited[side] = true;
+                    stack.push(side);
+                }
+            };
+            if x > 0 {
+                visit(id - 1);
+            }
+            if x + 1 < num_x {
+                visit(id + 1);
+            }
+            if y > 0 {
+                visit(id - num_x);
+            }
+            if y + 1 < num_y {
+                visit(id + num_x);
+            }
+        }
+        if region.len() > largest.len() {
+            std::mem::swap(&mut largest, &mut region);
+        }
+    }
+    largest
+}
+
+// Walls dropped with a 30% chance and random terrain, the original map.
+fn scatter(num_x: usize, num_y: usize, rng: &mut Rng) -> Vec<Cell> {
+    (0..num_x * num_y)
+        .map(|_| {
+            if rng.random() >= 0.7 {
+                Cell::wall()
+            } else {
+                Cell::open(Terrain::from_random(rng.random()))
+            }
+        })
+        .collect()
+}
+
+// Recursive backtracker on the odd cells, carving the wall between a cell
+// and a random unvisited neighbour. Produces a perfect maze (one path
+// between any two cells).
+fn maze(num_x: usize, num_y: usize, rng: &mut Rng) -> Vec<Cell> {
+    let mut cells = vec![Cell::wall(); num_x * num_y];
+    let maze_x = num_x.saturating_sub(1) / 2;
+    let maze_y = num_y.saturating_sub(1) / 2;
+    if maze_x == 0 || maze_y == 0 {
+        return cells;
+    }
+    let cell_id = |mx: usize, my: usize| (my * 2 + 1) * num_x + mx * 2 + 1;
+
+    let mut visited = vec![false; maze_x * maze_y];
+    let mut stack = vec![(0, 0)];
+    visited[0] = true;
+    cells[cell_id(0, 0)] = Cell::open(Terrain::Ground);
+
+    while let Some(&(mx, my)) = stack.last() {
+        let mut options = [(0, 0); 4];
+        let mut num_options = 0;
+        for (dx, dy) in [(0, -1), (0, 1), (-1, 0), (1, 0)] {
+            let nx = mx as i32 + dx;
+            let ny = my as i32 + dy;
+            if nx >= 0
+                && ny >= 0
+                && (nx as usize) < maze_x
+                && (ny as usize) < maze_y
+                && !visited[ny as usize * maze_x + nx as usize]
+            {
+                options[num_options] = (nx as usize, ny as usize);
+                num_options += 1;
+            }
+        }
+        if num_options == 0 {
+            stack.pop();
+            continue;
+        }
+
+        let (nx, ny) = options[rng.random_range(0, num_options as i32 - 1) as usize];
+        visited[ny * maze_x + nx] = true;
+        let (from, to) = (cell_id(mx, my), cell_id(nx, ny));
+        cells[(from + to) / 2] = Cell::open(Terrain::Ground);
+        cells[to] = Cell::open(Terrain::Ground);
+        stack.push((nx, ny));
+    }
+    cells
+}
+
+// Random fill smoothed by the 4-5 cellular automata rule, then every pocket
+// that isn't part of the main cave is filled in.
+fn cave(num_x: usize, num_y: usize, rng: &mut Rng) -> Vec<Cell> {
+    let mut is_wall: Vec<bool> = (0..num_x * num_y).map(|_| rng.random() < 0.45).collect();
+    let mut next = is_wall.clone();
+
+    for _ in 0..5 {
+        for y in 0..num_y {
+            for x in 0..num_x {
+                // Off the map counts as wall so caves close at the edges.
+                let mut walls = 0;
+                for dy in -1..=1 {
+                    for dx in -1..=1 {
+                        let nx = x as i32 + dx;
+                        let ny = y as i32 + dy;
+                        if nx < 0
+                            || ny < 0
+                            || nx as usize >= num_x
+                            || ny as usize >= num_y
+                            || is_wall[ny as usize * num_x + nx as usize]
+                        {
+                            walls += 1;
+                        }
+                    }
+                }
+                next[y * num_x + x] = walls >= 5;
+            }
+        }
+        std::mem::swap(&mut is_wall, &mut next);
+    }
+
+    let mut cells = vec![Cell::wall(); num_x * num_y];
+    for id in largest_region(&is_wall, num_x, num_y) {
+        let terrain = if rng.random() < 0.1 {
+            Terrain::Mud
+        } else {
+            Terrain::Ground
+        };
+        cells[id] = Cell::open(terrain);
+    }
+    cells
+}
+
+// Non-overlapping rooms, each joined to the previous one by an L-shaped
+// road, so every room is reachable from every other.
+fn dungeon(num_x: usize, num_y: usize, rng: &mut Rng) -> Vec<Cell> {
+    let mut cells = vec![Cell::wall(); num_x * num_y];
+    let max_size = (num_x.min(num_y) / 4).max(3) as i32;
+    let mut rooms: Vec<(i32, i32, i32, i32)> = Vec::new();
+
+    for _ in 0..(num_x * num_y / 60).max(4) {
+        let w = rng.random_range(3, max_size);
+        let h = rng.random_range(3, max_size);
+        if w + 2 > num_x as i32 || h + 2 > num_y as i32 {
+            continue;
+        }
+        let x = rng.random_range(1, num_x as i32 - w - 1);
+        let y = rng.random_range(1, num_y as i32 - h - 1);
+        // Keep a wall between rooms.
+        let overlaps = rooms.iter().any(|&(rx, ry, rw, rh)| {
+            x <= rx + rw && rx <= x + w && y <= ry + rh && ry <= y + h
+        });
+        if overlaps {
+            continue;
+        }
+
+        for cy in y..y + h {
+            for cx in x..x + w {
+                cells[cy as usize * num_x + cx as usize] = Cell::open(Terrain::Ground);
+            }
+        }
+        if let Some(&(px, py, pw, ph)) = rooms.last() {
+            let (x1, y1) = (px + pw / 2, py + ph / 2);
+            let (x2, y2) = (x + w / 2, y + h / 2);
+            // Horizontal leg first or vertical first, picked at random.
+            let corner = if rng.random() < 0.5 { (x2, y1) } else { (x1, y2) };
+            carve_corridor(&mut cells, num_x, (x1, y1), corner);
+            carve_corridor(&mut cells, num_x, corner, (x2, y2));
+        }
+        rooms.push((x, y, w, h));
+    }
+    cells
+}
+
+// Straight line of road between two points in the same row or column.
+// Room floors it passes through are left as they are.
+fn carve_corridor(cells: &mut [Cell], num_x: usize, from: (i32, i32), to: (i32, i32)) {
+    let (mut x, mut y) = from;
+    loop {
+        let id = y as usize * num_x + x as usize;
+        if cells[id].is_wall {
+            cells[id] = Cell::open(Terrain::Road);
+        }
+        if (x, y) == to {
+            break;
+        }
+        x += (to.0 - x).signum();
+        y += (to.1 - y).signum();
+    }
+}
+
+// Two octaves of Perlin noise read as a height map: low ground floods into
+// water and mud, the highest ground becomes impassable rock.
+fn noise(num_x: usize, num_y: usize, rng: &mut Rng) -> Vec<Cell> {
+    let perlin = Perlin::new(rng);
+    let scale = 8_f32;
+    let mut cells = Vec::with_capacity(num_x * num_y);
+    for y in 0..num_y {
+        for x in 0..num_x {
+            let (fx, fy) = (x as f32 / scale, y as f32 / scale);
+            let height = perlin.noise(fx, fy) * 0.75 + perlin.noise(fx * 2.0, fy * 2.0) * 0.25;
+            // Roughly [-1, 1] to [0, 1].
+            let height = (height + 1.0) / 2.0;
+            cells.push(if height > 0.66 {
+                Cell::wall()
+            } else if height < 0.3 {
+                Cell::open(Terrain::Water)
+            } else if height < 0.38 {
+                Cell::open(Terrain::Mud)
+            } else {
+                Cell::open(Terrain::Ground)
+            });
+        }
+    }
+    cells
+}
+
+// Classic gradient noise with a shuffled permutation table, so the seed
+// decides the landscape.
+struct Perlin {
+    perm: [u8; 512],
+}
+
+impl Perlin {
+    fn new(rng: &mut Rng) -> Perlin {
+        let mut table = [0_u8; 256];
+        for (i, v) in table.iter_mut().enumerate() {
+            *v = i as u8;
+        }
+        for i in (1..256).rev() {
+            let j = rng.random_range(0, i as i32) as usize;
+            table.swap(i, j);
+        }
+        let mut perm = [0_u8; 512];
+        for (i, v) in perm.iter_mut().enumerate() {
+            *v = table[i & 255];
+        }
+        Perlin { perm }
+    }
+
+    fn noise(&self, x: f32, y: f32) -> f32 {
+        let (x0, y0) = (x.floor(), y.floor());
+        let (xi, yi) = ((x0 as i32 & 255) as usize, (y0 as i32 & 255) as usize);
+        let (xf, yf) = (x - x0, y - y0);
+        let (u, v) = (fade(xf), fade(yf));
+
+        let p = &self.perm;
+        let aa = p[p[xi] as usize + yi];
+        let ab = p[p[xi] as usize + yi + 1];
+        let ba = p[p[xi + 1] as usize + yi];
+        let bb = p[p[xi + 1] as usize + yi + 1];
+
+        let top = lerp(grad(aa, xf, yf), grad(ba, xf - 1.0, yf), u);
+        let bottom = lerp(grad(ab, xf, yf - 1.0), grad(bb, xf - 1.0, yf - 1.0), u);
+        lerp(top, bottom, v)
+    }
+}
+
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+    a + t * (b - a)
+}
+
+fn grad(hash: u8, x: f32, y: f32) -> f32 {
+    match hash & 7 {
+        0 => x + y,
+        1 => -x + y,
+        2 => x - y,
+        3 => -x - y,
+        4 => x,
+        5 => -x,
+        6 => y,
+        _ => -y,
+    }
+}
@@ -0,0 +1,516 @@
+use std::collections::{BinaryHeap, HashMap, HashSet};
+
+use super::astar::{NodeState, OpenNode};
+use super::tile::MOVE_COST;
+use super::{Tile, WorldState};
+
+// Sectors are SECTOR_SIZE tiles square, the last column and row of sectors
+// can be smaller.
+const SECTOR_SIZE: i32 = 10;
+// Openings between two sectors up to this wide get one crossing in the
+// middle, wider ones get one at each end.
+const MAX_SINGLE_CROSSING: usize = 6;
+
+// Abstract graph for hierarchical search (HPA*). The map is split into
+// sectors, the tiles a path can cross from one sector into the next are
+// entrances, and the cheapest way between every two entrances of a sector
+// is worked out up front. A query then searches a few nodes per sector and
+// only runs tile searches in the sectors the path goes through.
+pub struct Hierarchy {
+    // Costs inside the sectors depend on whether diagonal moves are allowed.
+    allow_diagonal: bool,
+    sectors_x: usize,
+    sectors_y: usize,
+    // Crossings (tile, tile in the next sector) from each sector to the one
+    // on its right at sector * 2, and to the one below at sector * 2 + 1.
+    crossings: Vec<Vec<(usize, usize)>>,
+    sectors: Vec<Sector>,
+}
+
+struct Sector {
+    entrances: Vec<usize>,
+    // costs[i * n + j] is the cheapest path from entrance i to entrance j
+    // without leaving the sector, i32::MAX if there is none.
+    costs: Vec<i32>,
+}
+
+// Dijkstra over the tiles of one sector, indexed by position in the sector.
+struct SectorPaths {
+    x0: i32,
+    y0: i32,
+    width: i32,
+    dist: Vec<i32>,
+    // Previous tile on the path from the origin, or for reverse searches the
+    // next tile on the way to it. -1 for the origin and unreached tiles.
+    link: Vec<i32>,
+}
+
+impl SectorPaths {
+    fn index(&self, t: &Tile) -> usize {
+        ((t.y_id - self.y0) * self.width + t.x_id - self.x0) as usize
+    }
+
+    fn dist_to(&self, t: &Tile) -> i32 {
+        self.dist[self.index(t)]
+    }
+
+    fn link_of(&self, t: &Tile) -> usize {
+        self.link[self.index(t)] as usize
+    }
+}
+
+impl WorldState {
+    // HPA* from start_id to end_id. Leaves the path in the parent_id chain
+    // like the other algorithms. Paths that leave a sector go through its
+    // entrances, so they can be longer than the cheapest one.
+    pub(super) fn search_hierarchical(&mut self) {
+        if self
+            .hierarchy
+            .as_ref()
+            .is_none_or(|h| h.allow_diagonal != self.allow_diagonal)
+        {
+            self.hierarchy = Some(self.build_hierarchy());
+        }
+        let Some(h) = self.hierarchy.take() else {
+            return;
+        };
+        if let Some(path) = self.hierarchical_path(&h) {
+            self.link_path(&path);
+        }
+        self.hierarchy = Some(h);
+        self.scratch.done = true;
+    }
+
+    // Rebuilds the part of the hierarchy around a tile that was walled or
+    // opened. Only the sectors touched by its relinked 3x3 block and their
+    // neighbours change.
+    pub(super) fn update_hierarchy(&mut self, id: usize) {
+        let Some(mut h) = self.hierarchy.take() else {
+            return;
+        };
+        if h.allow_diagonal != self.allow_diagonal {
+            // Rebuilt from scratch by the next search.
+            return;
+        }
+        let (num_x, num_y) = self.num_tiles();
+        let (x, y) = (self.tiles[id].x_id, self.tiles[id].y_id);
+        let mut changed = Vec::new();
+        for ty in (y - 1).max(0)..=(y + 1).min(num_y - 1) {
+            for tx in (x - 1).max(0)..=(x + 1).min(num_x - 1) {
+                let sector = self.sector_at(&h, tx, ty);
+                if !changed.contains(&sector) {
+                    changed.push(sector);
+                }
+            }
+        }
+
+        let mut dirty = changed.clone();
+        let mut add_dirty = |sector: usize| {
+            if !dirty.contains(&sector) {
+                dirty.push(sector);
+            }
+        };
+        for &sector in changed.iter() {
+            let (sx, sy) = (sector % h.sectors_x, sector / h.sectors_x);
+            let mut borders = vec![sector * 2, sector * 2 + 1];
+            if sx > 0 {
+                borders.push((sector - 1) * 2);
+                add_dirty(sector - 1);
+            }
+            if sy > 0 {
+                borders.push((sector - h.sectors_x) * 2 + 1);
+                add_dirty(sector - h.sectors_x);
+            }
+            if sx + 1 < h.sectors_x {
+                add_dirty(sector + 1);
+            }
+            if sy + 1 < h.sectors_y {
+                add_dirty(sector + h.sectors_x);
+            }
+            for border in borders {
+                h.crossings[border] = self.find_crossings(&h, border / 2, border % 2 == 1);
+            }
+        }
+        for sector in dirty {
+            h.sectors[sector] = self.build_sector(&h, sector);
+        }
+        self.hierarchy = Some(h);
+    }
+
+    fn build_hierarchy(&self) -> Hierarchy {
+        let (num_x, num_y) = self.num_tiles();
+        let sectors_x = ((num_x + SECTOR_SIZE - 1) / SECTOR_SIZE) as usize;
+        let sectors_y = ((num_y + SECTOR_SIZE - 1) / SECTOR_SIZE) as usize;
+        let num_sectors = sectors_x * sectors_y;
+        let mut h = Hierarchy {
+            allow_diagonal: self.allow_diagonal,
+            sectors_x,
+            sectors_y,
+            crossings: Vec::with_capacity(num_sectors * 2),
+            sectors: Vec::with_capacity(num_sectors),
+        };
+        for sector in 0..num_sectors {
+            let right = self.find_crossings(&h, sector, false);
+            let down = self.find_crossings(&h, sector, true);
+            h.crossings.push(right);
+            h.crossings.push(down);
+        }
+        for sector in 0..num_sectors {
+            let built = self.build_sector(&h, sector);
+            h.sectors.push(built);
+        }
+        h
+    }
+
+    // Crossings over the right (or bottom) border of a sector. Every run of
+    // open tile pairs along the border is one opening.
+    fn find_crossings(&self, h: &Hierarchy, sector: usize, down: bool) -> Vec<(usize, usize)> {
+        let (num_x, num_y) = self.num_tiles();
+        let (x0, y0, x1, y1) = self.sector_bounds(h, sector);
+        let mut crossings = Vec::new();
+        let border: Vec<(i32, i32)> = if down {
+            if y1 >= num_y {
+                return crossings;
+            }
+            (x0..x1).map(|x| (x, y1 - 1)).collect()
+        } else {
+            if x1 >= num_x {
+                return crossings;
+            }
+            (y0..y1).map(|y| (x1 - 1, y)).collect()
+        };
+        let (x_dir, y_dir) = if down { (0, 1) } else { (1, 0) };
+
+        let mut run = Vec::new();
+        for (i, &(x, y)) in border.iter().enumerate() {
+            let id = self.get_tile_id_at(x as u32, y as u32);
+            // Walls keep their links to open tiles, only open ones count.
+            let side = if self.is_blocked(id) {
+                -1
+            } else {
+                self.tiles[id].side(x_dir, y_dir)
+            };
+            if side >= 0 {
+                run.push((id, side as usize));
+            }
+            if (side < 0 || i + 1 == border.len()) && !run.is_empty() {
+                if run.len() <= MAX_SINGLE_CROSSING {
+                    crossings.push(run[run.len() / 2]);
+                } else {
+                    crossings.push(run[0]);
+                    crossings.push(run[run.len() - 1]);
+                }
+                run.clear();
+            }
+        }
+        crossings
+    }
+
+    fn build_sector(&self, h: &Hierarchy, sector: usize) -> Sector {
+        let (sx, sy) = (sector % h.sectors_x, sector / h.sectors_x);
+        let mut entrances = Vec::new();
+        let mut add = |id: usize| {
+            if !entrances.contains(&id) {
+                entrances.push(id);
+            }
+        };
+        for &(id, _) in h.crossings[sector * 2]
+            .iter()
+            .chain(h.crossings[sector * 2 + 1].iter())
+        {
+            add(id);
+        }
+        if sx > 0 {
+            for &(_, id) in h.crossings[(sector - 1) * 2].iter() {
+                add(id);
+            }
+        }
+        if sy > 0 {
+            for &(_, id) in h.crossings[(sector - h.sectors_x) * 2 + 1].iter() {
+                add(id);
+            }
+        }
+
+        let bounds = self.sector_bounds(h, sector);
+        let n = entrances.len();
+        let mut costs = vec![i32::MAX; n * n];
+        for (i, &from) in entrances.iter().enumerate() {
+            let paths = self.sector_search(bounds, from, false);
+            for (j, &to) in entrances.iter().enumerate() {
+                costs[i * n + j] = paths.dist_to(&self.tiles[to]);
+            }
+        }
+        Sector { entrances, costs }
+    }
+
+    // Search over the entrances, with the start and goal joined to the
+    // entrances of their own sectors. Returns the refined tile path.
+    fn hierarchical_path(&mut self, h: &Hierarchy) -> Option<Vec<usize>> {
+        let (start, goal) = (self.start_id as usize, self.end_id as usize);
+        if start == goal {
+            return Some(vec![start]);
+        }
+        let start_bounds = self.sector_bounds(h, self.sector_of(h, start));
+        let goal_bounds = self.sector_bounds(h, self.sector_of(h, goal));
+        let from_start = self.sector_search(start_bounds, start, false);
+        let to_goal = self.sector_search(goal_bounds, goal, true);
+
+        // Abstract node (a tile) to its G and parent.
+        let mut best: HashMap<usize, (i32, usize)> = HashMap::new();
+        let mut closed = HashSet::new();
+        let mut open = BinaryHeap::new();
+        let mut edges = Vec::new();
+        best.insert(start, (0, usize::MAX));
+        let h_start = self.tiles[start].h;
+        open.push(OpenNode {
+            f: h_start,
+            h: h_start,
+            id: start,
+        });
+        while let Some(node) = open.pop() {
+            let id = node.id;
+            if !closed.insert(id) {
+                continue;
+            }
+            self.expanded_nodes += 1;
+            self.scratch.state[id] = NodeState::Closed;
+            if id == goal {
+                let mut nodes = Vec::new();
+                let mut n = goal;
+                while n != usize::MAX {
+                    nodes.push(n);
+                    n = best[&n].1;
+                }
+                nodes.reverse();
+                return Some(self.refine_path(h, &nodes, &from_start, &to_goal));
+            }
+
+            let g = best[&id].0;
+            edges.clear();
+            self.abstract_edges(h, id, &from_start, &to_goal, &mut edges);
+            for &(next_id, cost) in edges.iter() {
+                if closed.contains(&next_id) {
+                    continue;
+                }
+                let next_g = g + cost;
+                if best.get(&next_id).is_some_and(|&(b, _)| b <= next_g) {
+                    continue;
+                }
+                best.insert(next_id, (next_g, id));
+                self.scratch.state[next_id] = NodeState::Open;
+                let h = self.tiles[next_id].h;
+                open.push(OpenNode {
+                    f: next_g + h,
+                    h,
+                    id: next_id,
+                });
+            }
+        }
+        None
+    }
+
+    // Neighbours of an abstract node with the cost of getting there.
+    fn abstract_edges(
+        &self,
+        h: &Hierarchy,
+        id: usize,
+        from_start: &SectorPaths,
+        to_goal: &SectorPaths,
+        edges: &mut Vec<(usize, i32)>,
+    ) {
+        let (start, goal) = (self.start_id as usize, self.end_id as usize);
+        let sector = self.sector_of(h, id);
+        let s = &h.sectors[sector];
+        if id == start {
+            for &e in s.entrances.iter() {
+                let d = from_start.dist_to(&self.tiles[e]);
+                if e != id && d != i32::MAX {
+                    edges.push((e, d));
+                }
+            }
+        } else if let Some(i) = s.entrances.iter().position(|&e| e == id) {
+            let n = s.entrances.len();
+            for j in 0..n {
+                let cost = s.costs[i * n + j];
+                if j != i && cost != i32::MAX {
+                    edges.push((s.entrances[j], cost));
+                }
+            }
+        }
+
+        let (sx, sy) = (sector % h.sectors_x, sector / h.sectors_x);
+        let mut borders = vec![sector * 2, sector * 2 + 1];
+        if sx > 0 {
+            borders.push((sector - 1) * 2);
+        }
+        if sy > 0 {
+            borders.push((sector - h.sectors_x) * 2 + 1);
+        }
+        for border in borders {
+            for &(a, b) in h.crossings[border].iter() {
+                if a == id {
+                    edges.push((b, self.tiles[b].step_cost(MOVE_COST)));
+                } else if b == id {
+                    edges.push((a, self.tiles[a].step_cost(MOVE_COST)));
+                }
+            }
+        }
+
+        if sector == self.sector_of(h, goal) && id != goal {
+            let d = to_goal.dist_to(&self.tiles[id]);
+            if d != i32::MAX {
+                edges.push((goal, d));
+            }
+        }
+    }
+
+    // Turns the abstract path into tiles, searching only the sectors it
+    // passes through.
+    fn refine_path(
+        &self,
+        h: &Hierarchy,
+        nodes: &[usize],
+        from_start: &SectorPaths,
+        to_goal: &SectorPaths,
+    ) -> Vec<usize> {
+        let (start, goal) = (self.start_id as usize, self.end_id as usize);
+        let mut path = vec![nodes[0]];
+        // Position of every tile in the path. A path that comes back to a
+        // tile it already went through is cut short there, so the parent
+        // chain never loops.
+        let mut seen = HashMap::from([(nodes[0], 0)]);
+        let mut push = |path: &mut Vec<usize>, id: usize| {
+            if let Some(&i) = seen.get(&id) {
+                for removed in path.drain(i + 1..) {
+                    seen.remove(&removed);
+                }
+            } else {
+                seen.insert(id, path.len());
+                path.push(id);
+            }
+        };
+
+        for pair in nodes.windows(2) {
+            let (a, b) = (pair[0], pair[1]);
+            let sector = self.sector_of(h, a);
+            if sector != self.sector_of(h, b) {
+                push(&mut path, b);
+            } else if b == goal {
+                let mut id = a;
+                while id != goal {
+                    id = to_goal.link_of(&self.tiles[id]);
+                    push(&mut path, id);
+                }
+            } else {
+                let searched;
+                let paths = if a == start {
+                    from_start
+                } else {
+                    searched = self.sector_search(self.sector_bounds(h, sector), a, false);
+                    &searched
+                };
+                let mut back = Vec::new();
+                let mut id = b;
+                while id != a {
+                    back.push(id);
+                    id = paths.link_of(&self.tiles[id]);
+                }
+                for &id in back.iter().rev() {
+                    push(&mut path, id);
+                }
+            }
+        }
+        path
+    }
+
+    // Points every tile of the path at the one before it, with real G values.
+    fn link_path(&mut self, path: &[usize]) {
+        for pair in path.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let parent_g = self.tiles[from].g;
+            let step_cost = self.tiles[to].step_cost(self.side_move_cost(from, to));
+            self.tiles[to].parent_id = from as i32;
+            self.tiles[to].calc_f_g(parent_g, step_cost);
+        }
+    }
+
+    // Dijkstra from a tile without leaving the sector. Reverse searches give
+    // the cost of getting from every tile to the origin instead.
+    fn sector_search(
+        &self,
+        (x0, y0, x1, y1): (i32, i32, i32, i32),
+        from: usize,
+        reverse: bool,
+    ) -> SectorPaths {
+        let width = x1 - x0;
+        let len = (width * (y1 - y0)) as usize;
+        let mut paths = SectorPaths {
+            x0,
+            y0,
+            width,
+            dist: vec![i32::MAX; len],
+            link: vec![-1; len],
+        };
+        let mut open = BinaryHeap::new();
+        let i = paths.index(&self.tiles[from]);
+        paths.dist[i] = 0;
+        open.push(OpenNode {
+            f: 0,
+            h: 0,
+            id: from,
+        });
+        while let Some(node) = open.pop() {
+            let t = &self.tiles[node.id];
+            if node.f > paths.dist_to(t) {
+                continue;
+            }
+            for (s, move_cost) in self.linked_sides(node.id) {
+                let side = &self.tiles[s];
+                if side.x_id < x0 || side.x_id >= x1 || side.y_id < y0 || side.y_id >= y1 {
+                    continue;
+                }
+                let step_cost = if reverse {
+                    t.step_cost(move_cost)
+                } else {
+                    side.step_cost(move_cost)
+                };
+                let d = node.f + step_cost;
+                let i = paths.index(side);
+                if d < paths.dist[i] {
+                    paths.dist[i] = d;
+                    paths.link[i] = node.id as i32;
+                    open.push(OpenNode { f: d, h: 0, id: s });
+                }
+            }
+        }
+        paths
+    }
+
+    fn num_tiles(&self) -> (i32, i32) {
+        (
+            (self.width / self.tile_size) as i32,
+            (self.height / self.tile_size) as i32,
+        )
+    }
+
+    fn sector_at(&self, h: &Hierarchy, x: i32, y: i32) -> usize {
+        (y / SECTOR_SIZE) as usize * h.sectors_x + (x / SECTOR_SIZE) as usize
+    }
+
+    fn sector_of(&self, h: &Hierarchy, id: usize) -> usize {
+        self.sector_at(h, self.tiles[id].x_id, self.tiles[id].y_id)
+    }
+
+    // Tile range of a sector, end exclusive: (x0, y0, x1, y1).
+    fn sector_bounds(&self, h: &Hierarchy, sector: usize) -> (i32, i32, i32, i32) {
+        let (num_x, num_y) = self.num_tiles();
+        let x0 = (sector % h.sectors_x) as i32 * SECTOR_SIZE;
+        let y0 = (sector / h.sectors_x) as i32 * SECTOR_SIZE;
+        (
+            x0,
+            y0,
+            (x0 + SECTOR_SIZE).min(num_x),
+            (y0 + SECTOR_SIZE).min(num_y),
+        )
+    }
+}
@@ -0,0 +1,270 @@
+use serde::{Deserialize, Serialize};
+
+use crate::utils::Rng;
+
+use super::generator::Cell;
+use super::terrain::Terrain;
+use super::{build_tiles, WorldState};
+
+// Limits on imported maps, far beyond anything the canvas can show but small
+// enough that the tile count and canvas size can't overflow.
+const MAX_MAP_SIDE: usize = 1024;
+const MAX_TILE_SIZE: u32 = 256;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum MapFormat {
+    // Rows of comma separated 0 (open) / 1 (wall), like load_test_map.
+    Csv,
+    // Dimensions, tile size, endpoints and a cost per tile, see JsonMap.
+    Json,
+}
+
+impl MapFormat {
+    pub fn from_name(name: &str) -> Option<MapFormat> {
+        match name {
+            "csv" => Some(MapFormat::Csv),
+            "json" => Some(MapFormat::Json),
+            _ => None,
+        }
+    }
+}
+
+// Width and height are in tiles. Costs are the terrain weights in row
+// order (7 road, 10 ground, 30 mud, 50 water) with 0 for walls.
+#[derive(Serialize, Deserialize)]
+struct JsonMap {
+    width: usize,
+    height: usize,
+    tile_size: u32,
+    start_id: usize,
+    end_id: usize,
+    costs: Vec<i32>,
+}
+
+// A parsed map before it is turned into tiles. CSV maps don't carry a tile
+// size or endpoints, so those fall back to the current world.
+struct MapData {
+    num_x: usize,
+    num_y: usize,
+    tile_size: Option<u32>,
+    endpoints: Option<(usize, usize)>,
+    cells: Vec<Cell>,
+}
+
+impl WorldState {
+    // Replaces the current map. Nothing changes if the data doesn't validate.
+    pub fn import_map(&mut self, data: &str, format: MapFormat) -> Result<(), String> {
+        let map = match format {
+            MapFormat::Csv => parse_csv(data)?,
+            MapFormat::Json => parse_json(data)?,
+        };
+
+        let tile_size = map.tile_size.unwrap_or(self.tile_size);
+        let (width, height) = canvas_size(map.num_x, map.num_y, tile_size)?;
+
+        self.tile_size = tile_size;
+        self.width = width;
+        self.height = height;
+        self.tiles = build_tiles(
+            map.num_x,
+            map.num_y,
+            self.tile_size,
+            self.topology,
+            &map.cells,
+        );
+        self.dirty_tiles.clear();
+        self.hierarchy = None;
+        self.dstar = None;
+        self.flow_field = None;
+        self.reset_fog();
+        self.map_changed = true;
+        match map.endpoints {
+            Some((start_id, end_id)) => {
+                self.start_id = start_id as i32;
+                self.end_id = end_id as i32;
+                self.place_player_on(start_id);
+            }
+            None => self.set_target_tiles(&mut Rng::new(self.seed)),
+        }
+        self.set_all_tile_sides();
+        self.set_start_node();
+        self.set_agent_count(self.agents.len());
+        if self.step_mode {
+            self.begin_search();
+        }
+        Ok(())
+    }
+
+    pub fn export_map(&self, format: MapFormat) -> String {
+        let num_x = (self.width / self.tile_size) as usize;
+        match format {
+            MapFormat::Csv => {
+                let mut map = String::new();
+                for row in self.tiles.chunks(num_x) {
+                    let cols: Vec<&str> = row
+                        .iter()
+                        .map(|t| if t.is_wall { "1" } else { "0" })
+                        .collect();
+                    map.push_str(&cols.join(","));
+                    map.push('\n');
+                }
+                map
+            }
+            MapFormat::Json => {
+                let map = JsonMap {
+                    width: num_x,
+                    height: self.tiles.len() / num_x,
+                    tile_size: self.tile_size,
+                    start_id: self.start_id as usize,
+                    end_id: self.end_id as usize,
+                    costs: self
+                        .tiles
+                        .iter()
+                        .map(|t| if t.is_wall { 0 } else { t.terrain.weight() })
+                        .collect(),
+                };
+                // Only plain numbers in there, serializing can't fail.
+                serde_json::to_string(&map).unwrap()
+            }
+        }
+    }
+}
+
+fn parse_csv(map: &str) -> Result<MapData, String> {
+    let mut cells = Vec::new();
+    let mut num_x = 0;
+    let mut num_y = 0;
+
+    for (y, row) in map
+        .lines()
+        .map(str::trim)
+        .filter(|r| !r.is_empty())
+        .enumerate()
+    {
+        // Trailing commas are fine, print_map used to write them.
+        let row = row.strip_suffix(',').unwrap_or(row);
+        let cols: Vec<&str> = row.split(',').map(str::trim).collect();
+        if y == 0 {
+            num_x = cols.len();
+            if num_x > MAX_MAP_SIDE {
+                return Err(format!(
+                    "Map is {} columns wide, at most {} are allowed",
+                    num_x, MAX_MAP_SIDE
+                ));
+            }
+        } else if y == MAX_MAP_SIDE {
+            return Err(format!("Map has more than {} rows", MAX_MAP_SIDE));
+        } else if cols.len() != num_x {
+            return Err(format!(
+                "Row {} has {} columns, expected {}",
+                y + 1,
+                cols.len(),
+                num_x
+            ));
+        }
+        for (x, col) in cols.iter().enumerate() {
+            cells.push(match *col {
+                "0" => Cell::open(Terrain::Ground),
+                "1" => Cell::wall(),
+                _ => {
+                    return Err(format!(
+                        "Row {}, column {}: expected 0 or 1, got '{}'",
+                        y + 1,
+                        x + 1,
+                        col
+                    ))
+                }
+            });
+        }
+        num_y += 1;
+    }
+
+    if num_y == 0 {
+        return Err(String::from("Map is empty"));
+    }
+    Ok(MapData {
+        num_x,
+        num_y,
+        tile_size: None,
+        endpoints: None,
+        cells,
+    })
+}
+
+fn parse_json(map: &str) -> Result<MapData, String> {
+    let map: JsonMap = serde_json::from_str(map).map_err(|e| format!("Invalid map JSON: {}", e))?;
+
+    if map.width == 0 || map.height == 0 {
+        return Err(String::from("Map width and height must be at least 1"));
+    }
+    if map.width > MAX_MAP_SIDE || map.height > MAX_MAP_SIDE {
+        return Err(format!(
+            "A {}x{} map is too large, at most {} tiles per side are allowed",
+            map.width, map.height, MAX_MAP_SIDE
+        ));
+    }
+    if map.tile_size == 0 || map.tile_size > MAX_TILE_SIZE {
+        return Err(format!(
+            "Tile size must be between 1 and {}, got {}",
+            MAX_TILE_SIZE, map.tile_size
+        ));
+    }
+    let num_tiles = map
+        .width
+        .checked_mul(map.height)
+        .ok_or_else(|| format!("A {}x{} map is too large", map.width, map.height))?;
+    if map.costs.len() != num_tiles {
+        return Err(format!(
+            "Expected {} costs for a {}x{} map, got {}",
+            num_tiles,
+            map.width,
+            map.height,
+            map.costs.len()
+        ));
+    }
+
+    let mut cells = Vec::with_capacity(num_tiles);
+    for (id, cost) in map.costs.iter().enumerate() {
+        if *cost == 0 {
+            cells.push(Cell::wall());
+            continue;
+        }
+        match Terrain::from_weight(*cost) {
+            Some(terrain) => cells.push(Cell::open(terrain)),
+            None => return Err(format!("Tile {}: unknown cost {}", id, cost)),
+        }
+    }
+
+    for (name, id) in [("start_id", map.start_id), ("end_id", map.end_id)] {
+        if id >= num_tiles {
+            return Err(format!("{} {} is outside the map", name, id));
+        }
+        if cells[id].is_wall {
+            return Err(format!("{} {} is a wall", name, id));
+        }
+    }
+
+    Ok(MapData {
+        num_x: map.width,
+        num_y: map.height,
+        tile_size: Some(map.tile_size),
+        endpoints: Some((map.start_id, map.end_id)),
+        cells,
+    })
+}
+
+// Canvas width and height in pixels.
+fn canvas_size(num_x: usize, num_y: usize, tile_size: u32) -> Result<(u32, u32), String> {
+    let pixels = |num_tiles: usize| {
+        u32::try_from(num_tiles)
+            .ok()
+            .and_then(|n| n.checked_mul(tile_size))
+    };
+    match (pixels(num_x), pixels(num_y)) {
+        (Some(width), Some(height)) => Ok((width, height)),
+        _ => Err(format!(
+            "A {}x{} map of {}px tiles is too large",
+            num_x, num_y, tile_size
+        )),
+    }
+}
@@ -1,10 +1,37 @@
-use std::collections::HashSet;
-
 use crate::engine::{Color, Transform};
-use crate::utils::{log_fmt, random, random_range};
-
+use crate::utils::Rng;
+
+mod agents;
+mod any_angle;
+mod astar;
+mod dstar_lite;
+mod edit;
+mod flow_field;
+mod fog;
+mod generator;
+mod hierarchy;
+mod map_format;
+mod search;
+mod terrain;
 mod tile;
+mod topology;
+pub use self::agents::{Agent, AGENT_STEP_MS, MAX_AGENTS};
+pub use self::astar::NodeState;
+use self::astar::SearchScratch;
+use self::dstar_lite::DStarLite;
+use self::edit::Brush;
+pub use self::flow_field::FlowField;
+pub use self::fog::{Fog, Visibility, VIEW_RADIUS};
+use self::generator::{largest_region, Cell};
+use self::hierarchy::Hierarchy;
+pub use self::generator::MapGenerator;
+pub use self::map_format::MapFormat;
+pub use self::search::SearchAlgorithm;
 pub use self::tile::Tile;
+pub use self::topology::Topology;
+
+// Replaced by a random seed from the client in wasm_init.
+const DEFAULT_SEED: u32 = 1;
 
 pub struct WorldState {
     pub debug: bool,
@@ -17,8 +44,68 @@ pub struct WorldState {
     pub start_id: i32,
     pub end_id: i32,
     pub player: Transform,
+    // Player position before the last fixed step, see player_position.
+    pub prev_player: (f64, f64),
     pub tiles: Vec<Tile>,
-    pub recent_regen: bool,
+    // Seed of the current map, see load_random_map.
+    pub seed: u32,
+    pub generator: MapGenerator,
+    // Shape of the tiles and which ones touch, see set_topology.
+    pub topology: Topology,
+    // Background tiles changed by editing since the last draw.
+    pub dirty_tiles: Vec<usize>,
+    brush: Brush,
+    // Agents walking to their own goals around each other, see agents.rs.
+    pub agents: Vec<Agent>,
+    // When the agents last moved a tile.
+    pub agent_step_time: f64,
+    agent_rng: Rng,
+    // Walls changed since the agents were planned. They're planned again on
+    // their next update, not once for every tile of a brush stroke.
+    agents_stale: bool,
+    // 8-connected movement with diagonal costs and an octile heuristic.
+    pub allow_diagonal: bool,
+    pub algorithm: SearchAlgorithm,
+    // Pull the path taut around corners before drawing, see any_angle.rs.
+    pub smooth_path: bool,
+    // Nodes closed by the last search, exported to compare search algorithms.
+    pub expanded_nodes: u32,
+    // Step-by-step visualization: advance the search a few nodes per frame
+    // instead of running it to completion every tick.
+    pub step_mode: bool,
+    pub steps_per_frame: u32,
+    pub step_paused: bool,
+    pending_steps: u32,
+    // Start, end and settings the running search was begun with.
+    search_origin: (i32, i32, SearchAlgorithm, bool),
+    // Walls changed or the map was replaced since then.
+    map_changed: bool,
+    // Every open tile had the same terrain when the search began, see
+    // active_algorithm.
+    uniform_terrain: bool,
+    scratch: SearchScratch,
+    // Sector graph for SearchAlgorithm::Hierarchical, built by the first
+    // search that needs it and dropped when the map is replaced.
+    hierarchy: Option<Hierarchy>,
+    // Planner state for SearchAlgorithm::DStarLite, reused between searches
+    // to the same goal.
+    dstar: Option<DStarLite>,
+    // Directions to end_id from every tile, dropped when the map changes.
+    flow_field: Option<FlowField>,
+    // Draw the flow field as arrows on the debug layer.
+    pub show_flow_field: bool,
+    // The arrows are out of date or the debug layer was cleared.
+    pub redraw_flow_field: bool,
+    // Fog of war, None when the whole map is known.
+    fog: Option<Fog>,
+    // The fog changed or its layer was cleared.
+    pub redraw_fog: bool,
+}
+
+impl Default for WorldState {
+    fn default() -> WorldState {
+        WorldState::new()
+    }
 }
 
 impl WorldState {
@@ -37,21 +124,101 @@ impl WorldState {
             quality,
             tile_size,
             tiles: Vec::new(),
+            seed: DEFAULT_SEED,
+            generator: MapGenerator::Scatter,
+            topology: Topology::Square,
             player: Transform::default(),
+            prev_player: (0_f64, 0_f64),
             start_id: -1,
             end_id: -1,
-            recent_regen: false,
+            dirty_tiles: Vec::new(),
+            brush: Brush::None,
+            agents: Vec::new(),
+            agent_step_time: 0_f64,
+            agents_stale: false,
+            agent_rng: Rng::new(DEFAULT_SEED),
+            allow_diagonal: false,
+            algorithm: SearchAlgorithm::AStar,
+            smooth_path: false,
+            expanded_nodes: 0,
+            step_mode: false,
+            steps_per_frame: 1,
+            step_paused: false,
+            pending_steps: 0,
+            search_origin: (-1, -1, SearchAlgorithm::AStar, false),
+            map_changed: false,
+            uniform_terrain: true,
+            scratch: SearchScratch::new(),
+            hierarchy: None,
+            dstar: None,
+            flow_field: None,
+            show_flow_field: false,
+            redraw_flow_field: false,
+            fog: None,
+            redraw_fog: false,
         };
-        w.reset();
+        w.regenerate();
         w
     }
 
+    // New map from the next seed in the sequence. The sequence itself is
+    // seeded, so regenerating N times from a known seed is reproducible too.
     pub fn reset(&mut self) {
+        self.seed = Rng::new(self.seed).next_u32();
+        self.regenerate();
+    }
+
+    pub fn set_seed(&mut self, seed: u32) {
+        self.seed = seed;
+        self.regenerate();
+    }
+
+    // Rebuilds the map for the current seed, e.g. after the canvas resized.
+    pub fn regenerate(&mut self) {
         self.load_random_map();
         // self.load_test_map();
+        self.set_agent_count(self.agents.len());
+        if self.step_mode {
+            self.begin_search();
+        }
     }
 
+    // Called every tick. Runs the whole search, or in step mode advances the
+    // running one, restarting it when the endpoints or settings changed.
+    pub fn update_search(&mut self) {
+        if !self.step_mode {
+            // Nothing moved and nothing was edited, the last path still holds.
+            if self.scratch.done && self.is_search_current() && !self.map_changed {
+                return;
+            }
+            if self.algorithm == SearchAlgorithm::DStarLite {
+                // Picks up from the last search instead of resetting the tiles.
+                self.mark_search_begun();
+                self.search_dstar_lite();
+            } else {
+                self.calc_path();
+            }
+            return;
+        }
+        if !self.is_search_current() {
+            self.begin_search();
+        }
+        let steps = if self.step_paused {
+            std::mem::take(&mut self.pending_steps)
+        } else {
+            self.steps_per_frame
+        };
+        self.step_search(steps);
+    }
+
+    // Queues expansions to run on the next ticks while paused.
+    pub fn queue_search_steps(&mut self, count: u32) {
+        self.pending_steps += count;
+    }
+
+    // One fixed step of keyboard or gamepad movement.
     pub fn update_player(&mut self, x_dir: i32, y_dir: i32) {
+        self.prev_player = (self.player.pos_x, self.player.pos_y);
         let new_x = self.player.pos_x + (7_f64 * x_dir as f64);
         let new_y = self.player.pos_y + (7_f64 * y_dir as f64);
         if new_x + (self.tile_size as f64) < self.width as f64 && new_x > 0_f64 {
@@ -62,55 +229,34 @@ impl WorldState {
         }
     }
 
-    pub fn set_start_node(&mut self) {
-        let half_tile = (self.tile_size / 2) as f64;
-        self.start_id = self
-            .get_tile_id_closest_to(self.player.pos_x - half_tile, self.player.pos_y - half_tile)
-            as i32;
+    // Where to draw the player, interpolation of the way from its position
+    // before the last fixed step to the current one.
+    pub fn player_position(&self, interpolation: f64) -> (f64, f64) {
+        let (prev_x, prev_y) = self.prev_player;
+        (
+            prev_x + (self.player.pos_x - prev_x) * interpolation,
+            prev_y + (self.player.pos_y - prev_y) * interpolation,
+        )
     }
 
-    pub fn calc_astar(&mut self) {
-        let mut open_nodes: Vec<usize> = Vec::new();
-        let mut closed_nodes = HashSet::new();
-
-        open_nodes.push(self.start_id as usize);
-        let mut current_node;
-        let end = self.tiles[self.end_id as usize].clone();
+    // Moves the player without easing, e.g. onto a new map.
+    pub(super) fn place_player(&mut self, x: f64, y: f64) {
+        self.player.pos_x = x;
+        self.player.pos_y = y;
+        self.prev_player = (x, y);
+    }
 
-        for t in self.tiles.iter_mut() {
-            t.reset(&end);
-        }
+    // Centres the player on a tile.
+    pub(super) fn place_player_on(&mut self, id: usize) {
+        let half_tile = (self.tile_size / 2) as f64;
+        let (x, y) = self.tiles[id].center();
+        self.place_player(x - half_tile, y - half_tile);
+    }
 
-        // Stop searching when either:
-        // 1) target is closed, in which case the path has been found
-        // 2) failed to find the target and the open list is empty (no path)
-        while !closed_nodes.contains(&(self.end_id as usize)) && open_nodes.len() > 0 {
-            // Find lowest F score
-            open_nodes.sort_by(|a, b| {
-                let a_f = &self.tiles[*a].f;
-                let b_f = &self.tiles[*b].f;
-                a_f.cmp(b_f)
-            });
-
-            current_node = open_nodes.swap_remove(0);
-            closed_nodes.insert(current_node);
-
-            let side_ids = vec![
-                self.tiles[current_node].top,
-                self.tiles[current_node].bottom,
-                self.tiles[current_node].right,
-                self.tiles[current_node].left,
-            ];
-
-            // Check each side node.
-            // If the side exists (id >= 0)
-            // If it's a wall, it's not set as a side so we don't need to worry about it.
-            for s in side_ids.iter() {
-                let id = *s as usize;
-                if *s >= 0 && !closed_nodes.contains(&id) {
-                    self.check_node(&mut open_nodes, current_node, id);
-                }
-            }
+    // Off the hex and isometric maps the start stays where it was.
+    pub fn set_start_node(&mut self) {
+        if let Some(id) = self.get_tile_id_closest_to(self.player.pos_x, self.player.pos_y) {
+            self.start_id = id as i32;
         }
     }
 
@@ -124,32 +270,8 @@ impl WorldState {
         if new_y + (self.tile_size as f64) < self.height as f64 && new_y > 0_f64 {
             self.player.pos_y = new_y;
         }
-    }
-
-    fn check_node(
-        &mut self,
-        open_nodes: &mut Vec<usize>,
-        curr_node_id: usize,
-        side_node_id: usize,
-    ) {
-        let id = side_node_id as usize;
-        let mut parent_id = 0;
-        let mut parent_g = -1;
-        // if it's not already on the open list
-        if !open_nodes.contains(&id) {
-            open_nodes.push(id);
-            parent_id = curr_node_id;
-            parent_g = self.tiles[parent_id].g;
-        }
-        // if it's already on the open list and the path is better (lower G value)
-        else if self.tiles[id].g > self.tiles[curr_node_id].g + tile::MOVE_COST {
-            parent_id = curr_node_id;
-            parent_g = self.tiles[parent_id].g;
-        }
-        if parent_g != -1 {
-            self.tiles[id].parent_id = parent_id as i32;
-            self.tiles[id].calc_f_g(parent_g);
-        }
+        // The mouse is followed directly, there is nothing to ease.
+        self.prev_player = (self.player.pos_x, self.player.pos_y);
     }
 
     #[allow(dead_code)]
@@ -159,13 +281,8 @@ impl WorldState {
     }
 
     #[allow(dead_code)]
-    fn get_random_tile(&mut self) -> Tile {
-        let num_x_tiles = (self.width / self.tile_size) as i32;
-        let num_y_tiles = (self.height / self.tile_size) as i32;
-        let index = self.get_tile_id_at(
-            random_range(0, num_x_tiles - 1) as u32,
-            random_range(0, num_y_tiles - 1) as u32,
-        );
+    fn get_random_tile(&mut self, rng: &mut Rng) -> Tile {
+        let index = self.get_random_tile_id(rng);
         self.tiles[index].clone()
     }
 
@@ -175,96 +292,141 @@ impl WorldState {
         index as usize
     }
 
-    fn get_tile_id_closest_to(&self, x: f64, y: f64) -> usize {
+    // Tile under the middle of a tile sized box with its top left at x, y.
+    fn get_tile_id_closest_to(&self, x: f64, y: f64) -> Option<usize> {
+        let half_tile = (self.tile_size / 2) as f64;
+        if self.topology != Topology::Square {
+            return self.get_tile_id_at_point(x + half_tile, y + half_tile);
+        }
         let size = self.tile_size as f64;
-        let x_id = (x / size).ceil() as u32;
-        let y_id = (y / size).ceil() as u32;
-        self.get_tile_id_at(x_id, y_id)
+        let x_id = ((x - half_tile) / size).ceil() as u32;
+        let y_id = ((y - half_tile) / size).ceil() as u32;
+        Some(self.get_tile_id_at(x_id, y_id))
     }
 
-    fn get_random_tile_id(&self) -> usize {
+    fn get_random_tile_id(&self, rng: &mut Rng) -> usize {
         let num_x_tiles = (self.width / self.tile_size) as i32;
         let num_y_tiles = (self.height / self.tile_size) as i32;
         self.get_tile_id_at(
-            random_range(0, num_x_tiles - 1) as u32,
-            random_range(0, num_y_tiles - 1) as u32,
+            rng.random_range(0, num_x_tiles - 1) as u32,
+            rng.random_range(0, num_y_tiles - 1) as u32,
         )
     }
 
-    fn set_target_tiles(&mut self) {
-        self.start_id = self.get_random_tile_id() as i32;
-        self.end_id = self.get_random_tile_id() as i32;
-        self.player.pos_x = self.tiles[self.start_id as usize].transform.pos_x;
-        self.player.pos_y = self.tiles[self.start_id as usize].transform.pos_y;
+    // Both endpoints come from the largest connected region, so there is
+    // always a path between them without having to search first.
+    fn set_target_tiles(&mut self, rng: &mut Rng) {
+        let num_x_tiles = (self.width / self.tile_size) as usize;
+        let num_y_tiles = (self.height / self.tile_size) as usize;
+        let is_wall: Vec<bool> = self.tiles.iter().map(|t| t.is_wall).collect();
+        let region = largest_region(&is_wall, num_x_tiles, num_y_tiles);
+        if region.is_empty() {
+            // Nothing but walls, open a single tile to stand on.
+            self.tiles[0].is_wall = false;
+            self.tiles[0].color = tile_color(&self.tiles[0]);
+            self.start_id = 0;
+            self.end_id = 0;
+        } else {
+            let last = region.len() as i32 - 1;
+            self.start_id = region[rng.random_range(0, last) as usize] as i32;
+            self.end_id = region[rng.random_range(0, last) as usize] as i32;
+        }
+        self.place_player_on(self.start_id as usize);
     }
 
     fn set_all_tile_sides(&mut self) {
+        for t_id in 0..self.tiles.len() {
+            self.set_tile_sides(t_id);
+        }
+    }
+
+    fn set_tile_sides(&mut self, t_id: usize) {
         let num_x_tiles = (self.width / self.tile_size) as i32;
         let num_y_tiles = (self.height / self.tile_size) as i32;
-        for t_id in 0..self.tiles.len() {
-            let x_id = self.tiles[t_id].x_id;
-            let y_id = self.tiles[t_id].y_id;
-            if x_id + 1 < num_x_tiles {
-                let right = y_id * num_x_tiles + x_id + 1;
-                if !self.tiles[right as usize].is_wall {
-                    self.tiles[t_id].right = right;
-                }
+        let x_id = self.tiles[t_id].x_id;
+        let y_id = self.tiles[t_id].y_id;
+        if self.topology == Topology::Hex {
+            self.set_hex_sides(t_id);
+            return;
+        }
+        self.tiles[t_id].clear_sides();
+        if x_id + 1 < num_x_tiles {
+            let right = y_id * num_x_tiles + x_id + 1;
+            if !self.is_blocked(right as usize) {
+                self.tiles[t_id].right = right;
             }
-            if x_id - 1 >= 0 {
-                let left = y_id * num_x_tiles + x_id - 1;
-                if !self.tiles[left as usize].is_wall {
-                    self.tiles[t_id].left = left;
-                }
+        }
+        if x_id > 0 {
+            let left = y_id * num_x_tiles + x_id - 1;
+            if !self.is_blocked(left as usize) {
+                self.tiles[t_id].left = left;
             }
+        }
 
-            if y_id - 1 >= 0 {
-                let top = ((y_id - 1) * num_x_tiles) + x_id;
-                if !self.tiles[top as usize].is_wall {
-                    self.tiles[t_id].top = top;
-                }
+        if y_id > 0 {
+            let top = ((y_id - 1) * num_x_tiles) + x_id;
+            if !self.is_blocked(top as usize) {
+                self.tiles[t_id].top = top;
             }
-            if y_id + 1 < num_y_tiles {
-                let bottom = ((y_id + 1) * num_x_tiles) + x_id;
-                if !self.tiles[bottom as usize].is_wall {
-                    self.tiles[t_id].bottom = bottom;
-                }
+        }
+        if y_id + 1 < num_y_tiles {
+            let bottom = ((y_id + 1) * num_x_tiles) + x_id;
+            if !self.is_blocked(bottom as usize) {
+                self.tiles[t_id].bottom = bottom;
             }
         }
-    }
 
-    #[allow(dead_code)]
-    fn print_map(&self) {
-        let num_y_tiles = self.height / self.tile_size;
-        let num_x_tiles = self.width / self.tile_size;
-        let mut map = String::from("");
-
-        for y in 0..num_y_tiles {
-            for x in 0..num_x_tiles {
-                let id = self.get_tile_id_at(x, y);
-                if self.tiles[id].is_wall {
-                    map = format!("{}{},", map, "1");
-                } else {
-                    map = format!("{}{},", map, "0");
-                }
+        // Diagonal sides are always linked, calc_astar decides whether to
+        // follow them. Squeezing between two walls that only touch at
+        // their corners isn't allowed.
+        for (x_dir, y_dir) in [(-1, -1), (1, -1), (-1, 1), (1, 1)] {
+            let x = x_id + x_dir;
+            let y = y_id + y_dir;
+            if x < 0 || x >= num_x_tiles || y < 0 || y >= num_y_tiles {
+                continue;
+            }
+            let diagonal = y * num_x_tiles + x;
+            let beside = y_id * num_x_tiles + x;
+            let above_or_below = y * num_x_tiles + x_id;
+            if self.is_blocked(diagonal as usize)
+                || (self.is_blocked(beside as usize) && self.is_blocked(above_or_below as usize))
+            {
+                continue;
+            }
+            let t = &mut self.tiles[t_id];
+            match (x_dir, y_dir) {
+                (-1, -1) => t.top_left = diagonal,
+                (1, -1) => t.top_right = diagonal,
+                (-1, 1) => t.bottom_left = diagonal,
+                _ => t.bottom_right = diagonal,
             }
-            map = format!("{}\n", map);
         }
-        log_fmt(map);
     }
 
+    // Everything random comes from the seed, so the same seed and canvas
+    // size always give the same map and endpoints.
     fn load_random_map(&mut self) {
-        let tile_sizes = vec![10, 20, 50];
-        self.tile_size = tile_sizes[random_range(0, (tile_sizes.len() - 1) as i32) as usize];
-        self.tiles = generate_tiles(self.width, self.height, self.tile_size);
+        let mut rng = Rng::new(self.seed);
+        let tile_sizes = [10, 20, 50];
+        self.tile_size = tile_sizes[rng.random_range(0, (tile_sizes.len() - 1) as i32) as usize];
+        self.tiles = generate_tiles(
+            self.width,
+            self.height,
+            self.tile_size,
+            self.topology,
+            self.generator,
+            &mut rng,
+        );
+        // The whole background is redrawn anyway.
+        self.dirty_tiles.clear();
+        self.hierarchy = None;
+        self.dstar = None;
+        self.flow_field = None;
+        self.map_changed = true;
+        self.reset_fog();
+        self.set_target_tiles(&mut rng);
         self.set_all_tile_sides();
-        self.set_target_tiles();
         self.set_start_node();
-        self.calc_astar();
-
-        // Force a new map if no path found.
-        if self.tiles[self.end_id as usize].parent_id == -1 {
-            self.reset();
-        }
     }
 
     #[allow(dead_code)]
@@ -295,71 +457,59 @@ impl WorldState {
             0,0,0,0,0,0,1,1,0,0,0,1,1,1,0,0,0,0,0,0,0,0,1,1,1,0,1,0,1,0,1,0,1,0,0,0,";
 
         self.tile_size = 50;
-        self.tiles = load_map(self.tile_size, test_map);
-        self.set_all_tile_sides();
+        self.import_map(test_map, MapFormat::Csv).unwrap();
         self.start_id = 418;
         self.end_id = 316;
-        self.player.pos_x = self.tiles[self.start_id as usize].transform.pos_x;
-        self.player.pos_y = self.tiles[self.start_id as usize].transform.pos_y;
+        self.place_player_on(self.start_id as usize);
+        self.set_start_node();
     }
 }
 
-#[allow(dead_code)]
-fn load_map(tile_size: u32, map: &str) -> Vec<Tile> {
-    let mut vec = Vec::new();
-    let rows: Vec<&str> = map.split_terminator("\n").collect();
-
-    for (y, row) in rows.iter().enumerate() {
-        let cols: Vec<&str> = row.trim().split_terminator(",").collect();
-        let num_cols = cols.len();
-        for (x, col) in cols.iter().enumerate() {
-            let px = x as f64 * tile_size as f64;
-            let py = y as f64 * tile_size as f64;
-            let size = tile_size as f64;
-            let mut t: Tile = Tile::new(px, py, size);
+fn generate_tiles(
+    grid_width: u32,
+    grid_height: u32,
+    tile_size: u32,
+    topology: Topology,
+    generator: MapGenerator,
+    rng: &mut Rng,
+) -> Vec<Tile> {
+    let num_y_tiles = (grid_height / tile_size) as usize;
+    let num_x_tiles = (grid_width / tile_size) as usize;
+    let cells = generator.generate(num_x_tiles, num_y_tiles, rng);
+    build_tiles(num_x_tiles, num_y_tiles, tile_size, topology, &cells)
+}
+
+// Cells are in row order, num_x * num_y long.
+fn build_tiles(
+    num_x: usize,
+    num_y: usize,
+    tile_size: u32,
+    topology: Topology,
+    cells: &[Cell],
+) -> Vec<Tile> {
+    let mut vec = Vec::with_capacity(num_x * num_y);
+    for y in 0..num_y {
+        for x in 0..num_x {
+            let (px, py, width, height) =
+                topology.tile_bounds(x as i32, y as i32, num_x, num_y, tile_size);
+            let mut t: Tile = Tile::new(px, py, width);
+            t.transform.scale_y = height;
             t.x_id = x as i32;
             t.y_id = y as i32;
-            t.node_id = (y * num_cols + x) as usize;
-            t.is_wall = {
-                if String::from(*col).eq("1") {
-                    true
-                } else {
-                    false
-                }
-            };
-            let lightness = if t.is_wall { 20 } else { 30 };
-            t.color = Color::new(0, 0, lightness, 1_f32);
+            t.node_id = y * num_x + x;
+            t.is_wall = cells[t.node_id].is_wall;
+            t.terrain = cells[t.node_id].terrain;
+            t.color = tile_color(&t);
             vec.push(t);
         }
     }
     vec
 }
 
-fn generate_tiles(grid_width: u32, grid_height: u32, tile_size: u32) -> Vec<Tile> {
-    let mut vec = Vec::new();
-    let num_y_tiles = grid_height / tile_size;
-    let num_x_tiles = grid_width / tile_size;
-
-    for y in 0..num_y_tiles {
-        for x in 0..num_x_tiles {
-            let px = x as f64 * tile_size as f64;
-            let py = y as f64 * tile_size as f64;
-            let size = tile_size as f64;
-            let mut t: Tile = Tile::new(px, py, size);
-            t.x_id = x as i32;
-            t.y_id = y as i32;
-            t.node_id = (y * num_x_tiles + x) as usize;
-            t.is_wall = {
-                if random() >= 0.7 {
-                    true
-                } else {
-                    false
-                }
-            };
-            let lightness = if t.is_wall { 20 } else { 30 };
-            t.color = Color::new(0, 0, lightness, 1_f32);
-            vec.push(t);
-        }
+fn tile_color(t: &Tile) -> Color {
+    if t.is_wall {
+        Color::new(0, 0, 20, 1_f32)
+    } else {
+        t.terrain.color()
     }
-    vec
 }
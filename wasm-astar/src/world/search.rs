@@ -0,0 +1,520 @@
+use super::astar::NodeState;
+use super::terrain::MIN_TERRAIN_WEIGHT;
+use super::tile::{Heuristic, DIAGONAL_MOVE_COST, MOVE_COST};
+use super::{Tile, Topology, WorldState};
+
+// Maps to the ids passed to set_search_algorithm on the client side
+#[derive(Clone, Copy, PartialEq)]
+pub enum SearchAlgorithm {
+    AStar = 0,
+    Dijkstra = 1,
+    GreedyBestFirst = 2,
+    BreadthFirst = 3,
+    JumpPoint = 4,
+    ThetaStar = 5,
+    Hierarchical = 6,
+    DStarLite = 7,
+}
+
+impl SearchAlgorithm {
+    pub fn from_id(id: i32) -> Option<SearchAlgorithm> {
+        match id {
+            0 => Some(SearchAlgorithm::AStar),
+            1 => Some(SearchAlgorithm::Dijkstra),
+            2 => Some(SearchAlgorithm::GreedyBestFirst),
+            3 => Some(SearchAlgorithm::BreadthFirst),
+            4 => Some(SearchAlgorithm::JumpPoint),
+            5 => Some(SearchAlgorithm::ThetaStar),
+            6 => Some(SearchAlgorithm::Hierarchical),
+            7 => Some(SearchAlgorithm::DStarLite),
+            _ => None,
+        }
+    }
+
+    // Key the open heap is ordered by, lowest first.
+    fn priority(&self, g: i32, h: i32) -> i32 {
+        match self {
+            SearchAlgorithm::Dijkstra => g,
+            SearchAlgorithm::GreedyBestFirst => h,
+            _ => g + h,
+        }
+    }
+}
+
+impl WorldState {
+    // Runs the selected algorithm from start_id to end_id. Every algorithm
+    // leaves the path in the tiles' parent_id chain with real (terrain
+    // weighted) G values, so drawing and stats don't care which one ran.
+    pub fn calc_path(&mut self) {
+        self.begin_search();
+        self.step_search(u32::MAX);
+    }
+
+    // Resets the tiles and opens the start node without expanding anything,
+    // so the search can be advanced a few nodes at a time with step_search.
+    pub fn begin_search(&mut self) {
+        let end = self.tiles[self.end_id as usize].clone();
+        let heuristic = self.heuristic();
+        for t in self.tiles.iter_mut() {
+            t.reset(&end, heuristic);
+        }
+        self.scratch.reset(self.tiles.len());
+        self.expanded_nodes = 0;
+        self.uniform_terrain = self.has_uniform_terrain();
+        self.mark_search_begun();
+
+        let algorithm = self.active_algorithm();
+        let start_id = self.start_id as usize;
+        let start = &self.tiles[start_id];
+        match algorithm {
+            SearchAlgorithm::BreadthFirst => self.scratch.enqueue(start_id),
+            _ => {
+                let priority = algorithm.priority(start.g, start.h);
+                let h = start.h;
+                self.scratch.push(start_id, priority, h);
+            }
+        }
+    }
+
+    // Expands up to max_expansions nodes. Returns true once the search is
+    // finished, either because the target was closed or nothing is left open.
+    pub fn step_search(&mut self, max_expansions: u32) -> bool {
+        let algorithm = self.active_algorithm();
+        // HPA* only expands a handful of abstract nodes and D* Lite mostly
+        // reuses its last search, so they always run to the end in one step.
+        if matches!(
+            algorithm,
+            SearchAlgorithm::Hierarchical | SearchAlgorithm::DStarLite
+        ) {
+            if !self.scratch.done && max_expansions > 0 {
+                if algorithm == SearchAlgorithm::Hierarchical {
+                    self.search_hierarchical();
+                } else {
+                    self.search_dstar_lite();
+                }
+            }
+            return self.scratch.done;
+        }
+        let end_id = self.end_id as usize;
+        for _ in 0..max_expansions {
+            if self.scratch.done {
+                break;
+            }
+            let next = match algorithm {
+                SearchAlgorithm::BreadthFirst => self.scratch.dequeue(),
+                _ => self.scratch.pop(),
+            };
+
+            // Stop searching when either:
+            // 1) target is closed, in which case the path has been found
+            // 2) failed to find the target and the open list is empty (no path)
+            let Some(current_node) = next else {
+                self.scratch.done = true;
+                break;
+            };
+            self.expanded_nodes += 1;
+            self.scratch.last_expanded = current_node as i32;
+            if current_node == end_id {
+                self.scratch.done = true;
+                if algorithm == SearchAlgorithm::JumpPoint && self.tiles[end_id].parent_id >= 0 {
+                    self.fill_jump_path();
+                }
+                break;
+            }
+
+            match algorithm {
+                SearchAlgorithm::BreadthFirst => self.expand_breadth_first(current_node),
+                SearchAlgorithm::JumpPoint => self.expand_jump_point(current_node),
+                SearchAlgorithm::ThetaStar => self.expand_theta_star(current_node),
+                _ => self.expand_best_first(current_node),
+            }
+        }
+        self.scratch.done
+    }
+
+    pub(super) fn mark_search_begun(&mut self) {
+        self.search_origin = (
+            self.start_id,
+            self.end_id,
+            self.algorithm,
+            self.allow_diagonal,
+        );
+        self.map_changed = false;
+    }
+
+    // Whether the running search still matches the current endpoints and
+    // settings. Stepped searches restart when this goes false.
+    pub fn is_search_current(&self) -> bool {
+        self.search_origin
+            == (
+                self.start_id,
+                self.end_id,
+                self.algorithm,
+                self.allow_diagonal,
+            )
+    }
+
+    // Open/closed state of every tile in the running search, by node_id.
+    pub fn search_states(&self) -> &[NodeState] {
+        &self.scratch.state
+    }
+
+    // End of the path worth drawing: the target once the search is done,
+    // otherwise the node expanded last so partial paths show up while stepping.
+    pub fn path_tail_id(&self) -> usize {
+        if self.scratch.done || self.scratch.last_expanded < 0 {
+            self.end_id as usize
+        } else {
+            self.scratch.last_expanded as usize
+        }
+    }
+
+    // Cost of the last path found, -1 if the target wasn't reached.
+    pub fn path_cost(&self) -> i32 {
+        let end = &self.tiles[self.end_id as usize];
+        if end.parent_id >= 0 || self.start_id == self.end_id {
+            end.g
+        } else {
+            -1
+        }
+    }
+
+    // Tiles from the tail of the current path back to the start.
+    pub fn path_tiles(&self) -> impl Iterator<Item = &Tile> {
+        let mut id = self.path_tail_id() as i32;
+        std::iter::from_fn(move || {
+            if id < 0 {
+                return None;
+            }
+            let t = &self.tiles[id as usize];
+            id = t.parent_id;
+            Some(t)
+        })
+    }
+
+    pub(super) fn num_sides(&self) -> usize {
+        if self.allow_diagonal {
+            8
+        } else {
+            4
+        }
+    }
+
+    fn heuristic(&self) -> Heuristic {
+        if self.active_algorithm() == SearchAlgorithm::ThetaStar {
+            Heuristic::Euclidean
+        } else {
+            self.side_heuristic()
+        }
+    }
+
+    // The algorithm that actually runs. Jump points, line of sight and the
+    // sector borders all follow square grid rows and columns, so on a hex
+    // map those searches run as plain A*. Jump point pruning is only optimal
+    // when every step costs the same, so mixed terrain falls back too.
+    pub fn active_algorithm(&self) -> SearchAlgorithm {
+        match self.algorithm {
+            SearchAlgorithm::JumpPoint
+            | SearchAlgorithm::ThetaStar
+            | SearchAlgorithm::Hierarchical
+                if self.topology == Topology::Hex =>
+            {
+                SearchAlgorithm::AStar
+            }
+            SearchAlgorithm::JumpPoint if !self.uniform_terrain => SearchAlgorithm::AStar,
+            algorithm => algorithm,
+        }
+    }
+
+    fn has_uniform_terrain(&self) -> bool {
+        let mut open_tiles = self.tiles.iter().filter(|t| !t.is_wall);
+        match open_tiles.next() {
+            Some(first) => open_tiles.all(|t| t.terrain == first.terrain),
+            None => true,
+        }
+    }
+
+    // A*, Dijkstra and greedy best-first only differ in the heap priority.
+    fn expand_best_first(&mut self, current_node: usize) {
+        // Check each side node.
+        // If it's a wall, it's not set as a side so we don't need to worry about it.
+        for (id, move_cost) in self.linked_sides(current_node) {
+            if !self.scratch.is_closed(id) {
+                self.check_node(current_node, id, move_cost);
+            }
+        }
+    }
+
+    fn check_node(&mut self, curr_node_id: usize, side_node_id: usize, move_cost: i32) {
+        let id = side_node_id;
+        let parent_g = self.tiles[curr_node_id].g;
+        let step_cost = self.tiles[id].step_cost(move_cost);
+        // Push it if it's not already on the open list, or push it again if
+        // the path through the current node is better (lower G value).
+        // The stale heap entry is skipped when popped.
+        if !self.scratch.is_open(id) || self.tiles[id].g > parent_g + step_cost {
+            self.tiles[id].parent_id = curr_node_id as i32;
+            self.tiles[id].calc_f_g(parent_g, step_cost);
+            let t = &self.tiles[id];
+            self.scratch
+                .push(id, self.active_algorithm().priority(t.g, t.h), t.h);
+        }
+    }
+
+    // Fewest steps, terrain is ignored while searching.
+    fn expand_breadth_first(&mut self, current_node: usize) {
+        for (id, move_cost) in self.linked_sides(current_node) {
+            if !self.scratch.is_visited(id) {
+                let parent_g = self.tiles[current_node].g;
+                let step_cost = self.tiles[id].step_cost(move_cost);
+                self.tiles[id].parent_id = current_node as i32;
+                self.tiles[id].calc_f_g(parent_g, step_cost);
+                self.scratch.enqueue(id);
+            }
+        }
+    }
+
+    // Jump Point Search. It prunes symmetric paths on a uniform cost grid, so
+    // it only runs when every open tile has the same terrain. Steps are
+    // costed at the cheapest weight while searching and fill_jump_path puts
+    // the real weights back.
+    fn expand_jump_point(&mut self, current_node: usize) {
+        let x = self.tiles[current_node].x_id;
+        let y = self.tiles[current_node].y_id;
+        let (dirs, num_dirs) = self.jump_directions(current_node);
+        for &(x_dir, y_dir) in dirs[..num_dirs].iter() {
+            let jump = if self.allow_diagonal {
+                self.jump(x, y, x_dir, y_dir)
+            } else {
+                self.jump_orthogonal(x, y, x_dir, y_dir)
+            };
+            let Some(id) = jump else {
+                continue;
+            };
+            if self.scratch.is_closed(id) {
+                continue;
+            }
+            let steps_x = (self.tiles[id].x_id - x).abs();
+            let steps_y = (self.tiles[id].y_id - y).abs();
+            let move_cost = if steps_x != 0 && steps_y != 0 {
+                DIAGONAL_MOVE_COST
+            } else {
+                MOVE_COST
+            };
+            let g =
+                self.tiles[current_node].g + steps_x.max(steps_y) * move_cost * MIN_TERRAIN_WEIGHT;
+            if !self.scratch.is_open(id) || g < self.tiles[id].g {
+                let t = &mut self.tiles[id];
+                t.parent_id = current_node as i32;
+                t.g = g;
+                t.f = g + t.h;
+                let (f, h) = (t.f, t.h);
+                self.scratch.push(id, f, h);
+            }
+        }
+    }
+
+    // Directions worth jumping in from a node, pruned by the direction it
+    // was reached from. Returned as a fixed array and a count so expanding a
+    // node doesn't allocate. The jump itself checks each step is linked.
+    fn jump_directions(&self, id: usize) -> ([(i32, i32); 8], usize) {
+        let mut dirs = [(0, 0); 8];
+        let mut count = 0;
+        let mut add = |dir: (i32, i32)| {
+            dirs[count] = dir;
+            count += 1;
+        };
+
+        let t = &self.tiles[id];
+        if t.parent_id < 0 {
+            let all = [
+                (0, -1),
+                (0, 1),
+                (1, 0),
+                (-1, 0),
+                (-1, -1),
+                (1, -1),
+                (-1, 1),
+                (1, 1),
+            ];
+            for dir in all[..self.num_sides()].iter() {
+                add(*dir);
+            }
+            return (dirs, count);
+        }
+        let parent = &self.tiles[t.parent_id as usize];
+        let x_dir = (t.x_id - parent.x_id).signum();
+        let y_dir = (t.y_id - parent.y_id).signum();
+        let (x, y) = (t.x_id, t.y_id);
+
+        if !self.allow_diagonal {
+            add((x_dir, y_dir));
+            if x_dir != 0 {
+                // Horizontal moves come first on canonical paths, so turning
+                // vertical is always allowed.
+                add((0, 1));
+                add((0, -1));
+            } else {
+                // Turning horizontal only happens where it couldn't have
+                // been done a row earlier.
+                for side in [-1, 1] {
+                    if !self.is_walkable(x + side, y - y_dir) {
+                        add((side, 0));
+                    }
+                }
+            }
+        } else if x_dir != 0 && y_dir != 0 {
+            add((x_dir, 0));
+            add((0, y_dir));
+            add((x_dir, y_dir));
+            if !self.is_walkable(x - x_dir, y) {
+                add((-x_dir, y_dir));
+            }
+            if !self.is_walkable(x, y - y_dir) {
+                add((x_dir, -y_dir));
+            }
+        } else if x_dir != 0 {
+            add((x_dir, 0));
+            for side in [-1, 1] {
+                if !self.is_walkable(x, y + side) {
+                    add((x_dir, side));
+                }
+            }
+        } else {
+            add((0, y_dir));
+            for side in [-1, 1] {
+                if !self.is_walkable(x + side, y) {
+                    add((side, y_dir));
+                }
+            }
+        }
+        (dirs, count)
+    }
+
+    // 8-connected jump. Returns the next jump point in a direction, if any.
+    fn jump(&self, mut x: i32, mut y: i32, x_dir: i32, y_dir: i32) -> Option<usize> {
+        loop {
+            let id = self.tiles[self.get_tile_id_at(x as u32, y as u32)].side(x_dir, y_dir);
+            if id < 0 {
+                return None;
+            }
+            x += x_dir;
+            y += y_dir;
+            if id == self.end_id {
+                return Some(id as usize);
+            }
+
+            if x_dir != 0 && y_dir != 0 {
+                let forced = (self.is_walkable(x - x_dir, y + y_dir)
+                    && !self.is_walkable(x - x_dir, y))
+                    || (self.is_walkable(x + x_dir, y - y_dir) && !self.is_walkable(x, y - y_dir));
+                if forced
+                    || self.jump(x, y, x_dir, 0).is_some()
+                    || self.jump(x, y, 0, y_dir).is_some()
+                {
+                    return Some(id as usize);
+                }
+            } else if x_dir != 0 {
+                for side in [-1, 1] {
+                    if self.is_walkable(x + x_dir, y + side) && !self.is_walkable(x, y + side) {
+                        return Some(id as usize);
+                    }
+                }
+            } else {
+                for side in [-1, 1] {
+                    if self.is_walkable(x + side, y + y_dir) && !self.is_walkable(x + side, y) {
+                        return Some(id as usize);
+                    }
+                }
+            }
+        }
+    }
+
+    // 4-connected jump where canonical paths move horizontally before
+    // vertically, so horizontal jumps stop wherever a vertical jump would.
+    fn jump_orthogonal(&self, mut x: i32, mut y: i32, x_dir: i32, y_dir: i32) -> Option<usize> {
+        loop {
+            let id = self.tiles[self.get_tile_id_at(x as u32, y as u32)].side(x_dir, y_dir);
+            if id < 0 {
+                return None;
+            }
+            x += x_dir;
+            y += y_dir;
+            if id == self.end_id {
+                return Some(id as usize);
+            }
+
+            if x_dir != 0 {
+                if self.jump_orthogonal(x, y, 0, 1).is_some()
+                    || self.jump_orthogonal(x, y, 0, -1).is_some()
+                {
+                    return Some(id as usize);
+                }
+            } else {
+                for side in [-1, 1] {
+                    if self.is_walkable(x + side, y) && !self.is_walkable(x + side, y - y_dir) {
+                        return Some(id as usize);
+                    }
+                }
+            }
+        }
+    }
+
+    // Jump points are straight or diagonal lines apart. Link every tile in
+    // between so the parent chain is a tile-by-tile path again, then redo G
+    // with the real terrain costs.
+    fn fill_jump_path(&mut self) {
+        self.collect_path();
+        for i in 0..self.scratch.path.len() - 1 {
+            let (child, parent) = (self.scratch.path[i], self.scratch.path[i + 1]);
+            let x_dir = (self.tiles[parent].x_id - self.tiles[child].x_id).signum();
+            let y_dir = (self.tiles[parent].y_id - self.tiles[child].y_id).signum();
+            let mut current = child;
+            while current != parent {
+                let next = self.get_tile_id_at(
+                    (self.tiles[current].x_id + x_dir) as u32,
+                    (self.tiles[current].y_id + y_dir) as u32,
+                );
+                self.tiles[current].parent_id = next as i32;
+                current = next;
+            }
+        }
+
+        // Walk forwards from the start to accumulate the real G values.
+        self.collect_path();
+        for i in (0..self.scratch.path.len() - 1).rev() {
+            let (child, parent) = (self.scratch.path[i], self.scratch.path[i + 1]);
+            let diagonal = self.tiles[parent].x_id != self.tiles[child].x_id
+                && self.tiles[parent].y_id != self.tiles[child].y_id;
+            let move_cost = if diagonal {
+                DIAGONAL_MOVE_COST
+            } else {
+                MOVE_COST
+            };
+            let parent_g = self.tiles[parent].g;
+            let step_cost = self.tiles[child].step_cost(move_cost);
+            self.tiles[child].calc_f_g(parent_g, step_cost);
+        }
+    }
+
+    // Fills the scratch path with the parent chain from the target back to
+    // the start.
+    fn collect_path(&mut self) {
+        self.scratch.path.clear();
+        let mut id = self.end_id;
+        while id >= 0 {
+            self.scratch.path.push(id as usize);
+            id = self.tiles[id as usize].parent_id;
+        }
+    }
+
+    pub(super) fn is_walkable(&self, x: i32, y: i32) -> bool {
+        let num_x_tiles = (self.width / self.tile_size) as i32;
+        let num_y_tiles = (self.height / self.tile_size) as i32;
+        x >= 0
+            && y >= 0
+            && x < num_x_tiles
+            && y < num_y_tiles
+            && !self.is_blocked(self.get_tile_id_at(x as u32, y as u32))
+    }
+}
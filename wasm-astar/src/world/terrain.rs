@@ -0,0 +1,58 @@
+use crate::engine::Color;
+
+// Weights are in tenths of a normal step so cheap terrain can go below 1x
+// while the A* values stay integers.
+pub const MIN_TERRAIN_WEIGHT: i32 = 7;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Terrain {
+    Road,
+    Ground,
+    Mud,
+    Water,
+}
+
+impl Terrain {
+    // Picks a terrain from a random value in [0, 1).
+    pub fn from_random(r: f32) -> Terrain {
+        if r < 0.15 {
+            Terrain::Road
+        } else if r < 0.75 {
+            Terrain::Ground
+        } else if r < 0.9 {
+            Terrain::Mud
+        } else {
+            Terrain::Water
+        }
+    }
+
+    // Multiplier applied to the move cost of stepping onto a tile.
+    pub fn weight(&self) -> i32 {
+        match self {
+            Terrain::Road => MIN_TERRAIN_WEIGHT,
+            Terrain::Ground => 10,
+            Terrain::Mud => 30,
+            Terrain::Water => 50,
+        }
+    }
+
+    // Inverse of weight, used when loading maps that store tile costs.
+    pub fn from_weight(weight: i32) -> Option<Terrain> {
+        match weight {
+            MIN_TERRAIN_WEIGHT => Some(Terrain::Road),
+            10 => Some(Terrain::Ground),
+            30 => Some(Terrain::Mud),
+            50 => Some(Terrain::Water),
+            _ => None,
+        }
+    }
+
+    pub fn color(&self) -> Color {
+        match self {
+            Terrain::Road => Color::new(35, 25, 42, 1_f32),
+            Terrain::Ground => Color::new(0, 0, 30, 1_f32),
+            Terrain::Mud => Color::new(25, 45, 20, 1_f32),
+            Terrain::Water => Color::new(210, 55, 32, 1_f32),
+        }
+    }
+}
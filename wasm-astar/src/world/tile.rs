@@ -1,6 +1,22 @@
 use crate::engine::{Color, Transform};
 
+use super::terrain::{Terrain, MIN_TERRAIN_WEIGHT};
+use super::topology::to_axial;
+
 pub const MOVE_COST: i32 = 10;
+// Roughly MOVE_COST * sqrt(2), kept integer like the other A* values.
+pub const DIAGONAL_MOVE_COST: i32 = 14;
+
+// Distance estimate to the target, matching how the search can move.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Heuristic {
+    Manhattan,
+    Octile,
+    // Straight line, for any-angle paths.
+    Euclidean,
+    // Steps between hexagons, see Topology::Hex.
+    Hex,
+}
 
 #[derive(Clone)]
 pub struct Tile {
@@ -14,7 +30,13 @@ pub struct Tile {
     pub bottom: i32,
     pub left: i32,
     pub right: i32,
+    // Only followed when diagonal movement is enabled.
+    pub top_left: i32,
+    pub top_right: i32,
+    pub bottom_left: i32,
+    pub bottom_right: i32,
     pub is_wall: bool,
+    pub terrain: Terrain,
     // A* values
     // TODO: move to a new struct type just for A*
     pub h: i32,
@@ -35,36 +57,117 @@ impl Tile {
             bottom: -1,
             left: -1,
             right: -1,
+            top_left: -1,
+            top_right: -1,
+            bottom_left: -1,
+            bottom_right: -1,
             is_wall: false,
+            terrain: Terrain::Ground,
             h: 0,
             g: 0,
             f: 0,
         }
     }
 
-    pub fn reset(&mut self, end_node: &Tile) {
+    // Middle of the tile in canvas pixels, whatever its shape.
+    pub fn center(&self) -> (f64, f64) {
+        (
+            self.transform.pos_x + self.transform.scale_x / 2_f64,
+            self.transform.pos_y + self.transform.scale_y / 2_f64,
+        )
+    }
+
+    pub fn clear_sides(&mut self) {
+        self.top = -1;
+        self.bottom = -1;
+        self.left = -1;
+        self.right = -1;
+        self.top_left = -1;
+        self.top_right = -1;
+        self.bottom_left = -1;
+        self.bottom_right = -1;
+    }
+
+    // Linked sides with the cost of stepping onto each, orthogonal first.
+    pub fn sides(&self) -> [(i32, i32); 8] {
+        [
+            (self.top, MOVE_COST),
+            (self.bottom, MOVE_COST),
+            (self.right, MOVE_COST),
+            (self.left, MOVE_COST),
+            (self.top_left, DIAGONAL_MOVE_COST),
+            (self.top_right, DIAGONAL_MOVE_COST),
+            (self.bottom_left, DIAGONAL_MOVE_COST),
+            (self.bottom_right, DIAGONAL_MOVE_COST),
+        ]
+    }
+
+    // Linked side in a grid direction, -1 if there is no walkable side there.
+    pub fn side(&self, x_dir: i32, y_dir: i32) -> i32 {
+        match (x_dir, y_dir) {
+            (0, -1) => self.top,
+            (0, 1) => self.bottom,
+            (1, 0) => self.right,
+            (-1, 0) => self.left,
+            (-1, -1) => self.top_left,
+            (1, -1) => self.top_right,
+            (-1, 1) => self.bottom_left,
+            (1, 1) => self.bottom_right,
+            _ => -1,
+        }
+    }
+
+    pub fn reset(&mut self, end_node: &Tile, heuristic: Heuristic) {
         self.parent_id = -1;
         self.g = 0;
         self.f = 0;
-        self.calc_h(end_node);
+        self.calc_h(end_node, heuristic);
     }
 
-    fn calc_h(&mut self, end_node: &Tile) {
-        if self.is_wall {
-            return;
-        }
+    // Walls get one too, hidden ones are searched under fog of war.
+    fn calc_h(&mut self, end_node: &Tile, heuristic: Heuristic) {
+        self.h = self.estimate_cost(end_node, heuristic);
+    }
+
+    // Lower bound on the cost of getting from this tile to another.
+    pub fn estimate_cost(&self, other: &Tile, heuristic: Heuristic) -> i32 {
         // H: difference between this position and the end target
         // REMINDER TO SELF: the MOVE_COST is very dependant on the x/y diff scale.
         // I was using px,py before by accident which caused diffs to be very large
         // and my MOVE_COST of 10 became useless. Using x/y ids keeps the diffs small
         // enough for MOVE_COST of 10 to work.
-        let x_diff = (self.x_id - end_node.x_id).abs() as i32;
-        let y_diff = (self.y_id - end_node.y_id).abs() as i32;
-        self.h = (x_diff + y_diff) * MOVE_COST;
+        let x_diff = (self.x_id - other.x_id).abs();
+        let y_diff = (self.y_id - other.y_id).abs();
+        // Scaled by the cheapest terrain so H never overestimates the real cost.
+        let steps = match heuristic {
+            Heuristic::Manhattan => (x_diff + y_diff) * MOVE_COST,
+            // Move diagonally until aligned, then straight.
+            Heuristic::Octile => {
+                let diagonal = x_diff.min(y_diff);
+                let straight = x_diff.max(y_diff) - diagonal;
+                diagonal * DIAGONAL_MOVE_COST + straight * MOVE_COST
+            }
+            // Rounded down so it never overestimates.
+            Heuristic::Euclidean => {
+                (((x_diff * x_diff + y_diff * y_diff) as f64).sqrt() * MOVE_COST as f64) as i32
+            }
+            Heuristic::Hex => {
+                let (q, r) = to_axial(self.x_id, self.y_id);
+                let (other_q, other_r) = to_axial(other.x_id, other.y_id);
+                let (q_diff, r_diff) = (q - other_q, r - other_r);
+                (q_diff.abs() + r_diff.abs() + (q_diff + r_diff).abs()) / 2 * MOVE_COST
+            }
+        };
+        steps * MIN_TERRAIN_WEIGHT
+    }
+
+    // Cost of stepping onto this tile, weighted by its terrain.
+    pub fn step_cost(&self, move_cost: i32) -> i32 {
+        move_cost * self.terrain.weight()
     }
 
-    pub fn calc_f_g(&mut self, parent_g: i32) {
-        self.g = parent_g + MOVE_COST;
+    pub fn calc_f_g(&mut self, parent_g: i32, step_cost: i32) {
+        self.g = parent_g + step_cost;
         self.f = self.g + self.h;
     }
 }
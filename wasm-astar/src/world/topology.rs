@@ -0,0 +1,299 @@
+use std::f64::consts::{FRAC_PI_3, FRAC_PI_6};
+
+use super::tile::{Heuristic, DIAGONAL_MOVE_COST, MOVE_COST};
+use super::{Tile, WorldState};
+
+// Width of a pointy topped hexagon over its radius.
+const SQRT_3: f64 = 1.732_050_807_568_877_2;
+
+// How tiles are laid out on the canvas and which ones touch. Tiles are
+// stored in rows of num_x on every topology, x_id and y_id being their
+// column and row, so the search only sees ids and linked sides.
+// Maps to the ids passed to set_topology on the client side.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Topology {
+    Square = 0,
+    // Pointy topped hexagons, every odd row shifted half a tile right.
+    // Distances are worked out in axial coordinates, see to_axial.
+    Hex = 1,
+    // The square grid turned on its corner and squashed, tiles are diamonds.
+    // Links and costs are the square ones, only the drawing differs.
+    Isometric = 2,
+}
+
+impl Topology {
+    pub fn from_id(id: i32) -> Option<Topology> {
+        match id {
+            0 => Some(Topology::Square),
+            1 => Some(Topology::Hex),
+            2 => Some(Topology::Isometric),
+            _ => None,
+        }
+    }
+
+    // Box the tile at column x and row y is drawn in: left, top, width and
+    // height in canvas pixels. The map is scaled to fit the num_x by num_y
+    // tiles of tile_size a square map would cover.
+    pub fn tile_bounds(
+        &self,
+        x: i32,
+        y: i32,
+        num_x: usize,
+        num_y: usize,
+        tile_size: u32,
+    ) -> (f64, f64, f64, f64) {
+        let size = tile_size as f64;
+        match self {
+            Topology::Square => (x as f64 * size, y as f64 * size, size, size),
+            Topology::Hex => {
+                let radius = hex_radius(num_x, num_y, tile_size);
+                let width = radius * SQRT_3;
+                let shift = (y & 1) as f64 / 2_f64;
+                (
+                    (x as f64 + shift) * width,
+                    y as f64 * radius * 1.5,
+                    width,
+                    radius * 2_f64,
+                )
+            }
+            Topology::Isometric => {
+                let width = diamond_width(num_x, num_y, tile_size);
+                (
+                    (x - y + num_y as i32 - 1) as f64 * width / 2_f64,
+                    (x + y) as f64 * width / 4_f64,
+                    width,
+                    width / 2_f64,
+                )
+            }
+        }
+    }
+
+    // Column and row of the tile under a canvas pixel, None off the map.
+    pub fn tile_at(
+        &self,
+        px: f64,
+        py: f64,
+        num_x: usize,
+        num_y: usize,
+        tile_size: u32,
+    ) -> Option<(i32, i32)> {
+        let (x, y) = match self {
+            Topology::Square => {
+                let size = tile_size as f64;
+                ((px / size).floor() as i32, (py / size).floor() as i32)
+            }
+            Topology::Hex => {
+                // Relative to the centre of the first tile, then into axial
+                // coordinates and rounded to the nearest hexagon.
+                let radius = hex_radius(num_x, num_y, tile_size);
+                let px = px - radius * SQRT_3 / 2_f64;
+                let py = py - radius;
+                let q = (px * SQRT_3 / 3_f64 - py / 3_f64) / radius;
+                let r = py * 2_f64 / 3_f64 / radius;
+                let (q, r) = round_axial(q, r);
+                from_axial(q, r)
+            }
+            Topology::Isometric => {
+                // Along the diamond edges, a counts x - y and b counts x + y.
+                let width = diamond_width(num_x, num_y, tile_size);
+                let a = px / (width / 2_f64) - num_y as f64;
+                let b = py / (width / 4_f64);
+                (
+                    ((a + b) / 2_f64).floor() as i32,
+                    ((b - a) / 2_f64).floor() as i32,
+                )
+            }
+        };
+        if x < 0 || y < 0 || x >= num_x as i32 || y >= num_y as i32 {
+            None
+        } else {
+            Some((x, y))
+        }
+    }
+
+    // Corners of a tile in drawing order. Squares are drawn as tiles and
+    // have none.
+    pub fn outline(&self, t: &Tile) -> Vec<(f64, f64)> {
+        let (cx, cy) = t.center();
+        match self {
+            Topology::Square => Vec::new(),
+            Topology::Hex => {
+                let radius = t.transform.scale_y / 2_f64;
+                (0..6)
+                    .map(|i| {
+                        let angle = FRAC_PI_6 + FRAC_PI_3 * i as f64;
+                        (cx + radius * angle.cos(), cy + radius * angle.sin())
+                    })
+                    .collect()
+            }
+            Topology::Isometric => {
+                let (half_x, half_y) = (t.transform.scale_x / 2_f64, t.transform.scale_y / 2_f64);
+                vec![
+                    (cx, cy - half_y),
+                    (cx + half_x, cy),
+                    (cx, cy + half_y),
+                    (cx - half_x, cy),
+                ]
+            }
+        }
+    }
+}
+
+// Hexagon centre to corner, as large as fits the canvas.
+fn hex_radius(num_x: usize, num_y: usize, tile_size: u32) -> f64 {
+    let width = (num_x as u32 * tile_size) as f64;
+    let height = (num_y as u32 * tile_size) as f64;
+    let across = width / ((num_x as f64 + 0.5) * SQRT_3);
+    let down = height / (num_y as f64 * 1.5 + 0.5);
+    across.min(down)
+}
+
+// Diamonds are twice as wide as they are high, as large as fits the canvas.
+fn diamond_width(num_x: usize, num_y: usize, tile_size: u32) -> f64 {
+    let width = (num_x as u32 * tile_size) as f64;
+    let height = (num_y as u32 * tile_size) as f64;
+    let diagonal = (num_x + num_y) as f64;
+    (width * 2_f64 / diagonal).min(height * 4_f64 / diagonal)
+}
+
+// Axial coordinates of the hex tile at column x and row y. Rows stay the
+// same, columns lean so every side is one step on q, r or both.
+pub fn to_axial(x: i32, y: i32) -> (i32, i32) {
+    (x - (y - (y & 1)) / 2, y)
+}
+
+fn from_axial(q: i32, r: i32) -> (i32, i32) {
+    (q + (r - (r & 1)) / 2, r)
+}
+
+// Nearest hexagon to fractional axial coordinates. Rounded in cube
+// coordinates, where the one that moved most is fixed up so all three sum
+// to zero again.
+fn round_axial(q: f64, r: f64) -> (i32, i32) {
+    let s = -q - r;
+    let (mut rq, mut rr, rs) = (q.round(), r.round(), s.round());
+    let (dq, dr, ds) = ((rq - q).abs(), (rr - r).abs(), (rs - s).abs());
+    if dq > dr && dq > ds {
+        rq = -rr - rs;
+    } else if dr > ds {
+        rr = -rq - rs;
+    }
+    (rq as i32, rr as i32)
+}
+
+impl WorldState {
+    // Lays the current map out again on another topology. Walls, terrain and
+    // endpoints stay where they are in the grid.
+    pub fn set_topology(&mut self, topology: Topology) {
+        self.topology = topology;
+        let num_x = (self.width / self.tile_size) as usize;
+        let num_y = (self.height / self.tile_size) as usize;
+        for t in self.tiles.iter_mut() {
+            let (px, py, width, height) =
+                topology.tile_bounds(t.x_id, t.y_id, num_x, num_y, self.tile_size);
+            t.transform.pos_x = px;
+            t.transform.pos_y = py;
+            t.transform.scale_x = width;
+            t.transform.scale_y = height;
+        }
+        self.set_all_tile_sides();
+        self.dirty_tiles.clear();
+        self.hierarchy = None;
+        self.dstar = None;
+        self.flow_field = None;
+        self.redraw_fog |= self.fog.is_some();
+        self.map_changed = true;
+        self.place_player_on(self.start_id as usize);
+        self.set_start_node();
+        self.set_agent_count(self.agents.len());
+        if self.step_mode {
+            self.begin_search();
+        }
+    }
+
+    // Walkable tiles next to a tile and the cost of stepping onto each,
+    // before terrain. Hexagons have six sides the same distance away and
+    // don't care about diagonal movement.
+    pub(super) fn linked_sides(&self, id: usize) -> impl Iterator<Item = (usize, i32)> {
+        let sides = self.tiles[id].sides();
+        let hex = self.topology == Topology::Hex;
+        // Hex tiles leave top and bottom unlinked, see set_hex_sides.
+        let slots = if hex { 2..8 } else { 0..self.num_sides() };
+        slots.filter_map(move |i| {
+            let (s, move_cost) = sides[i];
+            let move_cost = if hex { MOVE_COST } else { move_cost };
+            (s >= 0).then_some((s as usize, move_cost))
+        })
+    }
+
+    // Cost of stepping between two linked tiles, before terrain.
+    pub(super) fn side_move_cost(&self, from: usize, to: usize) -> i32 {
+        let diagonal = self.tiles[from].x_id != self.tiles[to].x_id
+            && self.tiles[from].y_id != self.tiles[to].y_id;
+        if diagonal && self.topology != Topology::Hex {
+            DIAGONAL_MOVE_COST
+        } else {
+            MOVE_COST
+        }
+    }
+
+    // Heuristic for searches that move from side to side.
+    pub(super) fn side_heuristic(&self) -> Heuristic {
+        if self.topology == Topology::Hex {
+            Heuristic::Hex
+        } else if self.allow_diagonal {
+            Heuristic::Octile
+        } else {
+            Heuristic::Manhattan
+        }
+    }
+
+    // Odd rows are shifted right, so from an odd row the rows above and
+    // below are reached straight down and one column right, from an even
+    // row straight down and one column left. The diagonal slots hold those.
+    pub(super) fn set_hex_sides(&mut self, t_id: usize) {
+        let num_x_tiles = (self.width / self.tile_size) as i32;
+        let num_y_tiles = (self.height / self.tile_size) as i32;
+        let (x_id, y_id) = (self.tiles[t_id].x_id, self.tiles[t_id].y_id);
+        let shift = y_id & 1;
+        let side = |x_dir: i32, y_dir: i32| {
+            let (x, y) = (x_id + x_dir, y_id + y_dir);
+            if x < 0 || x >= num_x_tiles || y < 0 || y >= num_y_tiles {
+                return -1;
+            }
+            let id = y * num_x_tiles + x;
+            if self.is_blocked(id as usize) {
+                -1
+            } else {
+                id
+            }
+        };
+        let (right, left) = (side(1, 0), side(-1, 0));
+        let (top_left, top_right) = (side(shift - 1, -1), side(shift, -1));
+        let (bottom_left, bottom_right) = (side(shift - 1, 1), side(shift, 1));
+
+        let t = &mut self.tiles[t_id];
+        t.clear_sides();
+        t.right = right;
+        t.left = left;
+        t.top_left = top_left;
+        t.top_right = top_right;
+        t.bottom_left = bottom_left;
+        t.bottom_right = bottom_right;
+    }
+
+    // Tile under a canvas pixel on the current topology.
+    pub(super) fn get_tile_id_at_point(&self, px: f64, py: f64) -> Option<usize> {
+        let num_x = (self.width / self.tile_size) as usize;
+        let num_y = (self.height / self.tile_size) as usize;
+        let (x, y) = self
+            .topology
+            .tile_at(px, py, num_x, num_y, self.tile_size)?;
+        let id = self.get_tile_id_at(x as u32, y as u32);
+        if id < self.tiles.len() {
+            Some(id)
+        } else {
+            None
+        }
+    }
+}
@@ -0,0 +1,109 @@
+mod common;
+
+use common::{mouse_over, test_world, tile_id};
+use wasm_astar::world::{MapFormat, WorldState, AGENT_STEP_MS, MAX_AGENTS};
+
+// 8x3 tiles, a one tile wide corridor with a passing bay below (1, 0) and (2, 0).
+//
+//   . . . . . . . .
+//   # . . # # # # #
+//   # # # # # # # #
+const CORRIDOR_MAP: &str = r#"{
+    "width": 8,
+    "height": 3,
+    "tile_size": 10,
+    "start_id": 0,
+    "end_id": 7,
+    "costs": [
+        10, 10, 10, 10, 10, 10, 10, 10,
+         0, 10, 10,  0,  0,  0,  0,  0,
+         0,  0,  0,  0,  0,  0,  0,  0
+    ]
+}"#;
+
+// Puts agent i on starts[i] heading for goals[i] and plans them all.
+fn place_agents(world: &mut WorldState, starts: &[usize], goals: &[usize]) {
+    world.set_agent_count(starts.len());
+    for (agent, (&start_id, &goal_id)) in world.agents.iter_mut().zip(starts.iter().zip(goals)) {
+        agent.path = vec![start_id];
+        agent.step = 0;
+        agent.goal_id = goal_id;
+    }
+    world.plan_agents();
+}
+
+fn agent_tiles(world: &WorldState) -> Vec<usize> {
+    world.agents.iter().map(|a| a.tile_id()).collect()
+}
+
+#[test]
+fn agents_never_share_or_swap_tiles_in_a_corridor() {
+    let mut world = WorldState::new();
+    world.import_map(CORRIDOR_MAP, MapFormat::Json).unwrap();
+    // One agent heads left through two heading right, who have to step
+    // into the bay to let it pass.
+    let goals = [tile_id(0, 0), tile_id(7, 0), tile_id(6, 0)];
+    place_agents(
+        &mut world,
+        &[tile_id(7, 0), tile_id(1, 0), tile_id(0, 0)],
+        &goals,
+    );
+
+    let mut tiles = agent_tiles(&world);
+    let mut arrived = false;
+    let mut time = 0_f64;
+    // Long enough to arrive and carry on to new random goals.
+    for _ in 0..30 {
+        time += AGENT_STEP_MS;
+        world.update_agents(time);
+        let next = agent_tiles(&world);
+        for i in 0..next.len() {
+            assert!(!world.tiles[next[i]].is_wall);
+            for j in i + 1..next.len() {
+                assert_ne!(next[i], next[j], "agents {} and {} share a tile", i, j);
+                assert!(
+                    !(next[i] == tiles[j] && next[j] == tiles[i] && next[i] != tiles[i]),
+                    "agents {} and {} swapped tiles",
+                    i,
+                    j
+                );
+            }
+        }
+        arrived |= next == goals;
+        tiles = next;
+    }
+    assert!(arrived);
+}
+
+#[test]
+fn painting_replans_the_agents_once_on_their_next_update() {
+    let mut world = test_world();
+    place_agents(&mut world, &[tile_id(0, 0)], &[tile_id(7, 0)]);
+    let planned = world.agents[0].path.clone();
+    assert!(planned.contains(&tile_id(3, 0)));
+
+    // A stroke across the top row, the agent keeps its plan while it lasts.
+    let (x, y) = mouse_over(&world, 3, 0);
+    world.begin_paint(x, y);
+    let (x, y) = mouse_over(&world, 4, 0);
+    world.paint(x, y);
+    world.end_paint();
+    assert!(world.tiles[tile_id(3, 0)].is_wall);
+    assert_eq!(world.agents[0].path, planned);
+
+    let time = world.agent_step_time;
+    world.update_agents(time);
+    let path = &world.agents[0].path;
+    assert_eq!(path[0], tile_id(0, 0));
+    assert_eq!(path[path.len() - 1], tile_id(7, 0));
+    assert!(path.iter().all(|&id| !world.tiles[id].is_wall));
+}
+
+#[test]
+fn the_agent_count_is_capped() {
+    let mut world = WorldState::new();
+    world.set_agent_count(usize::MAX);
+    assert_eq!(world.agents.len(), MAX_AGENTS);
+    world.set_agent_count(3);
+    assert_eq!(world.agents.len(), 3);
+}
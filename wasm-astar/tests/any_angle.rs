@@ -0,0 +1,84 @@
+mod common;
+
+use common::{test_game, test_world, tile_id};
+use wasm_astar::platform::{DrawCommand, Layer};
+use wasm_astar::world::{SearchAlgorithm, WorldState};
+
+fn assert_straight_segments(world: &WorldState, waypoints: &[usize]) {
+    for pair in waypoints.windows(2) {
+        assert!(world.line_of_sight(pair[0], pair[1]), "{:?}", pair);
+    }
+}
+
+#[test]
+fn line_of_sight_is_blocked_by_walls() {
+    let world = test_world();
+    assert!(world.line_of_sight(tile_id(0, 0), tile_id(7, 0)));
+    assert!(world.line_of_sight(tile_id(6, 0), tile_id(6, 5)));
+    assert!(!world.line_of_sight(tile_id(0, 2), tile_id(4, 4)));
+    assert!(!world.line_of_sight(tile_id(0, 0), tile_id(0, 4)));
+    // Straight through the wall at (1, 1).
+    assert!(!world.line_of_sight(tile_id(0, 0), tile_id(2, 2)));
+    // Past the corner of (3, 3), with (4, 2) open beside it.
+    assert!(world.line_of_sight(tile_id(3, 2), tile_id(4, 3)));
+}
+
+#[test]
+fn theta_star_paths_are_straight_segments() {
+    let mut grid = test_world();
+    grid.allow_diagonal = true;
+    grid.calc_path();
+
+    let mut world = test_world();
+    world.allow_diagonal = true;
+    world.algorithm = SearchAlgorithm::ThetaStar;
+    world.calc_path();
+
+    assert!(world.path_cost() > 0);
+    assert!(world.path_cost() <= grid.path_cost());
+    let waypoints = world.path_waypoints();
+    assert_eq!(waypoints[0], tile_id(0, 0));
+    assert_eq!(waypoints[waypoints.len() - 1], tile_id(0, 4));
+    assert!(waypoints.len() < grid.path_tiles().count());
+    assert_straight_segments(&world, &waypoints);
+}
+
+#[test]
+fn smoothing_keeps_the_endpoints_and_drops_inner_tiles() {
+    let mut world = test_world();
+    world.smooth_path = true;
+    world.calc_path();
+
+    let waypoints = world.path_waypoints();
+    assert_eq!(waypoints[0], tile_id(0, 0));
+    assert_eq!(waypoints[waypoints.len() - 1], tile_id(0, 4));
+    assert!(waypoints.len() < world.path_tiles().count());
+    assert_straight_segments(&world, &waypoints);
+    // Smoothing only changes what is drawn, not the search result.
+    assert_eq!(world.path_cost(), 1200);
+}
+
+#[test]
+fn any_angle_paths_are_drawn_as_a_polyline() {
+    let mut game = test_game();
+    game.world.smooth_path = true;
+    game.tick(1000_f64);
+
+    let commands = game.platform.take();
+    let lines: Vec<&DrawCommand> = commands
+        .iter()
+        .filter(|c| matches!(c, DrawCommand::Polyline { .. }))
+        .collect();
+    assert_eq!(lines.len(), 1);
+    let DrawCommand::Polyline { layer, points, .. } = lines[0] else {
+        unreachable!();
+    };
+    assert_eq!(*layer, Layer::Main);
+    assert_eq!(points.len(), game.world.path_waypoints().len());
+    assert_eq!(points[0], (5_f64, 5_f64));
+    // No circles for the path tiles, only the player.
+    let circles = commands
+        .iter()
+        .filter(|c| matches!(c, DrawCommand::Circle { .. }));
+    assert_eq!(circles.count(), 1);
+}
@@ -0,0 +1,93 @@
+#![allow(dead_code)]
+
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use wasm_astar::game::Game;
+use wasm_astar::platform::RecordingPlatform;
+use wasm_astar::world::{MapFormat, WorldState};
+
+// 8x6 tiles of 10px. # wall, ~ water, m mud, everything else ground.
+// S is tile 0, E is tile 32.
+//
+//   S . . . . . . .
+//   . # # # # # . .
+//   . . . . . # . .
+//   # # # # . # . ~
+//   E . . . . # . .
+//   . m m . . . . .
+pub const TEST_MAP: &str = r#"{
+    "width": 8,
+    "height": 6,
+    "tile_size": 10,
+    "start_id": 0,
+    "end_id": 32,
+    "costs": [
+        10, 10, 10, 10, 10, 10, 10, 10,
+        10,  0,  0,  0,  0,  0, 10, 10,
+        10, 10, 10, 10, 10,  0, 10, 10,
+         0,  0,  0,  0, 10,  0, 10, 50,
+        10, 10, 10, 10, 10,  0, 10, 10,
+        10, 30, 30, 10, 10, 10, 10, 10
+    ]
+}"#;
+
+pub fn test_world() -> WorldState {
+    let mut world = WorldState::new();
+    world.import_map(TEST_MAP, MapFormat::Json).unwrap();
+    world
+}
+
+pub fn test_game() -> Game<RecordingPlatform> {
+    let mut game = Game::new(RecordingPlatform::new(1));
+    game.world = test_world();
+    game
+}
+
+pub fn tile_id(x: usize, y: usize) -> usize {
+    y * 8 + x
+}
+
+// Mouse position (CSS pixels, like mouse_move) over the middle of a tile.
+pub fn mouse_over(world: &WorldState, x: usize, y: usize) -> (i32, i32) {
+    let half = world.tile_size as usize / 2;
+    let quality = world.quality as usize;
+    (
+        ((x * world.tile_size as usize + half) / quality) as i32,
+        ((y * world.tile_size as usize + half) / quality) as i32,
+    )
+}
+
+// Compares against tests/snapshots/<name>.snap. Run the tests with
+// UPDATE_SNAPSHOTS=1 to write the current output as the new golden file.
+pub fn assert_snapshot(name: &str, actual: &str) {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("snapshots")
+        .join(format!("{}.snap", name));
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+    let expected = fs::read_to_string(&path).unwrap_or_else(|_| {
+        panic!(
+            "Missing snapshot {}, run with UPDATE_SNAPSHOTS=1 to create it",
+            path.display()
+        )
+    });
+    if expected != actual {
+        let line = expected
+            .lines()
+            .zip(actual.lines())
+            .position(|(e, a)| e != a)
+            .unwrap_or_else(|| expected.lines().count().min(actual.lines().count()));
+        panic!(
+            "Snapshot {} differs from line {}:\n  expected: {:?}\n    actual: {:?}\nRun with UPDATE_SNAPSHOTS=1 if the change is intended.",
+            name,
+            line + 1,
+            expected.lines().nth(line),
+            actual.lines().nth(line)
+        );
+    }
+}
@@ -0,0 +1,76 @@
+mod common;
+
+use common::{mouse_over, test_world, tile_id};
+use wasm_astar::world::{SearchAlgorithm, WorldState};
+
+// Paints or erases the same tile in both worlds.
+fn toggle_wall(worlds: [&mut WorldState; 2], x: usize, y: usize) {
+    for world in worlds {
+        let (x, y) = mouse_over(world, x, y);
+        world.begin_paint(x, y);
+        world.end_paint();
+    }
+}
+
+#[test]
+fn dstar_lite_finds_the_cheapest_path() {
+    let mut world = test_world();
+    world.algorithm = SearchAlgorithm::DStarLite;
+    world.update_search();
+    assert_eq!(world.path_cost(), 1200);
+    assert_eq!(world.path_tiles().last().unwrap().node_id, tile_id(0, 0));
+}
+
+#[test]
+fn moving_the_start_reuses_the_last_search() {
+    for seed in 1..=4 {
+        let mut world = WorldState::new();
+        world.set_seed(seed);
+        world.algorithm = SearchAlgorithm::DStarLite;
+        world.update_search();
+        let first = world.expanded_nodes;
+
+        // Step onto the path, every tile on it already knows its cost.
+        let next = world.path_tiles().nth(world.path_tiles().count() - 2);
+        world.start_id = next.unwrap().node_id as i32;
+        world.update_search();
+        let replanned = world.expanded_nodes;
+
+        let moved_cost = world.path_cost();
+        world.algorithm = SearchAlgorithm::AStar;
+        world.calc_path();
+        assert_eq!(moved_cost, world.path_cost(), "seed {}", seed);
+        assert!(replanned < first / 4, "seed {}", seed);
+    }
+}
+
+#[test]
+fn painting_walls_replans_around_them() {
+    let mut world = test_world();
+    world.algorithm = SearchAlgorithm::DStarLite;
+    world.update_search();
+    let mut a_star = test_world();
+    // Close the gap at (4, 3) the path goes through and the one at (6, 3),
+    // leaving the way through the water, then open (4, 3) again.
+    for (x, y) in [(4, 3), (6, 3), (4, 3)] {
+        toggle_wall([&mut world, &mut a_star], x, y);
+        world.update_search();
+        a_star.calc_path();
+        assert_eq!(world.path_cost(), a_star.path_cost(), "({}, {})", x, y);
+        assert!(world.expanded_nodes > 0);
+    }
+    assert_eq!(world.path_cost(), 1200);
+}
+
+#[test]
+fn unchanged_searches_are_skipped() {
+    let mut world = test_world();
+    world.update_search();
+    world.expanded_nodes = 0;
+    world.update_search();
+    assert_eq!(world.expanded_nodes, 0);
+
+    world.start_id = tile_id(1, 0) as i32;
+    world.update_search();
+    assert!(world.expanded_nodes > 0);
+}
@@ -0,0 +1,130 @@
+mod common;
+
+use common::{mouse_over, test_game, test_world, tile_id};
+use wasm_astar::platform::{DrawCommand, Layer};
+
+#[test]
+fn following_the_field_reaches_the_goal_at_the_integrated_cost() {
+    let mut world = test_world();
+    world.update_flow_field();
+    let field = world.flow_field().unwrap();
+    // Same as the A* path from the start in pathfinding.rs.
+    assert_eq!(field.costs[tile_id(0, 0)], 1200);
+
+    let size = world.tile_size as f64;
+    let mut id = tile_id(0, 0);
+    let mut steps = 0;
+    while id != field.goal {
+        let (x, y) = world.tiles[id].center();
+        let (dx, dy) = field.directions[id];
+        let (px, py) = (x + dx * size, y + dy * size);
+        id = world
+            .tiles
+            .iter()
+            .position(|t| t.center() == (px, py))
+            .unwrap();
+        steps += 1;
+        assert!(steps < world.tiles.len());
+    }
+    assert_eq!(id, tile_id(0, 4));
+}
+
+#[test]
+fn walls_the_goal_and_positions_off_the_map_have_no_direction() {
+    let mut world = test_world();
+    for (x, y) in [(1, 1), (0, 4)] {
+        let (mx, my) = mouse_over(&world, x, y);
+        assert_eq!(world.flow_direction_at(mx as f64, my as f64), (0.0, 0.0));
+    }
+    assert_eq!(world.flow_direction_at(-5.0, 10.0), (0.0, 0.0));
+    // Just right of the goal, the way is left.
+    let (mx, my) = mouse_over(&world, 1, 4);
+    assert_eq!(world.flow_direction_at(mx as f64, my as f64), (-1.0, 0.0));
+}
+
+#[test]
+fn the_field_follows_the_goal_and_map_edits() {
+    let mut world = test_world();
+    world.update_flow_field();
+    assert_eq!(world.flow_field().unwrap().costs[tile_id(0, 0)], 1200);
+
+    // Walling off the gap at (4, 3) sends the way round through (6, 3).
+    let (x, y) = mouse_over(&world, 4, 3);
+    world.begin_paint(x, y);
+    world.end_paint();
+    assert!(world.flow_field().is_none());
+    world.update_flow_field();
+    let around = world.flow_field().unwrap().costs[tile_id(0, 0)];
+    assert!(around > 1200, "{}", around);
+
+    world.end_id = tile_id(7, 0) as i32;
+    world.update_flow_field();
+    let field = world.flow_field().unwrap();
+    assert_eq!(field.goal, tile_id(7, 0));
+    assert_eq!(field.costs[tile_id(0, 0)], 700);
+}
+
+#[test]
+fn switching_diagonal_moves_rebuilds_the_field() {
+    let mut world = test_world();
+    world.update_flow_field();
+    assert_eq!(world.flow_field().unwrap().costs[tile_id(0, 0)], 1200);
+
+    world.allow_diagonal = true;
+    world.update_flow_field();
+    let field = world.flow_field().unwrap();
+    assert!(field.allow_diagonal);
+    let cut = field.costs[tile_id(0, 0)];
+    assert!(cut < 1200, "{}", cut);
+
+    world.allow_diagonal = false;
+    world.update_flow_field();
+    assert_eq!(world.flow_field().unwrap().costs[tile_id(0, 0)], 1200);
+}
+
+#[test]
+fn arrows_are_only_redrawn_when_the_field_changes() {
+    let mut game = test_game();
+    game.world.set_flow_field_visible(true);
+    game.tick(1000_f64);
+    let commands = game.platform.take();
+    assert!(commands.contains(&DrawCommand::ClearScreen(Layer::Debug)));
+    let arrows = commands
+        .iter()
+        .filter(|c| {
+            matches!(
+                c,
+                DrawCommand::Polyline {
+                    layer: Layer::Debug,
+                    ..
+                }
+            )
+        })
+        .count();
+    let field = game.world.flow_field().unwrap();
+    let reachable = field.directions.iter().filter(|d| **d != (0.0, 0.0));
+    assert_eq!(arrows, reachable.count());
+
+    game.tick(1020_f64);
+    let commands = game.platform.take();
+    assert!(!commands.iter().any(|c| matches!(
+        c,
+        DrawCommand::ClearScreen(Layer::Debug)
+            | DrawCommand::Polyline {
+                layer: Layer::Debug,
+                ..
+            }
+    )));
+
+    game.world.set_flow_field_visible(false);
+    game.tick(1040_f64);
+    let commands = game.platform.take();
+    assert!(commands.contains(&DrawCommand::ClearScreen(Layer::Debug)));
+    assert!(!commands.iter().any(|c| matches!(
+        c,
+        DrawCommand::Polyline {
+            layer: Layer::Debug,
+            ..
+        }
+    )));
+}
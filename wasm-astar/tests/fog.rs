@@ -0,0 +1,112 @@
+mod common;
+
+use common::{test_game, test_world, tile_id};
+use wasm_astar::platform::{DrawCommand, Layer};
+use wasm_astar::world::{Visibility, WorldState, AGENT_STEP_MS, VIEW_RADIUS};
+
+#[test]
+fn walls_hide_what_is_behind_them() {
+    let mut world = test_world();
+    world.set_fog_of_war(true);
+    // From S the row of walls below is in view, the goal behind them isn't.
+    assert_eq!(world.visibility(tile_id(1, 1)), Visibility::Visible);
+    assert_eq!(world.visibility(tile_id(7, 0)), Visibility::Visible);
+    assert_eq!(world.visibility(tile_id(3, 2)), Visibility::Unseen);
+    assert_eq!(world.visibility(tile_id(0, 4)), Visibility::Unseen);
+
+    // Nothing is further away than the view radius, on an open map.
+    let mut world = WorldState::new();
+    world.set_fog_of_war(true);
+    let start = &world.tiles[world.start_id as usize];
+    let (sx, sy) = (start.x_id, start.y_id);
+    for t in world.tiles.iter() {
+        let (dx, dy) = (t.x_id - sx, t.y_id - sy);
+        if dx * dx + dy * dy > VIEW_RADIUS * VIEW_RADIUS {
+            assert_eq!(world.visibility(t.node_id), Visibility::Unseen);
+        }
+    }
+}
+
+#[test]
+fn hidden_walls_are_planned_through_until_seen() {
+    let mut world = test_world();
+    world.update_search();
+    let true_cost = world.path_cost();
+
+    world.set_fog_of_war(true);
+    world.update_search();
+    let hopeful_cost = world.path_cost();
+    assert!(hopeful_cost < true_cost, "{} {}", hopeful_cost, true_cost);
+
+    // Stepping down to (0, 2) shows the walls in row 3, the path goes round.
+    world.start_id = tile_id(0, 2) as i32;
+    world.update_fog();
+    assert_eq!(world.visibility(tile_id(2, 3)), Visibility::Visible);
+    world.update_search();
+    let mut without_fog = test_world();
+    without_fog.start_id = tile_id(0, 2) as i32;
+    without_fog.update_search();
+    assert_eq!(world.path_cost(), without_fog.path_cost());
+}
+
+#[test]
+fn tiles_out_of_view_stay_seen() {
+    let mut world = test_world();
+    world.set_fog_of_war(true);
+    world.start_id = tile_id(0, 4) as i32;
+    world.update_fog();
+    assert_eq!(world.visibility(tile_id(7, 0)), Visibility::Seen);
+    assert_eq!(world.visibility(tile_id(0, 4)), Visibility::Visible);
+
+    world.set_fog_of_war(false);
+    assert!(world.fog().is_none());
+    assert!((0..world.tiles.len()).all(|id| world.visibility(id) == Visibility::Visible));
+}
+
+#[test]
+fn the_overlay_is_only_redrawn_when_the_view_changes() {
+    let mut game = test_game();
+    game.world.set_fog_of_war(true);
+    game.tick(1000_f64);
+    let commands = game.platform.take();
+    assert!(commands.contains(&DrawCommand::ClearScreen(Layer::Fog)));
+    let covered = (0..game.world.tiles.len())
+        .filter(|id| game.world.visibility(*id) != Visibility::Visible)
+        .count();
+    let overlays = commands
+        .iter()
+        .filter(|c| {
+            matches!(
+                c,
+                DrawCommand::Tile {
+                    layer: Layer::Fog,
+                    ..
+                }
+            )
+        })
+        .count();
+    assert!(covered > 0);
+    assert_eq!(overlays, covered);
+
+    game.tick(1020_f64);
+    let commands = game.platform.take();
+    assert!(!commands.contains(&DrawCommand::ClearScreen(Layer::Fog)));
+}
+
+#[test]
+fn agents_never_walk_through_unseen_walls() {
+    for seed in 1..=4 {
+        let mut world = test_world();
+        world.seed = seed;
+        world.set_fog_of_war(true);
+        world.set_agent_count(3);
+        let mut time = 0_f64;
+        for _ in 0..60 {
+            time += AGENT_STEP_MS;
+            world.update_agents(time);
+            for agent in world.agents.iter() {
+                assert!(agent.path.iter().all(|&id| !world.tiles[id].is_wall));
+            }
+        }
+    }
+}
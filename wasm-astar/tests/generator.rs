@@ -0,0 +1,96 @@
+use wasm_astar::world::{MapFormat, MapGenerator, WorldState};
+
+const GENERATORS: [MapGenerator; 5] = [
+    MapGenerator::Scatter,
+    MapGenerator::Maze,
+    MapGenerator::Cave,
+    MapGenerator::Dungeon,
+    MapGenerator::Noise,
+];
+
+fn generate(generator: MapGenerator, seed: u32) -> WorldState {
+    let mut world = WorldState::new();
+    world.generator = generator;
+    world.set_seed(seed);
+    world
+}
+
+#[test]
+fn the_same_seed_gives_the_same_tiles_and_endpoints() {
+    for generator in GENERATORS {
+        for seed in [1, 42, 0xDEAD_BEEF] {
+            let first = generate(generator, seed);
+            let second = generate(generator, seed);
+            // The JSON export holds the tile size, every tile and both endpoints.
+            assert_eq!(
+                first.export_map(MapFormat::Json),
+                second.export_map(MapFormat::Json),
+                "generator {}, seed {}",
+                generator as i32,
+                seed
+            );
+            assert_eq!(first.player.pos_x, second.player.pos_x);
+            assert_eq!(first.player.pos_y, second.player.pos_y);
+        }
+        let other = generate(generator, 2);
+        assert_ne!(
+            generate(generator, 1).export_map(MapFormat::Json),
+            other.export_map(MapFormat::Json),
+            "generator {}",
+            generator as i32
+        );
+    }
+}
+
+#[test]
+fn resetting_follows_the_seed_sequence() {
+    let mut first = generate(MapGenerator::Cave, 7);
+    let mut second = generate(MapGenerator::Cave, 7);
+    for _ in 0..3 {
+        first.reset();
+        second.reset();
+    }
+    assert_eq!(first.seed, second.seed);
+    assert_eq!(
+        first.export_map(MapFormat::Json),
+        second.export_map(MapFormat::Json)
+    );
+}
+
+#[test]
+fn every_generator_puts_start_and_end_in_one_region() {
+    for generator in GENERATORS {
+        for seed in 1..=5 {
+            let mut world = generate(generator, seed);
+            let (start_id, end_id) = (world.start_id as usize, world.end_id as usize);
+            assert!(!world.tiles[start_id].is_wall);
+            assert!(!world.tiles[end_id].is_wall);
+            // Without diagonals the search only links tiles through their
+            // sides, so it finds a path exactly when both are in one region.
+            world.allow_diagonal = false;
+            world.calc_path();
+            assert!(
+                start_id == end_id || world.path_cost() > 0,
+                "generator {}, seed {}",
+                generator as i32,
+                seed
+            );
+        }
+    }
+}
+
+#[test]
+fn a_map_of_only_walls_gets_one_open_tile() {
+    let mut world = WorldState::new();
+    world.import_map("1,1,1\n1,1,1\n", MapFormat::Csv).unwrap();
+    assert_eq!((world.start_id, world.end_id), (0, 0));
+    assert!(!world.tiles[0].is_wall);
+    assert!(world.tiles[1..].iter().all(|t| t.is_wall));
+
+    world.calc_path();
+    assert_eq!(world.path_cost(), 0);
+    world.update_flow_field();
+    assert_eq!(world.flow_field().unwrap().costs[0], 0);
+    world.set_agent_count(3);
+    assert_eq!(world.agents.len(), 1);
+}
@@ -0,0 +1,77 @@
+mod common;
+
+use common::test_world;
+use wasm_astar::world::{MapFormat, SearchAlgorithm, WorldState};
+
+// Every tile on the path is linked to the one before it.
+fn assert_walkable(world: &WorldState) {
+    let tiles: Vec<usize> = world.path_tiles().map(|t| t.node_id).collect();
+    assert_eq!(tiles[tiles.len() - 1], world.start_id as usize);
+    for pair in tiles.windows(2) {
+        let sides = world.tiles[pair[0]].sides();
+        assert!(
+            sides.iter().any(|&(s, _)| s == pair[1] as i32),
+            "{:?}",
+            pair
+        );
+    }
+}
+
+#[test]
+fn paths_inside_one_sector_are_exact() {
+    let mut world = test_world();
+    world.algorithm = SearchAlgorithm::Hierarchical;
+    world.calc_path();
+    assert_eq!(world.path_cost(), 1200);
+    assert_walkable(&world);
+}
+
+#[test]
+fn hierarchical_paths_are_walkable_with_fewer_expansions() {
+    let (mut a_star_expanded, mut hierarchical_expanded) = (0, 0);
+    for seed in 1..=6 {
+        for allow_diagonal in [false, true] {
+            let mut world = WorldState::new();
+            world.set_seed(seed);
+            world.allow_diagonal = allow_diagonal;
+            world.calc_path();
+            let cheapest = world.path_cost();
+            a_star_expanded += world.expanded_nodes;
+
+            world.algorithm = SearchAlgorithm::Hierarchical;
+            world.calc_path();
+            hierarchical_expanded += world.expanded_nodes;
+            assert!(world.path_cost() >= cheapest, "seed {}", seed);
+            assert_walkable(&world);
+        }
+    }
+    assert!(hierarchical_expanded < a_star_expanded);
+}
+
+#[test]
+fn painting_walls_updates_the_sectors_in_place() {
+    let mut world = WorldState::new();
+    world.set_seed(3);
+    world.algorithm = SearchAlgorithm::Hierarchical;
+    world.calc_path();
+    for i in 0..15 {
+        let (x, y) = ((i * 197) % 900, (i * 131) % 600);
+        world.begin_paint(x, y);
+        world.end_paint();
+        world.calc_path();
+
+        // Same map and endpoints, hierarchy built from scratch.
+        let mut fresh = WorldState::new();
+        fresh.algorithm = SearchAlgorithm::Hierarchical;
+        fresh
+            .import_map(&world.export_map(MapFormat::Json), MapFormat::Json)
+            .unwrap();
+        fresh.start_id = world.start_id;
+        fresh.end_id = world.end_id;
+        fresh.calc_path();
+        assert_eq!(world.path_cost(), fresh.path_cost(), "paint {}", i);
+        if world.path_cost() > 0 {
+            assert_walkable(&world);
+        }
+    }
+}
@@ -0,0 +1,188 @@
+mod common;
+
+use common::{mouse_over, test_game, tile_id};
+use wasm_astar::engine::{Action, KeyCode, MouseButton};
+
+const LEFT: u32 = MouseButton::Left as u32;
+const RIGHT: u32 = MouseButton::Right as u32;
+
+#[test]
+fn painting_a_wall_relinks_its_neighbours() {
+    let mut game = test_game();
+    let (x, y) = mouse_over(&game.world, 4, 3);
+    game.mouse_down(x, y, LEFT);
+    game.mouse_up();
+
+    let world = &mut game.world;
+    let id = tile_id(4, 3);
+    assert!(world.tiles[id].is_wall);
+    assert_eq!(world.dirty_tiles, vec![id]);
+    assert_eq!(world.tiles[tile_id(4, 2)].bottom, -1);
+    assert_eq!(world.tiles[tile_id(4, 4)].top, -1);
+    // Squeezing between (4, 3) and (3, 3) is now a corner cut.
+    assert_eq!(world.tiles[tile_id(3, 2)].bottom_right, -1);
+
+    world.calc_path();
+    assert!(world.path_cost() > 1200);
+}
+
+#[test]
+fn dragging_paints_every_tile_passed_until_released() {
+    let mut game = test_game();
+    let (x, y) = mouse_over(&game.world, 4, 3);
+    game.mouse_down(x, y, LEFT);
+    for tile_x in [6, 7] {
+        let (x, y) = mouse_over(&game.world, tile_x, 3);
+        game.mouse_move(x, y);
+    }
+    game.mouse_up();
+    // Moving after release only moves the player again.
+    let (x, y) = mouse_over(&game.world, 6, 5);
+    game.mouse_move(x, y);
+
+    let world = &mut game.world;
+    assert!(!world.tiles[tile_id(6, 5)].is_wall);
+    // Row 3 is all walls now, the bottom half can't be reached.
+    for tile_x in 0..8 {
+        assert!(world.tiles[tile_id(tile_x, 3)].is_wall, "{}", tile_x);
+    }
+    world.start_id = tile_id(0, 0) as i32;
+    world.calc_path();
+    assert_eq!(world.path_cost(), -1);
+}
+
+#[test]
+fn pressing_on_a_wall_erases() {
+    let mut game = test_game();
+    let (x, y) = mouse_over(&game.world, 1, 1);
+    game.mouse_down(x, y, LEFT);
+    let (x, y) = mouse_over(&game.world, 2, 1);
+    game.world.paint(x, y);
+    game.mouse_up();
+
+    let world = &game.world;
+    assert!(!world.tiles[tile_id(1, 1)].is_wall);
+    assert!(!world.tiles[tile_id(2, 1)].is_wall);
+    assert!(world.tiles[tile_id(3, 1)].is_wall);
+    assert_eq!(world.tiles[tile_id(1, 0)].bottom, tile_id(1, 1) as i32);
+}
+
+#[test]
+fn endpoints_are_never_painted_over() {
+    let mut game = test_game();
+    let (x, y) = mouse_over(&game.world, 1, 0);
+    game.mouse_down(x, y, LEFT);
+    let (x, y) = mouse_over(&game.world, 0, 0);
+    game.world.paint(x, y);
+    game.mouse_up();
+
+    assert!(game.world.tiles[tile_id(1, 0)].is_wall);
+    assert!(!game.world.tiles[tile_id(0, 0)].is_wall);
+}
+
+#[test]
+fn right_click_moves_the_goal_but_not_onto_walls() {
+    let mut game = test_game();
+    let (x, y) = mouse_over(&game.world, 7, 5);
+    game.mouse_down(x, y, RIGHT);
+    assert_eq!(game.world.end_id, tile_id(7, 5) as i32);
+
+    let (x, y) = mouse_over(&game.world, 1, 1);
+    game.mouse_down(x, y, RIGHT);
+    assert_eq!(game.world.end_id, tile_id(7, 5) as i32);
+    assert!(!game.world.is_painting());
+}
+
+#[test]
+fn arrow_keys_move_the_start() {
+    let mut game = test_game();
+    game.key_down(KeyCode::ArrowRight as u32);
+    let mut elapsed = 0_f64;
+    for _ in 0..4 {
+        elapsed += 16_f64;
+        game.tick(elapsed);
+    }
+    game.key_up(KeyCode::ArrowRight as u32);
+    game.tick(elapsed + 16_f64);
+
+    assert_ne!(game.world.start_id, tile_id(0, 0) as i32);
+    assert_eq!(game.world.tiles[game.world.start_id as usize].y_id, 0);
+}
+
+#[test]
+fn spacebar_regenerates_the_map_once_per_press() {
+    let mut game = test_game();
+    let seed = game.world.seed;
+    game.key_down(KeyCode::Spacebar as u32);
+    game.tick(16_f64);
+    let new_seed = game.world.seed;
+    assert_ne!(new_seed, seed);
+    // Held down, no new map.
+    game.key_down(KeyCode::Spacebar as u32);
+    game.tick(32_f64);
+    assert_eq!(game.world.seed, new_seed);
+}
+
+#[test]
+fn a_tap_between_frames_still_counts() {
+    let mut game = test_game();
+    let seed = game.world.seed;
+    game.key_down(KeyCode::Spacebar as u32);
+    game.key_up(KeyCode::Spacebar as u32);
+    game.tick(16_f64);
+    assert_ne!(game.world.seed, seed);
+    assert!(game.engine.input.was_pressed(Action::Regenerate));
+
+    game.tick(32_f64);
+    assert!(game.engine.input.was_released(Action::Regenerate));
+}
+
+#[test]
+fn rebound_keys_trigger_their_action() {
+    const KEY_R: u32 = 82;
+    let mut game = test_game();
+    game.engine.input.unbind_key(KeyCode::Spacebar as u32);
+    game.engine.input.bind_key(KEY_R, Action::Regenerate);
+    let seed = game.world.seed;
+    game.key_down(KeyCode::Spacebar as u32);
+    game.tick(16_f64);
+    assert_eq!(game.world.seed, seed);
+
+    game.key_down(KEY_R);
+    game.tick(32_f64);
+    assert_ne!(game.world.seed, seed);
+}
+
+#[test]
+fn the_gamepad_stick_moves_the_start_past_the_dead_zone() {
+    let mut game = test_game();
+    game.engine.input.set_gamepad_axis(1, 0.3);
+    game.tick(16_f64);
+    assert!(!game.engine.input.is_down(Action::MoveDown));
+
+    game.engine.input.set_gamepad_axis(0, 0.9);
+    let mut elapsed = 16_f64;
+    for _ in 0..4 {
+        elapsed += 16_f64;
+        game.tick(elapsed);
+    }
+    assert!(game.engine.input.is_down(Action::MoveRight));
+    assert_ne!(game.world.start_id, tile_id(0, 0) as i32);
+    assert_eq!(game.world.tiles[game.world.start_id as usize].y_id, 0);
+}
+
+#[test]
+fn games_keep_their_own_state() {
+    let mut painted = test_game();
+    let mut other = test_game();
+    let (x, y) = mouse_over(&painted.world, 4, 3);
+    painted.mouse_down(x, y, LEFT);
+    painted.key_down(KeyCode::Spacebar as u32);
+    painted.tick(16_f64);
+    other.tick(16_f64);
+
+    assert_ne!(painted.world.seed, other.world.seed);
+    assert!(painted.world.is_painting());
+    assert!(!other.world.is_painting());
+    assert!(!other.world.tiles[tile_id(4, 3)].is_wall);
+}
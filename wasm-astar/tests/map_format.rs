@@ -0,0 +1,105 @@
+mod common;
+
+use common::{test_world, TEST_MAP};
+use wasm_astar::world::{MapFormat, WorldState};
+
+// JSON in the layout export_map writes.
+fn json_map(
+    width: usize,
+    height: usize,
+    tile_size: u32,
+    endpoints: (usize, usize),
+    costs: &[i32],
+) -> String {
+    let costs: Vec<String> = costs.iter().map(|c| c.to_string()).collect();
+    format!(
+        r#"{{"width":{},"height":{},"tile_size":{},"start_id":{},"end_id":{},"costs":[{}]}}"#,
+        width,
+        height,
+        tile_size,
+        endpoints.0,
+        endpoints.1,
+        costs.join(",")
+    )
+}
+
+fn import_error(data: &str, format: MapFormat) -> String {
+    let mut world = test_world();
+    let before = world.export_map(MapFormat::Json);
+    let error = world.import_map(data, format).unwrap_err();
+    // A map that doesn't validate leaves the world as it was.
+    assert_eq!(world.export_map(MapFormat::Json), before);
+    error
+}
+
+#[test]
+fn ragged_csv_rows_are_rejected() {
+    let error = import_error("0,0,0\n0,1\n0,0,0\n", MapFormat::Csv);
+    assert_eq!(error, "Row 2 has 2 columns, expected 3");
+    let error = import_error("0,0\n0,2\n", MapFormat::Csv);
+    assert_eq!(error, "Row 2, column 2: expected 0 or 1, got '2'");
+    assert_eq!(import_error("\n\n", MapFormat::Csv), "Map is empty");
+}
+
+#[test]
+fn json_costs_must_match_the_size() {
+    let error = import_error(&json_map(2, 2, 10, (0, 3), &[10, 10, 10]), MapFormat::Json);
+    assert_eq!(error, "Expected 4 costs for a 2x2 map, got 3");
+    let error = import_error(
+        &json_map(2, 2, 10, (0, 3), &[10, 42, 10, 10]),
+        MapFormat::Json,
+    );
+    assert_eq!(error, "Tile 1: unknown cost 42");
+}
+
+#[test]
+fn endpoints_must_be_open_tiles_on_the_map() {
+    let costs = [10, 0, 10, 10];
+    let error = import_error(&json_map(2, 2, 10, (1, 3), &costs), MapFormat::Json);
+    assert_eq!(error, "start_id 1 is a wall");
+    let error = import_error(&json_map(2, 2, 10, (0, 1), &costs), MapFormat::Json);
+    assert_eq!(error, "end_id 1 is a wall");
+    let error = import_error(&json_map(2, 2, 10, (0, 4), &costs), MapFormat::Json);
+    assert_eq!(error, "end_id 4 is outside the map");
+}
+
+#[test]
+fn oversized_maps_are_rejected_instead_of_overflowing() {
+    let huge = usize::MAX / 2 + 1;
+    let error = import_error(&json_map(huge, 2, 10, (0, 0), &[]), MapFormat::Json);
+    assert!(error.contains("too large"), "{}", error);
+    let error = import_error(&json_map(2, 2, u32::MAX, (0, 3), &[10; 4]), MapFormat::Json);
+    assert!(
+        error.starts_with("Tile size must be between 1 and"),
+        "{}",
+        error
+    );
+
+    let wide = vec!["0"; 2000].join(",");
+    let error = import_error(&wide, MapFormat::Csv);
+    assert!(error.contains("columns wide"), "{}", error);
+    let tall = "0\n".repeat(2000);
+    let error = import_error(&tall, MapFormat::Csv);
+    assert!(error.contains("rows"), "{}", error);
+}
+
+#[test]
+fn maps_survive_a_round_trip_through_both_formats() {
+    let mut world = test_world();
+    let json = world.export_map(MapFormat::Json);
+    let mut copy = WorldState::new();
+    copy.import_map(&json, MapFormat::Json).unwrap();
+    assert_eq!(copy.export_map(MapFormat::Json), json);
+    assert_eq!((copy.width, copy.height), (world.width, world.height));
+
+    // CSV only keeps the walls, so terrain comes back as ground.
+    let csv = world.export_map(MapFormat::Csv);
+    assert_eq!(csv.lines().count(), 6);
+    assert_eq!(csv.lines().nth(1), Some("0,1,1,1,1,1,0,0"));
+    world.import_map(&csv, MapFormat::Csv).unwrap();
+    assert_eq!(world.export_map(MapFormat::Csv), csv);
+    let walls = |w: &WorldState| w.tiles.iter().map(|t| t.is_wall).collect::<Vec<_>>();
+    let mut original = WorldState::new();
+    original.import_map(TEST_MAP, MapFormat::Json).unwrap();
+    assert_eq!(walls(&world), walls(&original));
+}
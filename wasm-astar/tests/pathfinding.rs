@@ -0,0 +1,151 @@
+mod common;
+
+use common::{test_world, tile_id, TEST_MAP};
+use wasm_astar::world::{MapFormat, SearchAlgorithm, WorldState};
+
+// 5x2 tiles, mud between the endpoints and a road one row down.
+//
+//   S m m m E
+//   r r r r r
+const MUD_AND_ROAD_MAP: &str = r#"{
+    "width": 5,
+    "height": 2,
+    "tile_size": 10,
+    "start_id": 0,
+    "end_id": 4,
+    "costs": [
+        10, 30, 30, 30, 10,
+         7,  7,  7,  7,  7
+    ]
+}"#;
+
+fn world_from(map: &str) -> WorldState {
+    let mut world = WorldState::new();
+    world.import_map(map, MapFormat::Json).unwrap();
+    world
+}
+
+fn path(world: &wasm_astar::world::WorldState) -> Vec<usize> {
+    let mut path: Vec<usize> = world.path_tiles().map(|t| t.node_id).collect();
+    path.reverse();
+    path
+}
+
+#[test]
+fn finds_cheapest_path_around_the_walls() {
+    let mut world = test_world();
+    world.calc_path();
+
+    // 12 ground steps along row 2, down column 4 and back along row 4.
+    assert_eq!(world.path_cost(), 1200);
+    let path = path(&world);
+    assert_eq!(path.len(), 13);
+    assert_eq!(path[0], tile_id(0, 0));
+    assert_eq!(path[12], tile_id(0, 4));
+    assert!(path.contains(&tile_id(4, 3)));
+}
+
+#[test]
+fn diagonal_moves_cut_corners_next_to_single_walls() {
+    let mut world = test_world();
+    world.allow_diagonal = true;
+    world.calc_path();
+
+    // 6 straight moves and 3 diagonals of 14, all on ground.
+    assert_eq!(world.path_cost(), 1020);
+    let path = path(&world);
+    assert!(path.contains(&tile_id(1, 2)));
+    assert!(path.contains(&tile_id(4, 3)));
+}
+
+#[test]
+fn optimal_algorithms_agree_on_the_cost() {
+    for allow_diagonal in [false, true] {
+        let mut costs = Vec::new();
+        for algorithm in [
+            SearchAlgorithm::AStar,
+            SearchAlgorithm::Dijkstra,
+            SearchAlgorithm::JumpPoint,
+        ] {
+            let mut world = test_world();
+            world.allow_diagonal = allow_diagonal;
+            world.algorithm = algorithm;
+            world.calc_path();
+            costs.push(world.path_cost());
+        }
+        assert!(costs.iter().all(|&c| c == costs[0]), "{:?}", costs);
+    }
+}
+
+#[test]
+fn a_star_expands_fewer_nodes_than_dijkstra() {
+    let mut world = test_world();
+    world.calc_path();
+    let a_star = world.expanded_nodes;
+    world.algorithm = SearchAlgorithm::Dijkstra;
+    world.calc_path();
+    assert!(a_star <= world.expanded_nodes);
+}
+
+#[test]
+fn every_algorithm_reaches_the_goal() {
+    for id in 0..8 {
+        let mut world = test_world();
+        world.algorithm = SearchAlgorithm::from_id(id).unwrap();
+        world.calc_path();
+        assert!(world.path_cost() > 0, "algorithm {}", id);
+        assert_eq!(path(&world)[0], tile_id(0, 0), "algorithm {}", id);
+    }
+}
+
+#[test]
+fn step_mode_finishes_with_the_same_path() {
+    let mut full = test_world();
+    full.calc_path();
+
+    let mut stepped = test_world();
+    stepped.step_mode = true;
+    stepped.steps_per_frame = 3;
+    stepped.begin_search();
+    let mut frames = 0;
+    while !stepped.step_search(stepped.steps_per_frame) {
+        frames += 1;
+        assert!(frames < 100);
+    }
+    assert!(frames > 1);
+    assert_eq!(stepped.path_cost(), full.path_cost());
+    assert_eq!(stepped.expanded_nodes, full.expanded_nodes);
+}
+
+#[test]
+fn jump_point_search_runs_on_uniform_terrain() {
+    // The test map with its mud and water turned to ground
+    let uniform_map = TEST_MAP.replace("30", "10").replace("50", "10");
+    for allow_diagonal in [false, true] {
+        let mut costs = Vec::new();
+        for algorithm in [SearchAlgorithm::AStar, SearchAlgorithm::JumpPoint] {
+            let mut world = world_from(&uniform_map);
+            world.allow_diagonal = allow_diagonal;
+            world.algorithm = algorithm;
+            world.calc_path();
+            assert!(world.active_algorithm() == algorithm);
+            costs.push(world.path_cost());
+        }
+        assert_eq!(costs[0], costs[1]);
+    }
+}
+
+#[test]
+fn jump_point_search_falls_back_to_a_star_on_mixed_terrain() {
+    // Without diagonals, straight through the mud costs 3 * 300 + 100 and
+    // the road around it 5 * 70 + 100.
+    for (allow_diagonal, cheapest) in [(false, 450), (true, 378)] {
+        let mut world = world_from(MUD_AND_ROAD_MAP);
+        world.allow_diagonal = allow_diagonal;
+        world.algorithm = SearchAlgorithm::JumpPoint;
+        world.calc_path();
+        assert!(world.active_algorithm() == SearchAlgorithm::AStar);
+        assert_eq!(world.path_cost(), cheapest);
+        assert!(!path(&world).contains(&tile_id(2, 0)));
+    }
+}
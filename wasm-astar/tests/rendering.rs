@@ -0,0 +1,279 @@
+mod common;
+
+use common::{assert_snapshot, test_game, test_world, tile_id};
+use wasm_astar::engine::Color;
+use wasm_astar::game::Game;
+use wasm_astar::platform::{DrawCommand, Layer, RecordingPlatform};
+use wasm_astar::render::{RetainedLayer, Sprite};
+
+fn main_layer_commands(commands: Vec<DrawCommand>) -> Vec<DrawCommand> {
+    commands
+        .into_iter()
+        .filter(|c| match c {
+            DrawCommand::ClearScreen(layer) => *layer == Layer::Main,
+            DrawCommand::ClearRect { layer, .. }
+            | DrawCommand::Tile { layer, .. }
+            | DrawCommand::Circle { layer, .. }
+            | DrawCommand::Polyline { layer, .. }
+            | DrawCommand::SearchStats { layer, .. } => *layer == Layer::Main,
+            _ => false,
+        })
+        .collect()
+}
+
+#[test]
+fn background_draws_every_tile_once() {
+    let mut game = test_game();
+    game.resize();
+    assert_snapshot("background", &game.platform.snapshot());
+}
+
+#[test]
+fn frame() {
+    let mut game = test_game();
+    game.tick(1000_f64);
+    assert_snapshot("frame", &game.platform.snapshot());
+}
+
+#[test]
+fn step_mode_frame_shows_the_open_and_closed_tiles() {
+    let mut game = test_game();
+    game.world.step_mode = true;
+    game.world.steps_per_frame = 4;
+    game.world.begin_search();
+    game.tick(1000_f64);
+    assert_snapshot("step_mode_frame", &game.platform.snapshot());
+}
+
+#[test]
+fn painting_redraws_only_the_changed_tile() {
+    let mut game = test_game();
+    let (x, y) = common::mouse_over(&game.world, 7, 0);
+    game.mouse_down(x, y, 0);
+    game.mouse_up();
+    game.tick(1000_f64);
+
+    let background: Vec<DrawCommand> = game
+        .platform
+        .take()
+        .into_iter()
+        .filter(|c| {
+            matches!(
+                c,
+                DrawCommand::Tile {
+                    layer: Layer::TileBg,
+                    ..
+                }
+            )
+        })
+        .collect();
+    assert_eq!(background.len(), 1);
+    let wall = &game.world.tiles[tile_id(7, 0)];
+    assert_eq!(
+        background[0],
+        DrawCommand::Tile {
+            layer: Layer::TileBg,
+            px: wall.transform.pos_x,
+            py: wall.transform.pos_y,
+            size: 10_f64,
+            color: wall.color.clone(),
+        }
+    );
+
+    // Nothing left to redraw on the next frame.
+    game.tick(1016_f64);
+    assert!(!game.platform.take().iter().any(|c| matches!(
+        c,
+        DrawCommand::Tile {
+            layer: Layer::TileBg,
+            ..
+        }
+    )));
+}
+
+#[test]
+fn init_creates_the_layers_and_seeds_from_the_platform() {
+    let mut game = Game::new(RecordingPlatform::new(42));
+    game.world = test_world();
+    // Small window, so the map is regenerated at the mobile size.
+    game.init(false, 0, 400, 300);
+    assert_eq!(game.world.seed, 42);
+    let commands = game.platform.take();
+    let layers: Vec<&DrawCommand> = commands
+        .iter()
+        .filter(|c| matches!(c, DrawCommand::CreateLayer(_)))
+        .collect();
+    assert_eq!(
+        layers,
+        vec![
+            &DrawCommand::CreateLayer(Layer::TileBg),
+            &DrawCommand::CreateLayer(Layer::Search),
+            &DrawCommand::CreateLayer(Layer::Fog),
+            &DrawCommand::CreateLayer(Layer::Debug),
+            &DrawCommand::CreateLayer(Layer::Main),
+            &DrawCommand::CreateLayer(Layer::Stats),
+            &DrawCommand::CreateLayer(Layer::Fps),
+        ]
+    );
+    assert!(commands.contains(&DrawCommand::RequestNextTick));
+    assert!(commands.contains(&DrawCommand::SetScreenSize {
+        width: 700,
+        height: 900,
+        quality: 2,
+    }));
+}
+
+#[test]
+fn an_unchanged_frame_leaves_the_main_layer_alone() {
+    let mut game = test_game();
+    game.tick(1000_f64);
+    game.platform.take();
+    game.tick(1010_f64);
+    assert_eq!(main_layer_commands(game.platform.take()), vec![]);
+}
+
+#[test]
+fn moving_the_goal_redraws_only_around_the_change() {
+    let mut game = test_game();
+    game.tick(1000_f64);
+    let full = main_layer_commands(game.platform.take());
+    let (x, y) = common::mouse_over(&game.world, 1, 4);
+    game.mouse_down(x, y, 2);
+    game.tick(1010_f64);
+
+    let changed = main_layer_commands(game.platform.take());
+    assert!(!changed.contains(&DrawCommand::ClearScreen(Layer::Main)));
+    assert!(changed
+        .iter()
+        .any(|c| matches!(c, DrawCommand::ClearRect { .. })));
+    let draws = |commands: &[DrawCommand]| {
+        commands
+            .iter()
+            .filter(|c| !matches!(c, DrawCommand::ClearRect { .. }))
+            .count()
+    };
+    assert!(draws(&changed) < draws(&full) / 2);
+}
+
+#[test]
+fn clearing_a_sprite_redraws_what_overlaps_it() {
+    let color = Color::default();
+    let tile = Sprite::Tile {
+        px: 0_f64,
+        py: 0_f64,
+        size: 10_f64,
+        color: color.clone(),
+    };
+    let circle = Sprite::Circle {
+        px: 10_f64,
+        py: 5_f64,
+        radius: 3_f64,
+        color: color.clone(),
+    };
+    let far = Sprite::Circle {
+        px: 50_f64,
+        py: 50_f64,
+        radius: 3_f64,
+        color,
+    };
+    let mut layer = RetainedLayer::new(Layer::Main);
+    let mut platform = RecordingPlatform::new(1);
+    layer.present(
+        vec![tile.clone(), circle.clone(), far.clone()],
+        &mut platform,
+    );
+    platform.take();
+
+    layer.present(vec![circle.clone(), far], &mut platform);
+    let clear = |s: &Sprite| {
+        let r = s.bounds().unwrap();
+        DrawCommand::ClearRect {
+            layer: Layer::Main,
+            x: r.x,
+            y: r.y,
+            width: r.width,
+            height: r.height,
+        }
+    };
+    assert_eq!(
+        platform.take(),
+        vec![
+            clear(&tile),
+            clear(&circle),
+            DrawCommand::Circle {
+                layer: Layer::Main,
+                px: 10_f64,
+                py: 5_f64,
+                radius: 3_f64,
+                color: Color::default(),
+            },
+        ]
+    );
+}
+
+#[test]
+fn changed_search_stats_clear_their_whole_layer() {
+    let mut game = test_game();
+    game.tick(1000_f64);
+    game.platform.take();
+    game.tick(1010_f64);
+    assert!(!game
+        .platform
+        .take()
+        .iter()
+        .any(|c| matches!(c, DrawCommand::SearchStats { .. })));
+
+    // A new goal gives a new path and new numbers.
+    let (x, y) = common::mouse_over(&game.world, 7, 0);
+    game.mouse_down(x, y, 2);
+    game.tick(1020_f64);
+    let commands = game.platform.take();
+    let stats: Vec<&DrawCommand> = commands
+        .iter()
+        .filter(|c| match c {
+            DrawCommand::ClearScreen(layer) => *layer == Layer::Stats,
+            DrawCommand::ClearRect { layer, .. } => *layer == Layer::Stats,
+            DrawCommand::SearchStats { .. } => true,
+            _ => false,
+        })
+        .collect();
+    assert_eq!(stats.len(), 2);
+    assert_eq!(stats[0], &DrawCommand::ClearScreen(Layer::Stats));
+    assert!(matches!(
+        stats[1],
+        DrawCommand::SearchStats {
+            layer: Layer::Stats,
+            path_cost: 700,
+            ..
+        }
+    ));
+}
+
+#[test]
+fn sprites_without_bounds_redraw_the_whole_layer() {
+    let tile = |px: f64| Sprite::Tile {
+        px,
+        py: 0_f64,
+        size: 10_f64,
+        color: Color::default(),
+    };
+    let stats = Sprite::SearchStats {
+        path_count: 3,
+        path_cost: 30,
+        expanded_nodes: 5,
+    };
+    assert_eq!(stats.bounds(), None);
+    let mut layer = RetainedLayer::new(Layer::Main);
+    let mut platform = RecordingPlatform::new(1);
+    layer.present(vec![tile(0_f64), stats.clone()], &mut platform);
+    platform.take();
+
+    layer.present(vec![tile(0_f64), stats.clone()], &mut platform);
+    assert_eq!(platform.take(), vec![]);
+
+    // The text may be anywhere, so clearing around the tile could cut it.
+    layer.present(vec![tile(20_f64), stats], &mut platform);
+    let commands = platform.take();
+    assert_eq!(commands[0], DrawCommand::ClearScreen(Layer::Main));
+    assert_eq!(commands.len(), 3);
+}
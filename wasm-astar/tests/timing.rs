@@ -0,0 +1,81 @@
+mod common;
+
+use common::test_game;
+use wasm_astar::engine::{EngineState, KeyCode, FIXED_STEP_MS, MAX_STEPS_PER_FRAME};
+
+#[test]
+fn movement_speed_does_not_depend_on_the_frame_rate() {
+    let mut positions = Vec::new();
+    for frame_ms in [10_f64, 30_f64] {
+        let mut game = test_game();
+        game.world.player.pos_x = 1_f64;
+        game.tick(1000_f64);
+        game.key_down(KeyCode::ArrowRight as u32);
+        let mut elapsed = 1000_f64;
+        while elapsed < 1060_f64 {
+            elapsed += frame_ms;
+            game.tick(elapsed);
+        }
+        positions.push(game.world.player.pos_x);
+    }
+    // Three fixed steps of 7px in both.
+    assert_eq!(positions, vec![22_f64, 22_f64]);
+}
+
+#[test]
+fn the_player_is_drawn_between_its_last_two_steps() {
+    let mut game = test_game();
+    game.world.player.pos_x = 1_f64;
+    game.key_down(KeyCode::ArrowRight as u32);
+    game.tick(1000_f64);
+    game.tick(1000_f64 + FIXED_STEP_MS * 1.5);
+
+    assert_eq!(game.world.prev_player.0, 1_f64);
+    assert_eq!(game.world.player.pos_x, 8_f64);
+    let (x, _) = game.world.player_position(game.engine.interpolation());
+    assert!((x - 4.5).abs() < 1e-9, "{}", x);
+}
+
+#[test]
+fn long_stalls_only_run_a_few_steps() {
+    let mut engine = EngineState::new();
+    assert_eq!(engine.update(1000_f64), 0);
+    assert_eq!(engine.update(1001_f64 + FIXED_STEP_MS), 1);
+    assert_eq!(engine.update(10_000_f64), MAX_STEPS_PER_FRAME);
+    assert!(engine.interpolation() < 1_f64);
+}
+
+#[test]
+fn frame_stats_cover_the_recent_frames() {
+    let mut engine = EngineState::new();
+    let mut elapsed = 1000_f64;
+    engine.update(elapsed);
+    for i in 0..20 {
+        elapsed += if i % 10 == 0 { 50_f64 } else { 10_f64 };
+        engine.update(elapsed);
+    }
+    engine.stats.record_search(2_f64);
+    engine.stats.record_search(4_f64);
+
+    let stats = engine.stats.summary();
+    assert_eq!(stats.min_ms, 10_f64);
+    assert_eq!(stats.avg_ms, 14_f64);
+    assert_eq!(stats.p95_ms, 50_f64);
+    assert_eq!(stats.search_ms, 3_f64);
+    assert!((engine.fps - 1000_f64 / 14_f64).abs() < 1e-9);
+}
+
+#[test]
+fn the_running_average_only_covers_the_window() {
+    let mut engine = EngineState::new();
+    let mut elapsed = 1000_f64;
+    engine.update(elapsed);
+    // Slow frames first, then enough fast ones to push them all out.
+    for i in 0..300 {
+        elapsed += if i < 100 { 40_f64 } else { 10_f64 };
+        engine.update(elapsed);
+    }
+    assert!((engine.stats.avg_frame_ms() - 10_f64).abs() < 1e-9);
+    assert_eq!(engine.stats.summary().p95_ms, 10_f64);
+    assert!((engine.fps - 100_f64).abs() < 1e-6);
+}
@@ -0,0 +1,98 @@
+mod common;
+
+use common::{test_game, test_world, tile_id};
+use wasm_astar::platform::{DrawCommand, Layer};
+use wasm_astar::world::{SearchAlgorithm, Topology, WorldState};
+
+#[test]
+fn hex_tiles_link_six_sides() {
+    let mut world = test_world();
+    world.set_topology(Topology::Hex);
+
+    // Odd rows are shifted right, so (6, 1) touches (6, 0) and (7, 0) above
+    // and (6, 2) and (7, 2) below. (5, 1) is a wall.
+    let t = &world.tiles[tile_id(6, 1)];
+    assert_eq!((t.top, t.bottom), (-1, -1));
+    assert_eq!((t.left, t.right), (-1, tile_id(7, 1) as i32));
+    assert_eq!(
+        (t.top_left, t.top_right),
+        (tile_id(6, 0) as i32, tile_id(7, 0) as i32)
+    );
+    assert_eq!(
+        (t.bottom_left, t.bottom_right),
+        (tile_id(6, 2) as i32, tile_id(7, 2) as i32)
+    );
+    // Even rows lean the other way, (5, 1) and (5, 3) are walls.
+    let t = &world.tiles[tile_id(6, 2)];
+    assert_eq!((t.top_left, t.top_right), (-1, tile_id(6, 1) as i32));
+    assert_eq!((t.bottom_left, t.bottom_right), (-1, tile_id(6, 3) as i32));
+}
+
+#[test]
+fn every_tile_is_found_under_its_centre() {
+    for topology in [Topology::Square, Topology::Hex, Topology::Isometric] {
+        let mut world = test_world();
+        world.set_topology(topology);
+        for t in world.tiles.iter() {
+            let (x, y) = t.center();
+            assert_eq!(
+                topology.tile_at(x, y, 8, 6, world.tile_size),
+                Some((t.x_id, t.y_id)),
+                "{:?} {}",
+                topology,
+                t.node_id
+            );
+        }
+    }
+    // The top left corner is outside the first diamond.
+    assert_eq!(Topology::Isometric.tile_at(0_f64, 0_f64, 8, 6, 10), None);
+}
+
+#[test]
+fn searches_agree_on_hex_maps() {
+    for seed in 1..=4 {
+        let mut world = WorldState::new();
+        world.set_seed(seed);
+        world.set_topology(Topology::Hex);
+        let mut costs = Vec::new();
+        // Jump point search and HPA* fall back to A* on hexagons.
+        for algorithm in [
+            SearchAlgorithm::AStar,
+            SearchAlgorithm::Dijkstra,
+            SearchAlgorithm::JumpPoint,
+            SearchAlgorithm::Hierarchical,
+            SearchAlgorithm::DStarLite,
+        ] {
+            world.algorithm = algorithm;
+            world.update_search();
+            costs.push(world.path_cost());
+        }
+        assert!(costs[0] > 0, "seed {}", seed);
+        assert!(
+            costs.iter().all(|&c| c == costs[0]),
+            "seed {}: {:?}",
+            seed,
+            costs
+        );
+    }
+}
+
+#[test]
+fn non_square_tiles_are_drawn_as_polygons() {
+    for (topology, corners) in [(Topology::Hex, 6), (Topology::Isometric, 4)] {
+        let mut game = test_game();
+        game.world.set_topology(topology);
+        game.platform.take();
+        game.draw_background();
+        let commands = game.platform.take();
+        assert_eq!(commands[0], DrawCommand::ClearScreen(Layer::TileBg));
+        let polygons = commands
+            .iter()
+            .filter(|c| {
+                matches!(c, DrawCommand::Polygon { layer: Layer::TileBg, points, .. }
+                    if points.len() == corners)
+            })
+            .count();
+        assert_eq!(polygons, 48, "{:?}", topology);
+    }
+}
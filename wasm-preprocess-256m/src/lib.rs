@@ -1,8 +1,12 @@
 use wasm_bindgen::prelude::*;
-use image::{io::Reader as ImageReader, ImageFormat, GenericImageView};
+use image::{io::Reader as ImageReader, DynamicImage, GenericImageView, ImageFormat};
 use std::io::Cursor;
 use std::sync::{LazyLock, Mutex};
 
+mod tiling;
+
+pub use tiling::{SmolVlmTiles, TileGrid};
+
 // State management pattern similar to wasm-astar
 // Learned about this pattern from rocket_wasm on github
 // https://github.com/aochagavia/rocket_wasm/blob/d0ca51beb9c7c351a1f0266206edfd553bf078d3/src/lib.rs
@@ -43,6 +47,14 @@ pub fn init() {
     console_error_panic_hook::set_once();
 }
 
+/// Decode PNG or JPEG image data, trying PNG first
+fn decode_image(image_data: &[u8]) -> Result<DynamicImage, JsValue> {
+    ImageReader::with_format(Cursor::new(image_data), ImageFormat::Png)
+        .decode()
+        .or_else(|_| ImageReader::with_format(Cursor::new(image_data), ImageFormat::Jpeg).decode())
+        .map_err(|e| JsValue::from_str(&format!("Failed to decode image: {}", e)))
+}
+
 /// Preprocess image data by resizing to target dimensions using high-quality Lanczos3 filtering
 /// Returns preprocessed image data as RGBA bytes
 /// This is a building block for ML/AI preprocessing pipelines
@@ -55,19 +67,8 @@ pub fn preprocess_image(
     target_width: u32,
     target_height: u32,
 ) -> Result<Vec<u8>, JsValue> {
-    // Copy the image data into a Vec to ensure proper memory management
-    // This prevents issues with WASM memory deallocation
-    let image_bytes = image_data.to_vec();
-    
     // Decode image from bytes (supports PNG and JPEG)
-    // Try PNG first, then JPEG
-    let img = ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Png)
-        .decode()
-        .or_else(|_| {
-            ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Jpeg)
-                .decode()
-        })
-        .map_err(|e| JsValue::from_str(&format!("Failed to decode image: {}", e)))?;
+    let img = decode_image(image_data)?;
 
     // Resize using Lanczos3 filter for high-quality resizing
     // Lanczos3 provides excellent quality for ML model preprocessing
@@ -92,19 +93,8 @@ pub fn preprocess_image_crop(
     target_width: u32,
     target_height: u32,
 ) -> Result<Vec<u8>, JsValue> {
-    // Copy the image data into a Vec to ensure proper memory management
-    // This prevents issues with WASM memory deallocation
-    let image_bytes = image_data.to_vec();
-    
     // Decode image from bytes (supports PNG and JPEG)
-    // Try PNG first, then JPEG
-    let img = ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Png)
-        .decode()
-        .or_else(|_| {
-            ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Jpeg)
-                .decode()
-        })
-        .map_err(|e| JsValue::from_str(&format!("Failed to decode image: {}", e)))?;
+    let img = decode_image(image_data)?;
 
     let (img_width, img_height) = img.dimensions();
     
@@ -138,19 +128,8 @@ pub fn preprocess_image_for_smolvlm_256m(
     target_width: u32,
     target_height: u32,
 ) -> Result<Vec<f32>, JsValue> {
-    // Copy the image data into a Vec to ensure proper memory management
-    // This prevents issues with WASM memory deallocation
-    let image_bytes = image_data.to_vec();
-    
     // Decode image from bytes (supports PNG and JPEG)
-    // Try PNG first, then JPEG
-    let img = ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Png)
-        .decode()
-        .or_else(|_| {
-            ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Jpeg)
-                .decode()
-        })
-        .map_err(|e| JsValue::from_str(&format!("Failed to decode image: {}", e)))?;
+    let img = decode_image(image_data)?;
 
     let (img_width, img_height) = img.dimensions();
     
@@ -182,6 +161,37 @@ pub fn preprocess_image_for_smolvlm_256m(
     Ok(normalized_data)
 }
 
+/// Preprocess image data for SmolVLM-256M with image splitting, like the Idefics3 processor
+/// Performs: decode, resize so the longest edge is max_size, round up to whole patches,
+/// split into patch_size squares plus a global view resized to one patch, CHW layout,
+/// normalization with image_mean/image_std 0.5
+/// SmolVLM-256M uses patch_size 512 and max_size 2048
+/// Returns all patches as one batch tensor with the grid needed for the prompt's image tokens
+#[wasm_bindgen]
+pub fn preprocess_image_tiles_for_smolvlm_256m(
+    image_data: &[u8],
+    patch_size: u32,
+    max_size: u32,
+) -> Result<SmolVlmTiles, JsValue> {
+    // Decode image from bytes (supports PNG and JPEG)
+    let img = decode_image(image_data)?;
+
+    tiling::tile_image(&img, patch_size, max_size).map_err(|e| JsValue::from_str(&e))
+}
+
+/// Get the patch grid preprocess_image_tiles_for_smolvlm_256m uses for an image of the given size
+/// Lets the prompt be built without preprocessing the image first
+#[wasm_bindgen]
+pub fn get_smolvlm_tile_grid(
+    source_width: u32,
+    source_height: u32,
+    patch_size: u32,
+    max_size: u32,
+) -> Result<TileGrid, JsValue> {
+    tiling::compute_tile_grid(source_width, source_height, patch_size, max_size)
+        .map_err(|e| JsValue::from_str(&e))
+}
+
 /// Apply contrast enhancement to RGBA image data
 /// contrast: -100.0 to 100.0 (0.0 = no change, positive = increase, negative = decrease)
 /// Returns processed image data as RGBA bytes
@@ -0,0 +1,294 @@
+use image::imageops::FilterType;
+use image::{DynamicImage, GenericImageView};
+use wasm_bindgen::prelude::*;
+
+/// SmolVLM (Idefics3) image_mean and image_std, the same for every channel
+const SMOLVLM_MEAN: f32 = 0.5;
+const SMOLVLM_STD: f32 = 0.5;
+
+/// How an image is split into patches, following the Idefics3 image processor
+/// The prompt gets one `<row_{r}_col_{c}>` block of image tokens per patch (1-based, row-major)
+/// followed by a `<global-img>` block; rows and cols are 0 when the image fits a single patch
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TileGrid {
+    pub rows: u32,
+    pub cols: u32,
+    pub patch_size: u32,
+    /// Size the image is resized to before splitting, multiples of patch_size
+    pub resized_width: u32,
+    pub resized_height: u32,
+}
+
+#[wasm_bindgen]
+impl TileGrid {
+    /// Patches in the batch: rows * cols grid patches plus the global view, or 1 unsplit image
+    #[wasm_bindgen(getter)]
+    pub fn num_patches(&self) -> u32 {
+        if self.is_split() {
+            self.rows * self.cols + 1
+        } else {
+            1
+        }
+    }
+}
+
+impl TileGrid {
+    fn is_split(&self) -> bool {
+        self.rows > 0 && self.cols > 0
+    }
+}
+
+/// Patches of one image as a single batch tensor for the vision encoder
+#[wasm_bindgen]
+pub struct SmolVlmTiles {
+    grid: TileGrid,
+    data: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl SmolVlmTiles {
+    #[wasm_bindgen(getter)]
+    pub fn grid(&self) -> TileGrid {
+        self.grid
+    }
+
+    /// pixel_values shape: [1, num_patches, 3, patch_size, patch_size]
+    #[wasm_bindgen(getter)]
+    pub fn shape(&self) -> Vec<u32> {
+        let patch_size = self.grid.patch_size;
+        vec![1, self.grid.num_patches(), 3, patch_size, patch_size]
+    }
+
+    /// Patches in row-major order with the global view last, each planar RGB (CHW)
+    /// normalized as (pixel / 255 - 0.5) / 0.5
+    /// Copies the whole batch on every read, see into_data
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<f32> {
+        self.data.clone()
+    }
+
+    /// Same as data but hands the buffer over without a copy
+    /// Frees the tiles, so read grid and shape first
+    pub fn into_data(self) -> Vec<f32> {
+        self.data
+    }
+}
+
+/// Work out the grid for a source image
+/// The longest edge is scaled to max_size keeping the aspect ratio, then both edges are
+/// rounded up to whole patches
+pub fn compute_tile_grid(
+    source_width: u32,
+    source_height: u32,
+    patch_size: u32,
+    max_size: u32,
+) -> Result<TileGrid, String> {
+    if source_width == 0 || source_height == 0 {
+        return Err("Source dimensions must be non-zero".to_string());
+    }
+    if patch_size == 0 || max_size < patch_size {
+        return Err(format!(
+            "Invalid tiling sizes: patch_size {} and max_size {} (max_size must be at least patch_size)",
+            patch_size, max_size
+        ));
+    }
+
+    // Rescale so the longest edge is max_size, the other edge rounded up to an even length
+    let aspect_ratio = source_width as f64 / source_height as f64;
+    let (width, height) = if source_width >= source_height {
+        let height = (max_size as f64 / aspect_ratio) as u32;
+        (max_size, (height + height % 2).max(1))
+    } else {
+        let width = (max_size as f64 * aspect_ratio) as u32;
+        ((width + width % 2).max(1), max_size)
+    };
+
+    // Round up to whole patches, deriving the shorter edge from the rounded longer one
+    let round_up = |length: u32| length.div_ceil(patch_size) * patch_size;
+    let aspect_ratio = width as f64 / height as f64;
+    let (resized_width, resized_height) = if width >= height {
+        let width = round_up(width);
+        (
+            width,
+            round_up(((width as f64 / aspect_ratio) as u32).max(1)),
+        )
+    } else {
+        let height = round_up(height);
+        (
+            round_up(((height as f64 * aspect_ratio) as u32).max(1)),
+            height,
+        )
+    };
+
+    let (rows, cols) = if resized_width > patch_size || resized_height > patch_size {
+        (resized_height / patch_size, resized_width / patch_size)
+    } else {
+        (0, 0)
+    };
+    Ok(TileGrid {
+        rows,
+        cols,
+        patch_size,
+        resized_width,
+        resized_height,
+    })
+}
+
+/// Split a decoded image into grid patches plus a downscaled global view using Lanczos3
+pub fn tile_image(
+    img: &DynamicImage,
+    patch_size: u32,
+    max_size: u32,
+) -> Result<SmolVlmTiles, String> {
+    let (img_width, img_height) = img.dimensions();
+    let grid = compute_tile_grid(img_width, img_height, patch_size, max_size)?;
+
+    let resized_img = img.resize_exact(
+        grid.resized_width,
+        grid.resized_height,
+        FilterType::Lanczos3,
+    );
+    let mut patches = Vec::with_capacity(grid.num_patches() as usize);
+    if grid.is_split() {
+        for row in 0..grid.rows {
+            for col in 0..grid.cols {
+                patches.push(resized_img.crop_imm(
+                    col * patch_size,
+                    row * patch_size,
+                    patch_size,
+                    patch_size,
+                ));
+            }
+        }
+        patches.push(resized_img.resize_exact(patch_size, patch_size, FilterType::Lanczos3));
+    } else {
+        patches.push(resized_img);
+    }
+
+    let plane_size = (patch_size * patch_size) as usize;
+    let mut data = vec![0.0_f32; patches.len() * plane_size * 3];
+    for (patch, patch_data) in patches.iter().zip(data.chunks_exact_mut(plane_size * 3)) {
+        for (i, pixel) in patch.to_rgb8().pixels().enumerate() {
+            for c in 0..3 {
+                patch_data[c * plane_size + i] =
+                    (pixel[c] as f32 / 255.0 - SMOLVLM_MEAN) / SMOLVLM_STD;
+            }
+        }
+    }
+
+    Ok(SmolVlmTiles { grid, data })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    fn grid_for(width: u32, height: u32, patch_size: u32, max_size: u32) -> TileGrid {
+        compute_tile_grid(width, height, patch_size, max_size).unwrap()
+    }
+
+    #[test]
+    fn splits_landscape_images_into_more_columns() {
+        let grid = grid_for(2000, 1000, 512, 2048);
+        assert_eq!((grid.resized_width, grid.resized_height), (2048, 1024));
+        assert_eq!((grid.rows, grid.cols), (2, 4));
+        assert_eq!(grid.num_patches(), 9);
+    }
+
+    #[test]
+    fn splits_portrait_images_into_more_rows() {
+        // 2048 / 3 rounds up to 1024 wide
+        let grid = grid_for(1000, 3000, 512, 2048);
+        assert_eq!((grid.resized_width, grid.resized_height), (1024, 2048));
+        assert_eq!((grid.rows, grid.cols), (4, 2));
+        assert_eq!(grid.num_patches(), 9);
+    }
+
+    #[test]
+    fn leaves_images_that_fit_one_patch_unsplit() {
+        let grid = grid_for(512, 512, 512, 512);
+        assert_eq!((grid.resized_width, grid.resized_height), (512, 512));
+        assert_eq!((grid.rows, grid.cols), (0, 0));
+        assert_eq!(grid.num_patches(), 1);
+
+        // A wide image still only needs one patch
+        let grid = grid_for(800, 200, 512, 512);
+        assert_eq!((grid.resized_width, grid.resized_height), (512, 512));
+        assert_eq!((grid.rows, grid.cols), (0, 0));
+    }
+
+    #[test]
+    fn scales_tiny_images_up_to_whole_patches() {
+        let grid = grid_for(1, 1, 512, 2048);
+        assert_eq!((grid.rows, grid.cols), (4, 4));
+        assert_eq!(grid.num_patches(), 17);
+
+        // The short edge never drops to zero
+        let grid = grid_for(1000, 1, 16, 64);
+        assert_eq!((grid.resized_width, grid.resized_height), (64, 16));
+        assert_eq!((grid.rows, grid.cols), (1, 4));
+    }
+
+    #[test]
+    fn rejects_invalid_sizes() {
+        assert!(compute_tile_grid(0, 10, 16, 64).is_err());
+        assert!(compute_tile_grid(10, 0, 16, 64).is_err());
+        assert!(compute_tile_grid(10, 10, 0, 64).is_err());
+        assert!(compute_tile_grid(10, 10, 64, 16).is_err());
+    }
+
+    #[test]
+    fn tiles_patches_row_major_in_chw_with_the_global_view_last() {
+        // Red on the left, blue on the right, already at the resized size
+        let img = DynamicImage::ImageRgb8(RgbImage::from_fn(8, 4, |x, _| {
+            if x < 4 {
+                Rgb([255, 0, 0])
+            } else {
+                Rgb([0, 0, 255])
+            }
+        }));
+        let tiles = tile_image(&img, 4, 8).unwrap();
+        let grid = tiles.grid();
+        assert_eq!((grid.rows, grid.cols), (1, 2));
+        assert_eq!(tiles.shape(), vec![1, 3, 3, 4, 4]);
+        let data = tiles.data();
+        assert_eq!(data.len(), (grid.num_patches() * 3 * 4 * 4) as usize);
+
+        let plane = |patch: usize, channel: usize| {
+            let start = (patch * 3 + channel) * 16;
+            data[start..start + 16].to_vec()
+        };
+        // (pixel / 255 - 0.5) / 0.5 maps 0 to -1 and 255 to 1
+        for (patch, expected) in [(0, [1.0, -1.0, -1.0]), (1, [-1.0, -1.0, 1.0])] {
+            for (channel, value) in expected.into_iter().enumerate() {
+                assert_eq!(plane(patch, channel), vec![value; 16]);
+            }
+        }
+        // The global view keeps red on its left and blue on its right
+        assert!(plane(2, 0)[0] > 0.0 && plane(2, 2)[0] < 0.0);
+        assert!(plane(2, 0)[3] < 0.0 && plane(2, 2)[3] > 0.0);
+    }
+
+    #[test]
+    fn data_holds_every_patch() {
+        for (width, height) in [(300, 100), (100, 300), (64, 64), (1, 1)] {
+            let img = DynamicImage::ImageRgb8(RgbImage::new(width, height));
+            let tiles = tile_image(&img, 16, 64).unwrap();
+            let grid = tiles.grid();
+            assert_eq!(
+                tiles.data().len(),
+                (grid.num_patches() * 3 * 16 * 16) as usize,
+                "{}x{}",
+                width,
+                height
+            );
+            assert!(tiles.data().iter().all(|&v| v == -1.0));
+        }
+        let img = DynamicImage::ImageRgb8(RgbImage::new(16, 16));
+        let tiles = tile_image(&img, 16, 16).unwrap();
+        assert_eq!(tiles.shape(), vec![1, 1, 3, 16, 16]);
+        assert_eq!(tiles.into_data(), vec![-1.0; 3 * 16 * 16]);
+    }
+}
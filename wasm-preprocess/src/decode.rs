@@ -0,0 +1,545 @@
+use image::{io::Reader as ImageReader, DynamicImage, GenericImageView, ImageError, ImageFormat};
+use std::fmt;
+use std::io::Cursor;
+use wasm_bindgen::JsValue;
+
+/// Encoded image formats recognised from their leading magic bytes
+/// WebP, GIF and BMP are only decoded when the matching cargo feature is enabled
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SourceFormat {
+    Png,
+    Jpeg,
+    WebP,
+    Gif,
+    Bmp,
+}
+
+impl SourceFormat {
+    /// Identify the format from the first bytes of the file, ignoring any file extension or MIME type
+    pub fn sniff(bytes: &[u8]) -> Option<SourceFormat> {
+        if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+            Some(SourceFormat::Png)
+        } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+            Some(SourceFormat::Jpeg)
+        } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+            Some(SourceFormat::WebP)
+        } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+            Some(SourceFormat::Gif)
+        } else if bytes.starts_with(b"BM") {
+            Some(SourceFormat::Bmp)
+        } else {
+            None
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SourceFormat::Png => "PNG",
+            SourceFormat::Jpeg => "JPEG",
+            SourceFormat::WebP => "WebP",
+            SourceFormat::Gif => "GIF",
+            SourceFormat::Bmp => "BMP",
+        }
+    }
+
+    /// Whether this build can decode the format (see the features in Cargo.toml)
+    pub fn is_enabled(self) -> bool {
+        match self {
+            SourceFormat::Png | SourceFormat::Jpeg => true,
+            SourceFormat::WebP => cfg!(feature = "webp"),
+            SourceFormat::Gif => cfg!(feature = "gif"),
+            SourceFormat::Bmp => cfg!(feature = "bmp"),
+        }
+    }
+
+    fn image_format(self) -> ImageFormat {
+        match self {
+            SourceFormat::Png => ImageFormat::Png,
+            SourceFormat::Jpeg => ImageFormat::Jpeg,
+            SourceFormat::WebP => ImageFormat::WebP,
+            SourceFormat::Gif => ImageFormat::Gif,
+            SourceFormat::Bmp => ImageFormat::Bmp,
+        }
+    }
+}
+
+/// Why decoding failed
+/// UnsupportedFormat means the bytes may be fine but this build can't read them,
+/// CorruptData means the format was recognised but the data is broken
+#[derive(Debug)]
+pub enum DecodeError {
+    Empty,
+    UnsupportedFormat(String),
+    CorruptData {
+        format: SourceFormat,
+        message: String,
+    },
+    SizeMismatch {
+        expected: (u32, u32),
+        actual: (u32, u32),
+    },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Empty => write!(f, "Failed to decode image: no image data"),
+            DecodeError::UnsupportedFormat(message) => {
+                write!(f, "Unsupported image format: {}", message)
+            }
+            DecodeError::CorruptData { format, message } => {
+                write!(f, "Corrupt {} data: {}", format.name(), message)
+            }
+            DecodeError::SizeMismatch { expected, actual } => write!(
+                f,
+                "Image size mismatch: expected {}x{}, decoded {}x{}",
+                expected.0, expected.1, actual.0, actual.1
+            ),
+        }
+    }
+}
+
+impl From<DecodeError> for JsValue {
+    fn from(error: DecodeError) -> JsValue {
+        JsValue::from_str(&error.to_string())
+    }
+}
+
+/// Decode an encoded image and turn it upright according to its EXIF orientation
+/// GIFs decode to their first frame
+/// source_width and source_height are the dimensions the caller saw (e.g. HTMLImageElement.naturalWidth),
+/// checked against the decoded image either before or after orientation; pass 0 to skip the check
+pub fn decode_image(
+    bytes: &[u8],
+    source_width: u32,
+    source_height: u32,
+) -> Result<DynamicImage, DecodeError> {
+    if bytes.is_empty() {
+        return Err(DecodeError::Empty);
+    }
+    let format = SourceFormat::sniff(bytes)
+        .ok_or_else(|| DecodeError::UnsupportedFormat("unrecognised file signature".to_string()))?;
+    if !format.is_enabled() {
+        return Err(DecodeError::UnsupportedFormat(format!(
+            "{} support is not enabled in this build",
+            format.name()
+        )));
+    }
+
+    let img = ImageReader::with_format(Cursor::new(bytes), format.image_format())
+        .decode()
+        .map_err(|e| match e {
+            ImageError::Unsupported(e) => {
+                DecodeError::UnsupportedFormat(format!("{}: {}", format.name(), e))
+            }
+            e => DecodeError::CorruptData {
+                format,
+                message: e.to_string(),
+            },
+        })?;
+
+    let stored = img.dimensions();
+    let img = apply_orientation(img, exif_orientation(bytes, format));
+    let upright = img.dimensions();
+    if source_width != 0 && source_height != 0 {
+        let expected = (source_width, source_height);
+        if expected != stored && expected != upright {
+            return Err(DecodeError::SizeMismatch {
+                expected,
+                actual: upright,
+            });
+        }
+    }
+
+    Ok(img)
+}
+
+/// Rotate and flip as described by an EXIF orientation value (1-8, 1 being upright)
+fn apply_orientation(img: DynamicImage, orientation: u16) -> DynamicImage {
+    match orientation {
+        2 => img.fliph(),
+        3 => img.rotate180(),
+        4 => img.flipv(),
+        5 => img.rotate90().fliph(),
+        6 => img.rotate90(),
+        7 => img.rotate270().fliph(),
+        8 => img.rotate270(),
+        _ => img,
+    }
+}
+
+/// EXIF orientation of the image, 1 (upright) when there is no EXIF block or it can't be read
+fn exif_orientation(bytes: &[u8], format: SourceFormat) -> u16 {
+    let exif = match format {
+        SourceFormat::Jpeg => jpeg_exif(bytes),
+        SourceFormat::Png => png_exif(bytes),
+        SourceFormat::WebP => webp_exif(bytes),
+        SourceFormat::Gif | SourceFormat::Bmp => None,
+    };
+    exif.and_then(tiff_orientation).unwrap_or(1)
+}
+
+/// TIFF block of the APP1 Exif segment, searched up to the start of the image data
+fn jpeg_exif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() && bytes[pos] == 0xFF {
+        let marker = bytes[pos + 1];
+        match marker {
+            // Fill byte before a marker
+            0xFF => {
+                pos += 1;
+                continue;
+            }
+            // Markers without a length
+            0x01 | 0xD0..=0xD8 => {
+                pos += 2;
+                continue;
+            }
+            // Start of scan or end of image, no more metadata
+            0xDA | 0xD9 => return None,
+            _ => {}
+        }
+        let length = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        let segment = bytes.get(pos + 4..pos + 2 + length)?;
+        if marker == 0xE1 && segment.starts_with(b"Exif\0\0") {
+            return Some(&segment[6..]);
+        }
+        pos += 2 + length;
+    }
+    None
+}
+
+/// Contents of the eXIf chunk
+fn png_exif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut pos = 8;
+    while pos + 8 <= bytes.len() {
+        let length = u32::from_be_bytes(bytes[pos..pos + 4].try_into().ok()?) as usize;
+        let kind = &bytes[pos + 4..pos + 8];
+        let data = bytes.get(pos + 8..pos + 8 + length)?;
+        match kind {
+            b"eXIf" => return Some(data),
+            b"IEND" => return None,
+            _ => {}
+        }
+        // Length, type, data and CRC
+        pos += 12 + length;
+    }
+    None
+}
+
+/// Contents of the EXIF chunk, some encoders keep the JPEG style "Exif" prefix
+fn webp_exif(bytes: &[u8]) -> Option<&[u8]> {
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let length = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().ok()?) as usize;
+        let data = bytes.get(pos + 8..pos + 8 + length)?;
+        if &bytes[pos..pos + 4] == b"EXIF" {
+            return Some(data.strip_prefix(b"Exif\0\0").unwrap_or(data));
+        }
+        // Chunks are padded to an even length
+        pos += 8 + length + (length & 1);
+    }
+    None
+}
+
+/// Orientation tag (0x0112) from the first IFD of a TIFF header
+fn tiff_orientation(tiff: &[u8]) -> Option<u16> {
+    let little_endian = match tiff.get(..2)? {
+        b"II" => true,
+        b"MM" => false,
+        _ => return None,
+    };
+    let read_u16 = |at: usize| -> Option<u16> {
+        let b: [u8; 2] = tiff.get(at..at + 2)?.try_into().ok()?;
+        Some(if little_endian {
+            u16::from_le_bytes(b)
+        } else {
+            u16::from_be_bytes(b)
+        })
+    };
+    let read_u32 = |at: usize| -> Option<u32> {
+        let b: [u8; 4] = tiff.get(at..at + 4)?.try_into().ok()?;
+        Some(if little_endian {
+            u32::from_le_bytes(b)
+        } else {
+            u32::from_be_bytes(b)
+        })
+    };
+    if read_u16(2)? != 42 {
+        return None;
+    }
+    let ifd = read_u32(4)? as usize;
+    let entries = read_u16(ifd)? as usize;
+    (0..entries)
+        .map(|i| ifd + 2 + i * 12)
+        .find(|&entry| read_u16(entry) == Some(0x0112))
+        // A SHORT value sits in the first two bytes of the value field
+        .and_then(|entry| read_u16(entry + 8))
+        .filter(|orientation| (1..=8).contains(orientation))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageOutputFormat, Rgb, RgbImage};
+
+    /// 3x2 image where every pixel is distinct, red is x and green is y
+    fn test_image() -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(3, 2, |x, y| Rgb([x as u8, y as u8, 0])))
+    }
+
+    fn encode(img: &DynamicImage, format: ImageOutputFormat) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        img.write_to(&mut bytes, format).unwrap();
+        bytes.into_inner()
+    }
+
+    /// TIFF header with a single IFD entry holding the orientation
+    fn tiff(orientation: u16, little_endian: bool) -> Vec<u8> {
+        let u16_bytes = |v: u16| {
+            if little_endian {
+                v.to_le_bytes()
+            } else {
+                v.to_be_bytes()
+            }
+        };
+        let u32_bytes = |v: u32| {
+            if little_endian {
+                v.to_le_bytes()
+            } else {
+                v.to_be_bytes()
+            }
+        };
+        let mut tiff = if little_endian {
+            b"II".to_vec()
+        } else {
+            b"MM".to_vec()
+        };
+        tiff.extend(u16_bytes(42));
+        tiff.extend(u32_bytes(8));
+        tiff.extend(u16_bytes(1));
+        // Tag, type SHORT, count 1, value padded to 4 bytes
+        tiff.extend(u16_bytes(0x0112));
+        tiff.extend(u16_bytes(3));
+        tiff.extend(u32_bytes(1));
+        tiff.extend(u16_bytes(orientation));
+        tiff.extend([0, 0]);
+        // No next IFD
+        tiff.extend([0; 4]);
+        tiff
+    }
+
+    /// JPEG with an APP1 Exif segment after the encoder's APP0 segment
+    fn jpeg_with_exif(img: &DynamicImage, tiff: &[u8]) -> Vec<u8> {
+        let mut jpeg = encode(img, ImageOutputFormat::Jpeg(90));
+        assert_eq!(&jpeg[2..4], &[0xFF, 0xE0]);
+        let app0_end = 4 + u16::from_be_bytes([jpeg[4], jpeg[5]]) as usize;
+        let mut app1 = vec![0xFF, 0xE1];
+        app1.extend((2 + 6 + tiff.len() as u16).to_be_bytes());
+        app1.extend(b"Exif\0\0");
+        app1.extend(tiff);
+        jpeg.splice(app0_end..app0_end, app1);
+        jpeg
+    }
+
+    fn crc32(bytes: &[u8]) -> u32 {
+        let mut crc = !0_u32;
+        for &byte in bytes {
+            crc ^= byte as u32;
+            for _ in 0..8 {
+                crc = if crc & 1 != 0 {
+                    (crc >> 1) ^ 0xEDB8_8320
+                } else {
+                    crc >> 1
+                };
+            }
+        }
+        !crc
+    }
+
+    /// PNG with an eXIf chunk right after IHDR
+    fn png_with_exif(img: &DynamicImage, tiff: &[u8]) -> Vec<u8> {
+        let mut png = encode(img, ImageOutputFormat::Png);
+        assert_eq!(&png[12..16], b"IHDR");
+        let ihdr_end = 8 + 12 + 13;
+        let mut chunk = (tiff.len() as u32).to_be_bytes().to_vec();
+        chunk.extend(b"eXIf");
+        chunk.extend(tiff);
+        chunk.extend(crc32(&chunk[4..]).to_be_bytes());
+        png.splice(ihdr_end..ihdr_end, chunk);
+        png
+    }
+
+    fn webp_chunk(kind: &[u8; 4], data: &[u8]) -> Vec<u8> {
+        let mut chunk = kind.to_vec();
+        chunk.extend((data.len() as u32).to_le_bytes());
+        chunk.extend(data);
+        if data.len() % 2 == 1 {
+            chunk.push(0);
+        }
+        chunk
+    }
+
+    fn webp(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body: Vec<u8> = chunks.concat();
+        let mut webp = b"RIFF".to_vec();
+        webp.extend((4 + body.len() as u32).to_le_bytes());
+        webp.extend(b"WEBP");
+        webp.extend(body);
+        webp
+    }
+
+    #[test]
+    fn sniffs_formats_from_magic_bytes() {
+        let cases: [(&[u8], Option<SourceFormat>); 8] = [
+            (b"\x89PNG\r\n\x1a\n....", Some(SourceFormat::Png)),
+            (&[0xFF, 0xD8, 0xFF, 0xE0], Some(SourceFormat::Jpeg)),
+            (b"RIFF\0\0\0\0WEBPVP8 ", Some(SourceFormat::WebP)),
+            (b"RIFF\0\0\0\0WAVEfmt ", None),
+            (b"GIF89a..", Some(SourceFormat::Gif)),
+            (b"GIF87a..", Some(SourceFormat::Gif)),
+            (b"BM......", Some(SourceFormat::Bmp)),
+            (b"\x89PN", None),
+        ];
+        for (bytes, format) in cases {
+            assert_eq!(SourceFormat::sniff(bytes), format, "{:?}", bytes);
+        }
+    }
+
+    #[test]
+    fn classifies_decode_errors() {
+        assert!(matches!(decode_image(&[], 0, 0), Err(DecodeError::Empty)));
+        assert!(matches!(
+            decode_image(b"not an image", 0, 0),
+            Err(DecodeError::UnsupportedFormat(_))
+        ));
+
+        // A recognised format with broken data is corrupt, not unsupported
+        let png = encode(&test_image(), ImageOutputFormat::Png);
+        let truncated = &png[..png.len() / 2];
+        assert!(matches!(
+            decode_image(truncated, 0, 0),
+            Err(DecodeError::CorruptData {
+                format: SourceFormat::Png,
+                ..
+            })
+        ));
+        let mut broken_jpeg = vec![0xFF, 0xD8, 0xFF, 0xE0];
+        broken_jpeg.extend([0; 16]);
+        assert!(matches!(
+            decode_image(&broken_jpeg, 0, 0),
+            Err(DecodeError::CorruptData {
+                format: SourceFormat::Jpeg,
+                ..
+            })
+        ));
+
+        assert!(matches!(
+            decode_image(&png, 5, 5),
+            Err(DecodeError::SizeMismatch {
+                expected: (5, 5),
+                actual: (3, 2),
+            })
+        ));
+        assert_eq!(decode_image(&png, 3, 2).unwrap().dimensions(), (3, 2));
+    }
+
+    #[test]
+    fn applies_all_eight_orientations() {
+        let img = test_image();
+        let (w, h) = (3, 2);
+        // Where the stored pixel (x, y) ends up in the upright image, per the EXIF spec
+        let upright = |orientation: u16, x: u32, y: u32| match orientation {
+            1 => (x, y),
+            2 => (w - 1 - x, y),
+            3 => (w - 1 - x, h - 1 - y),
+            4 => (x, h - 1 - y),
+            5 => (y, x),
+            6 => (h - 1 - y, x),
+            7 => (h - 1 - y, w - 1 - x),
+            8 => (y, w - 1 - x),
+            _ => unreachable!(),
+        };
+        for orientation in 1..=8 {
+            let turned = apply_orientation(img.clone(), orientation).to_rgb8();
+            let expected = if orientation <= 4 { (w, h) } else { (h, w) };
+            assert_eq!(turned.dimensions(), expected, "orientation {}", orientation);
+            for (x, y) in (0..h).flat_map(|y| (0..w).map(move |x| (x, y))) {
+                let (ux, uy) = upright(orientation, x, y);
+                assert_eq!(
+                    turned.get_pixel(ux, uy).0,
+                    [x as u8, y as u8, 0],
+                    "orientation {}, pixel ({}, {})",
+                    orientation,
+                    x,
+                    y
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn reads_the_orientation_tag_in_either_byte_order() {
+        for orientation in 1..=8 {
+            assert_eq!(
+                tiff_orientation(&tiff(orientation, true)),
+                Some(orientation)
+            );
+            assert_eq!(
+                tiff_orientation(&tiff(orientation, false)),
+                Some(orientation)
+            );
+        }
+        assert_eq!(tiff_orientation(&tiff(0, true)), None);
+        assert_eq!(tiff_orientation(&tiff(9, false)), None);
+        assert_eq!(tiff_orientation(b"XX\x2a\0\x08\0\0\0"), None);
+        // Truncated IFD
+        assert_eq!(tiff_orientation(&tiff(6, true)[..12]), None);
+    }
+
+    #[test]
+    fn finds_exif_in_jpeg_app1_segments() {
+        let img = test_image();
+        let jpeg = jpeg_with_exif(&img, &tiff(6, false));
+        assert_eq!(jpeg_exif(&jpeg), Some(&tiff(6, false)[..]));
+        assert_eq!(exif_orientation(&jpeg, SourceFormat::Jpeg), 6);
+        let decoded = decode_image(&jpeg, 0, 0).unwrap();
+        assert_eq!(decoded.dimensions(), (2, 3));
+
+        let plain = encode(&img, ImageOutputFormat::Jpeg(90));
+        assert_eq!(jpeg_exif(&plain), None);
+        // A segment running past the end of the file
+        assert_eq!(jpeg_exif(&[0xFF, 0xD8, 0xFF, 0xE1, 0x01, 0x00, b'E']), None);
+    }
+
+    #[test]
+    fn finds_exif_in_png_chunks() {
+        let img = test_image();
+        let png = png_with_exif(&img, &tiff(8, true));
+        assert_eq!(png_exif(&png), Some(&tiff(8, true)[..]));
+        let decoded = decode_image(&png, 0, 0).unwrap().to_rgb8();
+        assert_eq!(decoded.dimensions(), (2, 3));
+        // Orientation 8: the stored top right corner is the upright top left
+        assert_eq!(decoded.get_pixel(0, 0).0, [2, 0, 0]);
+        // Either the stored or the upright size passes the check
+        assert!(decode_image(&png, 3, 2).is_ok());
+        assert!(decode_image(&png, 2, 3).is_ok());
+
+        assert_eq!(png_exif(&encode(&img, ImageOutputFormat::Png)), None);
+    }
+
+    #[test]
+    fn finds_exif_in_webp_chunks() {
+        let tiff = tiff(3, true);
+        let mut prefixed = b"Exif\0\0".to_vec();
+        prefixed.extend(&tiff);
+        // The odd length VP8X payload is padded before the next chunk
+        let with_prefix = webp(&[webp_chunk(b"VP8X", &[0; 9]), webp_chunk(b"EXIF", &prefixed)]);
+        assert_eq!(webp_exif(&with_prefix), Some(&tiff[..]));
+        assert_eq!(exif_orientation(&with_prefix, SourceFormat::WebP), 3);
+        let bare = webp(&[webp_chunk(b"EXIF", &tiff)]);
+        assert_eq!(webp_exif(&bare), Some(&tiff[..]));
+
+        assert_eq!(webp_exif(&webp(&[webp_chunk(b"VP8 ", &[0; 10])])), None);
+    }
+}
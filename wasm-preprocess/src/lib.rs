@@ -1,8 +1,15 @@
 use wasm_bindgen::prelude::*;
-use image::{io::Reader as ImageReader, ImageFormat, GenericImageView};
-use std::io::Cursor;
 use std::sync::{LazyLock, Mutex};
 
+mod decode;
+mod model;
+mod resize;
+
+use decode::decode_image;
+pub use model::{ChannelOrder, ModelSpec, ModelTensor, OutputType, TensorLayout};
+use resize::resize_image;
+pub use resize::{LetterboxedImage, Placement, ResizeMode};
+
 // State management pattern similar to wasm-astar
 // Learned about this pattern from rocket_wasm on github
 // https://github.com/aochagavia/rocket_wasm/blob/d0ca51beb9c7c351a1f0266206edfd553bf078d3/src/lib.rs
@@ -46,32 +53,21 @@ pub fn init() {
 /// Preprocess image data by resizing to target dimensions using high-quality Lanczos3 filtering
 /// Returns preprocessed image data as RGBA bytes
 /// This is a building block for ML/AI preprocessing pipelines
-/// Note: source_width and source_height are kept for API compatibility but dimensions are determined from decoded image
+/// source_width and source_height are checked against the decoded image, pass 0 when unknown
 #[wasm_bindgen]
 pub fn preprocess_image(
     image_data: &[u8],
-    _source_width: u32,
-    _source_height: u32,
+    source_width: u32,
+    source_height: u32,
     target_width: u32,
     target_height: u32,
 ) -> Result<Vec<u8>, JsValue> {
-    // Copy the image data into a Vec to ensure proper memory management
-    // This prevents issues with WASM memory deallocation
-    let image_bytes = image_data.to_vec();
-    
-    // Decode image from bytes (supports PNG and JPEG)
-    // Try PNG first, then JPEG
-    let img = ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Png)
-        .decode()
-        .or_else(|_| {
-            ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Jpeg)
-                .decode()
-        })
-        .map_err(|e| JsValue::from_str(&format!("Failed to decode image: {}", e)))?;
+    // Decode from the sniffed format and turn upright per EXIF orientation
+    let img = decode_image(image_data, source_width, source_height)?;
 
     // Resize using Lanczos3 filter for high-quality resizing
     // Lanczos3 provides excellent quality for ML model preprocessing
-    let resized_img = img.resize_exact(target_width, target_height, image::imageops::FilterType::Lanczos3);
+    let (resized_img, _) = resize_image(&img, ResizeMode::Stretch, target_width, target_height, 0);
 
     // Convert to RGBA format
     let rgba_img = resized_img.to_rgba8();
@@ -87,37 +83,17 @@ pub fn preprocess_image(
 #[wasm_bindgen]
 pub fn preprocess_image_crop(
     image_data: &[u8],
-    _source_width: u32,
-    _source_height: u32,
+    source_width: u32,
+    source_height: u32,
     target_width: u32,
     target_height: u32,
 ) -> Result<Vec<u8>, JsValue> {
-    // Copy the image data into a Vec to ensure proper memory management
-    // This prevents issues with WASM memory deallocation
-    let image_bytes = image_data.to_vec();
-    
-    // Decode image from bytes (supports PNG and JPEG)
-    // Try PNG first, then JPEG
-    let img = ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Png)
-        .decode()
-        .or_else(|_| {
-            ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Jpeg)
-                .decode()
-        })
-        .map_err(|e| JsValue::from_str(&format!("Failed to decode image: {}", e)))?;
+    // Decode from the sniffed format and turn upright per EXIF orientation
+    let img = decode_image(image_data, source_width, source_height)?;
 
-    let (img_width, img_height) = img.dimensions();
-    
-    // Calculate center crop to square (use smaller dimension)
-    let crop_size = img_width.min(img_height);
-    let crop_x = (img_width - crop_size) / 2;
-    let crop_y = (img_height - crop_size) / 2;
-    
-    // Crop to square
-    let cropped_img = img.crop_imm(crop_x, crop_y, crop_size, crop_size);
-    
-    // Resize cropped square to target dimensions using Lanczos3
-    let resized_img = cropped_img.resize_exact(target_width, target_height, image::imageops::FilterType::Lanczos3);
+    // Crop to a centered square, then resize to target dimensions using Lanczos3
+    let (resized_img, _) =
+        resize_image(&img, ResizeMode::CenterCrop, target_width, target_height, 0);
 
     // Convert to RGBA format
     let rgba_img = resized_img.to_rgba8();
@@ -126,6 +102,39 @@ pub fn preprocess_image_crop(
     Ok(rgba_img.into_raw())
 }
 
+/// Preprocess image data by scaling it to fit inside the target dimensions without cropping or distorting
+/// The bars left on two sides are filled with pad_color (0xRRGGBB)
+/// Returns the RGBA bytes together with the placement that maps coordinates in the result back to source pixels
+#[wasm_bindgen]
+pub fn preprocess_image_letterbox(
+    image_data: &[u8],
+    source_width: u32,
+    source_height: u32,
+    target_width: u32,
+    target_height: u32,
+    pad_color: u32,
+) -> Result<LetterboxedImage, JsValue> {
+    if target_width == 0 || target_height == 0 {
+        return Err(JsValue::from_str("Target dimensions must be non-zero"));
+    }
+
+    // Decode from the sniffed format and turn upright per EXIF orientation
+    let img = decode_image(image_data, source_width, source_height)?;
+
+    // Fit inside the target using Lanczos3, then pad to the target dimensions
+    let (resized_img, placement) = resize_image(
+        &img,
+        ResizeMode::Letterbox,
+        target_width,
+        target_height,
+        pad_color,
+    );
+
+    // Convert to RGBA format, keeping the placement for mapping detections back
+    let rgba_img = resized_img.to_rgba8();
+    Ok(LetterboxedImage::new(rgba_img.into_raw(), placement))
+}
+
 /// Preprocess image data specifically for SmolVLM-500M model
 /// Performs: decode, center crop, resize, RGB conversion, normalization
 /// Returns normalized Float32Array (shape: [height * width * 3]) for ONNX Runtime
@@ -133,53 +142,34 @@ pub fn preprocess_image_crop(
 #[wasm_bindgen]
 pub fn preprocess_image_for_smolvlm(
     image_data: &[u8],
-    _source_width: u32,
-    _source_height: u32,
+    source_width: u32,
+    source_height: u32,
     target_width: u32,
     target_height: u32,
 ) -> Result<Vec<f32>, JsValue> {
-    // Copy the image data into a Vec to ensure proper memory management
-    // This prevents issues with WASM memory deallocation
-    let image_bytes = image_data.to_vec();
-    
-    // Decode image from bytes (supports PNG and JPEG)
-    // Try PNG first, then JPEG
-    let img = ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Png)
-        .decode()
-        .or_else(|_| {
-            ImageReader::with_format(Cursor::new(&image_bytes), ImageFormat::Jpeg)
-                .decode()
-        })
-        .map_err(|e| JsValue::from_str(&format!("Failed to decode image: {}", e)))?;
-
-    let (img_width, img_height) = img.dimensions();
-    
-    // Calculate center crop to square (use smaller dimension)
-    let crop_size = img_width.min(img_height);
-    let crop_x = (img_width - crop_size) / 2;
-    let crop_y = (img_height - crop_size) / 2;
-    
-    // Crop to square
-    let cropped_img = img.crop_imm(crop_x, crop_y, crop_size, crop_size);
-    
-    // Resize cropped square to target dimensions using Lanczos3
-    let resized_img = cropped_img.resize_exact(target_width, target_height, image::imageops::FilterType::Lanczos3);
+    // Decode from the sniffed format and turn upright per EXIF orientation
+    let img = decode_image(image_data, source_width, source_height)?;
 
-    // Convert to RGB format (remove alpha channel)
-    let rgb_img = resized_img.to_rgb8();
-    
-    // Normalize pixel values to [0.0, 1.0] range and convert to Float32Array
+    // Center crop, resize and scale to [0.0, 1.0] without mean/std normalization
     // Output format: [R, G, B, R, G, B, ...] flattened (height * width * 3)
-    let mut normalized_data = Vec::with_capacity((target_width * target_height * 3) as usize);
-    
-    for pixel in rgb_img.pixels() {
-        // Normalize each channel: pixel_value / 255.0
-        normalized_data.push(pixel[0] as f32 / 255.0); // R
-        normalized_data.push(pixel[1] as f32 / 255.0); // G
-        normalized_data.push(pixel[2] as f32 / 255.0); // B
-    }
-    
-    Ok(normalized_data)
+    let mut spec = ModelSpec::new(target_width, target_height);
+    spec.layout = TensorLayout::Hwc;
+    let tensor = model::image_to_tensor(&img, &spec).map_err(|e| JsValue::from_str(&e))?;
+
+    Ok(tensor.into_f32_data())
+}
+
+/// Preprocess image data for any ONNX vision model described by a ModelSpec
+/// Performs: decode, resize (see ResizeMode), channel reordering, mean/std normalization and layout
+/// Start from ModelSpec.preset("smolvlm" | "vit-gpt2" | "clip") or new ModelSpec(width, height)
+/// Returns a ModelTensor whose shape, dtype and data can be passed straight to ort.Tensor,
+/// and whose placement maps tensor coordinates back to source pixels
+#[wasm_bindgen]
+pub fn preprocess_for_model(image_data: &[u8], spec: &ModelSpec) -> Result<ModelTensor, JsValue> {
+    // Source dimensions are not known here, skip the size check
+    let img = decode_image(image_data, 0, 0)?;
+
+    model::image_to_tensor(&img, spec).map_err(|e| JsValue::from_str(&e))
 }
 
 /// Apply contrast enhancement to RGBA image data
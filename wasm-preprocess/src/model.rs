@@ -0,0 +1,435 @@
+use image::DynamicImage;
+use wasm_bindgen::prelude::*;
+
+use crate::resize::{resize_image, Placement, ResizeMode};
+
+/// Channel order of the output tensor
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChannelOrder {
+    Rgb = 0,
+    /// OpenCV-style models trained on BGR input
+    Bgr = 1,
+}
+
+/// Memory layout of the output tensor
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TensorLayout {
+    /// Interleaved channels, shape [1, height, width, 3]
+    Hwc = 0,
+    /// Planar channels, shape [1, 3, height, width], what most ONNX vision encoders expect
+    Chw = 1,
+}
+
+/// Element type of the output tensor
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OutputType {
+    F32 = 0,
+    /// IEEE 754 half precision, returned as raw bits for ort.Tensor('float16', Uint16Array)
+    F16 = 1,
+}
+
+/// Everything a vision model needs from preprocessing
+/// Each channel becomes (pixel / 255 - mean) / std
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct ModelSpec {
+    pub target_width: u32,
+    pub target_height: u32,
+    pub resize_mode: ResizeMode,
+    pub channel_order: ChannelOrder,
+    pub layout: TensorLayout,
+    pub output_type: OutputType,
+    /// Colour of the Letterbox bars as 0xRRGGBB
+    pub pad_color: u32,
+    mean: Vec<f32>,
+    std: Vec<f32>,
+}
+
+#[wasm_bindgen]
+impl ModelSpec {
+    /// Plain [0, 1] RGB in CHW layout; adjust the fields or start from a preset
+    #[wasm_bindgen(constructor)]
+    pub fn new(target_width: u32, target_height: u32) -> ModelSpec {
+        ModelSpec {
+            target_width,
+            target_height,
+            resize_mode: ResizeMode::CenterCrop,
+            channel_order: ChannelOrder::Rgb,
+            layout: TensorLayout::Chw,
+            output_type: OutputType::F32,
+            pad_color: 0x000000,
+            mean: vec![0.0; 3],
+            std: vec![1.0; 3],
+        }
+    }
+
+    /// Built-in specs matching the Hugging Face preprocessor configs
+    /// "smolvlm": SmolVLM (Idefics3), 512x512 stretched like its unsplit global view, mean/std 0.5
+    /// "vit-gpt2": ViT-GPT2 image captioning, 224x224 stretched, mean/std 0.5
+    /// "clip": CLIP ViT, 224x224 center crop, OpenAI mean/std
+    pub fn preset(name: &str) -> Result<ModelSpec, JsValue> {
+        let spec = match name {
+            "smolvlm" => ModelSpec {
+                resize_mode: ResizeMode::Stretch,
+                ..ModelSpec::new(512, 512).with_normalization([0.5; 3], [0.5; 3])
+            },
+            "vit-gpt2" => ModelSpec {
+                resize_mode: ResizeMode::Stretch,
+                ..ModelSpec::new(224, 224).with_normalization([0.5; 3], [0.5; 3])
+            },
+            "clip" => ModelSpec::new(224, 224).with_normalization(
+                [0.481_454_66, 0.457_827_5, 0.408_210_73],
+                [0.268_629_54, 0.261_302_6, 0.275_777_1],
+            ),
+            _ => {
+                return Err(JsValue::from_str(&format!(
+                    "Unknown model preset: {}",
+                    name
+                )))
+            }
+        };
+        Ok(spec)
+    }
+
+    /// Per-channel mean in RGB order, subtracted after scaling to [0, 1]
+    #[wasm_bindgen(getter)]
+    pub fn mean(&self) -> Vec<f32> {
+        self.mean.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_mean(&mut self, mean: Vec<f32>) {
+        self.mean = mean;
+    }
+
+    /// Per-channel standard deviation in RGB order, divided by after subtracting the mean
+    #[wasm_bindgen(getter)]
+    pub fn std(&self) -> Vec<f32> {
+        self.std.clone()
+    }
+
+    #[wasm_bindgen(setter)]
+    pub fn set_std(&mut self, std: Vec<f32>) {
+        self.std = std;
+    }
+}
+
+impl ModelSpec {
+    fn with_normalization(self, mean: [f32; 3], std: [f32; 3]) -> ModelSpec {
+        ModelSpec {
+            mean: mean.to_vec(),
+            std: std.to_vec(),
+            ..self
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.target_width == 0 || self.target_height == 0 {
+            return Err("Target dimensions must be non-zero".to_string());
+        }
+        if self.mean.len() != 3 || self.std.len() != 3 {
+            return Err("mean and std need one value per channel (3)".to_string());
+        }
+        if self.std.contains(&0.0) {
+            return Err("std values must be non-zero".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// Model input produced by preprocess_for_model
+/// Only the data getter matching output_type is filled, the other one is empty
+#[wasm_bindgen]
+pub struct ModelTensor {
+    shape: Vec<u32>,
+    f32_data: Vec<f32>,
+    f16_data: Vec<u16>,
+    pub output_type: OutputType,
+    placement: Placement,
+}
+
+#[wasm_bindgen]
+impl ModelTensor {
+    /// Dimensions for ort.Tensor, see TensorLayout
+    #[wasm_bindgen(getter)]
+    pub fn shape(&self) -> Vec<u32> {
+        self.shape.clone()
+    }
+
+    /// ONNX Runtime type name: "float32" or "float16"
+    #[wasm_bindgen(getter)]
+    pub fn dtype(&self) -> String {
+        match self.output_type {
+            OutputType::F32 => "float32".to_string(),
+            OutputType::F16 => "float16".to_string(),
+        }
+    }
+
+    /// Scale and offset of the source image within the tensor, for mapping model outputs back
+    #[wasm_bindgen(getter)]
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+
+    /// Copies the tensor on every read, see into_f32_data
+    #[wasm_bindgen(getter)]
+    pub fn f32_data(&self) -> Vec<f32> {
+        self.f32_data.clone()
+    }
+
+    /// Copies the tensor on every read, see into_f16_data
+    #[wasm_bindgen(getter)]
+    pub fn f16_data(&self) -> Vec<u16> {
+        self.f16_data.clone()
+    }
+
+    /// Same as f32_data but hands the buffer over without a copy
+    /// Frees the tensor, so read shape, dtype and placement first
+    pub fn into_f32_data(self) -> Vec<f32> {
+        self.f32_data
+    }
+
+    /// Same as f16_data but hands the buffer over without a copy, see into_f32_data
+    pub fn into_f16_data(self) -> Vec<u16> {
+        self.f16_data
+    }
+}
+
+/// Resize, reorder, normalize and lay out a decoded image as described by the spec
+pub fn image_to_tensor(img: &DynamicImage, spec: &ModelSpec) -> Result<ModelTensor, String> {
+    spec.validate()?;
+    let (width, height) = (spec.target_width, spec.target_height);
+    let (resized_img, placement) =
+        resize_image(img, spec.resize_mode, width, height, spec.pad_color);
+    let rgb_img = resized_img.to_rgb8();
+
+    // Source channel for each output channel
+    let channels: [usize; 3] = match spec.channel_order {
+        ChannelOrder::Rgb => [0, 1, 2],
+        ChannelOrder::Bgr => [2, 1, 0],
+    };
+    let plane_size = (width * height) as usize;
+    let mut data = vec![0.0_f32; plane_size * 3];
+    for (i, pixel) in rgb_img.pixels().enumerate() {
+        for (c, &source) in channels.iter().enumerate() {
+            let value = (pixel[source] as f32 / 255.0 - spec.mean[source]) / spec.std[source];
+            let index = match spec.layout {
+                TensorLayout::Hwc => i * 3 + c,
+                TensorLayout::Chw => c * plane_size + i,
+            };
+            data[index] = value;
+        }
+    }
+
+    let shape = match spec.layout {
+        TensorLayout::Hwc => vec![1, height, width, 3],
+        TensorLayout::Chw => vec![1, 3, height, width],
+    };
+    let (f32_data, f16_data) = match spec.output_type {
+        OutputType::F32 => (data, Vec::new()),
+        OutputType::F16 => (Vec::new(), data.into_iter().map(f32_to_f16_bits).collect()),
+    };
+    Ok(ModelTensor {
+        shape,
+        f32_data,
+        f16_data,
+        output_type: spec.output_type,
+        placement,
+    })
+}
+
+/// Round an f32 to the nearest f16 (ties to even) and return its bits
+fn f32_to_f16_bits(value: f32) -> u16 {
+    let bits = value.to_bits();
+    let sign = ((bits >> 16) & 0x8000) as u16;
+    let exponent = ((bits >> 23) & 0xFF) as i32;
+    let mantissa = bits & 0x007F_FFFF;
+
+    // Infinity stays infinity, NaN stays a (quiet) NaN
+    if exponent == 0xFF {
+        return sign | 0x7C00 | if mantissa != 0 { 0x0200 } else { 0 };
+    }
+    let half_exponent = exponent - 127 + 15;
+    if half_exponent >= 0x1F {
+        return sign | 0x7C00;
+    }
+
+    // Subnormal halves keep the implicit leading bit in the mantissa
+    let (half, mantissa, shift) = if half_exponent <= 0 {
+        if half_exponent < -10 {
+            return sign;
+        }
+        let mantissa = mantissa | 0x0080_0000;
+        let shift = (14 - half_exponent) as u32;
+        (mantissa >> shift, mantissa, shift)
+    } else {
+        (
+            ((half_exponent as u32) << 10) | (mantissa >> 13),
+            mantissa,
+            13,
+        )
+    };
+
+    // Round up when past halfway, or exactly halfway and the result is odd
+    // A carry out of the mantissa correctly bumps the exponent
+    let round_bit = 1 << (shift - 1);
+    let round_up = mantissa & round_bit != 0 && mantissa & (3 * round_bit - 1) != 0;
+    sign | (half + round_up as u32) as u16
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    /// Two pixels: pure red, then (0, 128, 255)
+    fn test_image() -> DynamicImage {
+        let mut img = RgbImage::new(2, 1);
+        img.put_pixel(0, 0, Rgb([255, 0, 0]));
+        img.put_pixel(1, 0, Rgb([0, 128, 255]));
+        DynamicImage::ImageRgb8(img)
+    }
+
+    fn spec(layout: TensorLayout) -> ModelSpec {
+        ModelSpec {
+            resize_mode: ResizeMode::Stretch,
+            layout,
+            ..ModelSpec::new(2, 1)
+        }
+    }
+
+    #[test]
+    fn converts_normal_f16_values() {
+        assert_eq!(f32_to_f16_bits(0.0), 0x0000);
+        assert_eq!(f32_to_f16_bits(-0.0), 0x8000);
+        assert_eq!(f32_to_f16_bits(1.0), 0x3C00);
+        assert_eq!(f32_to_f16_bits(-2.0), 0xC000);
+        assert_eq!(f32_to_f16_bits(0.5), 0x3800);
+        assert_eq!(f32_to_f16_bits(65504.0), 0x7BFF);
+        // Smallest normal
+        assert_eq!(f32_to_f16_bits(2.0_f32.powi(-14)), 0x0400);
+    }
+
+    #[test]
+    fn rounds_f16_ties_to_even() {
+        let ulp = 2.0_f32.powi(-10);
+        // Halfway between 0x3C00 and 0x3C01 goes down to the even one
+        assert_eq!(f32_to_f16_bits(1.0 + ulp / 2.0), 0x3C00);
+        // Halfway between 0x3C01 and 0x3C02 goes up to the even one
+        assert_eq!(f32_to_f16_bits(1.0 + 3.0 * ulp / 2.0), 0x3C02);
+        // Anything past halfway rounds up
+        assert_eq!(f32_to_f16_bits(1.0 + ulp / 2.0 + ulp / 1024.0), 0x3C01);
+        assert_eq!(f32_to_f16_bits(1.0 + ulp / 2.0 - ulp / 1024.0), 0x3C00);
+        // Rounding up out of the mantissa bumps the exponent
+        assert_eq!(f32_to_f16_bits(2.0 - ulp / 4.0), 0x4000);
+    }
+
+    #[test]
+    fn converts_f16_subnormals() {
+        let smallest = 2.0_f32.powi(-24);
+        assert_eq!(f32_to_f16_bits(smallest), 0x0001);
+        assert_eq!(f32_to_f16_bits(-smallest), 0x8001);
+        assert_eq!(f32_to_f16_bits(3.0 * smallest), 0x0003);
+        // Largest subnormal
+        assert_eq!(f32_to_f16_bits(1023.0 * smallest), 0x03FF);
+        // Ties to even in the subnormal range, down to zero at half the smallest
+        assert_eq!(f32_to_f16_bits(smallest / 2.0), 0x0000);
+        assert_eq!(f32_to_f16_bits(1.5 * smallest), 0x0002);
+        assert_eq!(f32_to_f16_bits(2.5 * smallest), 0x0002);
+        assert_eq!(f32_to_f16_bits(0.75 * smallest), 0x0001);
+        // Halfway between the largest subnormal and the smallest normal
+        assert_eq!(f32_to_f16_bits(1023.5 * smallest), 0x0400);
+        assert_eq!(f32_to_f16_bits(smallest / 4.0), 0x0000);
+        assert_eq!(f32_to_f16_bits(f32::MIN_POSITIVE), 0x0000);
+    }
+
+    #[test]
+    fn overflows_to_f16_infinity_and_keeps_nan() {
+        // 65520 is halfway between the largest half and the next power of two
+        assert_eq!(f32_to_f16_bits(65520.0), 0x7C00);
+        assert_eq!(f32_to_f16_bits(65519.0), 0x7BFF);
+        assert_eq!(f32_to_f16_bits(1.0e6), 0x7C00);
+        assert_eq!(f32_to_f16_bits(-1.0e6), 0xFC00);
+        assert_eq!(f32_to_f16_bits(f32::INFINITY), 0x7C00);
+        assert_eq!(f32_to_f16_bits(f32::NEG_INFINITY), 0xFC00);
+
+        let nan = f32_to_f16_bits(f32::NAN);
+        assert_eq!(nan & 0x7C00, 0x7C00);
+        assert_ne!(nan & 0x03FF, 0);
+        // A NaN whose payload only sits in the low bits must not turn into infinity
+        let low_payload = f32_to_f16_bits(f32::from_bits(0x7F80_0001));
+        assert_ne!(low_payload & 0x03FF, 0);
+    }
+
+    #[test]
+    fn lays_out_chw_and_hwc() {
+        let img = test_image();
+        let values = [[1.0, 0.0, 0.0], [0.0, 128.0 / 255.0, 1.0]];
+
+        let chw = image_to_tensor(&img, &spec(TensorLayout::Chw)).unwrap();
+        assert_eq!(chw.shape(), vec![1, 3, 1, 2]);
+        let data = chw.f32_data();
+        for (i, pixel) in values.iter().enumerate() {
+            for (c, value) in pixel.iter().enumerate() {
+                assert_eq!(data[c * 2 + i], *value, "pixel {}, channel {}", i, c);
+            }
+        }
+
+        let hwc = image_to_tensor(&img, &spec(TensorLayout::Hwc)).unwrap();
+        assert_eq!(hwc.shape(), vec![1, 1, 2, 3]);
+        assert_eq!(hwc.f32_data(), values.concat());
+    }
+
+    #[test]
+    fn reorders_bgr_with_per_channel_normalization() {
+        let mut spec = ModelSpec {
+            channel_order: ChannelOrder::Bgr,
+            ..spec(TensorLayout::Hwc)
+        }
+        .with_normalization([0.1, 0.2, 0.3], [0.5, 0.25, 0.125]);
+        let tensor = image_to_tensor(&test_image(), &spec).unwrap();
+        let normalize = |value: f32, c: usize| (value / 255.0 - spec.mean[c]) / spec.std[c];
+        // Mean and std are given in RGB order and follow their channel
+        let expected = [
+            normalize(0.0, 2),
+            normalize(0.0, 1),
+            normalize(255.0, 0),
+            normalize(255.0, 2),
+            normalize(128.0, 1),
+            normalize(0.0, 0),
+        ];
+        assert_eq!(tensor.f32_data(), expected);
+        assert_eq!(tensor.into_f32_data(), expected);
+
+        spec.output_type = OutputType::F16;
+        let tensor = image_to_tensor(&test_image(), &spec).unwrap();
+        assert_eq!(tensor.dtype(), "float16");
+        assert!(tensor.f32_data().is_empty());
+        let bits: Vec<u16> = expected.iter().map(|&v| f32_to_f16_bits(v)).collect();
+        assert_eq!(tensor.f16_data(), bits);
+        assert_eq!(tensor.into_f16_data(), bits);
+    }
+
+    #[test]
+    fn rejects_invalid_specs() {
+        let zero_std = spec(TensorLayout::Chw).with_normalization([0.0; 3], [1.0, 0.0, 1.0]);
+        assert!(image_to_tensor(&test_image(), &zero_std).is_err());
+        let mut short_mean = spec(TensorLayout::Chw);
+        short_mean.set_mean(vec![0.5]);
+        assert!(image_to_tensor(&test_image(), &short_mean).is_err());
+    }
+
+    #[test]
+    fn smolvlm_preset_stretches_without_cropping() {
+        let spec = ModelSpec::preset("smolvlm").unwrap();
+        assert_eq!((spec.target_width, spec.target_height), (512, 512));
+        assert_eq!(spec.resize_mode, ResizeMode::Stretch);
+        let tensor = image_to_tensor(&test_image(), &spec).unwrap();
+        let placement = tensor.placement();
+        assert_eq!((placement.offset_x, placement.offset_y), (0.0, 0.0));
+        assert_eq!((placement.scale_x, placement.scale_y), (256.0, 512.0));
+    }
+}
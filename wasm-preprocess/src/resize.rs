@@ -0,0 +1,300 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, GenericImageView, Rgba, RgbaImage};
+use wasm_bindgen::prelude::*;
+
+/// How the decoded image is fitted to the target dimensions
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ResizeMode {
+    /// Scale both axes to the target, distorting the aspect ratio
+    Stretch = 0,
+    /// Crop the largest centered square, then scale it to the target
+    CenterCrop = 1,
+    /// Scale to fit inside the target keeping the aspect ratio, padding the rest with a solid colour
+    Letterbox = 2,
+}
+
+/// Where the source image ended up in the target image
+/// target = source * scale + offset on each axis; the offset is negative for cropped modes
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Placement {
+    pub scale_x: f64,
+    pub scale_y: f64,
+    pub offset_x: f64,
+    pub offset_y: f64,
+    pub source_width: u32,
+    pub source_height: u32,
+}
+
+#[wasm_bindgen]
+impl Placement {
+    /// Map an x coordinate in the target image (e.g. a bounding box edge) back to source pixels
+    /// Coordinates in the padding are clamped to the source edge
+    pub fn to_source_x(&self, x: f64) -> f64 {
+        ((x - self.offset_x) / self.scale_x).clamp(0.0, self.source_width as f64)
+    }
+
+    /// Map a y coordinate in the target image back to source pixels, see to_source_x
+    pub fn to_source_y(&self, y: f64) -> f64 {
+        ((y - self.offset_y) / self.scale_y).clamp(0.0, self.source_height as f64)
+    }
+}
+
+/// RGBA bytes of a letterboxed image and where the source landed in it
+#[wasm_bindgen]
+pub struct LetterboxedImage {
+    data: Vec<u8>,
+    placement: Placement,
+}
+
+#[wasm_bindgen]
+impl LetterboxedImage {
+    /// RGBA bytes, target_width * target_height * 4
+    /// Copies the image on every read, see into_data
+    #[wasm_bindgen(getter)]
+    pub fn data(&self) -> Vec<u8> {
+        self.data.clone()
+    }
+
+    /// Same as data but hands the buffer over without a copy
+    /// Frees the image, so read placement first
+    pub fn into_data(self) -> Vec<u8> {
+        self.data
+    }
+
+    /// Scale and offset of the source image, for mapping bounding boxes back with to_source_x / to_source_y
+    #[wasm_bindgen(getter)]
+    pub fn placement(&self) -> Placement {
+        self.placement
+    }
+}
+
+impl LetterboxedImage {
+    pub fn new(data: Vec<u8>, placement: Placement) -> LetterboxedImage {
+        LetterboxedImage { data, placement }
+    }
+}
+
+/// Work out where a source_width x source_height image lands in the target for a resize mode
+pub fn placement(
+    mode: ResizeMode,
+    source_width: u32,
+    source_height: u32,
+    target_width: u32,
+    target_height: u32,
+) -> Placement {
+    let (sw, sh) = (source_width as f64, source_height as f64);
+    let (tw, th) = (target_width as f64, target_height as f64);
+    let (scale_x, scale_y, offset_x, offset_y) = match mode {
+        ResizeMode::Stretch => (tw / sw, th / sh, 0.0, 0.0),
+        ResizeMode::CenterCrop => {
+            let (crop_size, crop_x, crop_y) = center_crop(source_width, source_height);
+            let (scale_x, scale_y) = (tw / crop_size as f64, th / crop_size as f64);
+            (
+                scale_x,
+                scale_y,
+                -(crop_x as f64) * scale_x,
+                -(crop_y as f64) * scale_y,
+            )
+        }
+        ResizeMode::Letterbox => {
+            let (fit_width, fit_height) =
+                letterbox_size(source_width, source_height, target_width, target_height);
+            (
+                fit_width as f64 / sw,
+                fit_height as f64 / sh,
+                ((target_width - fit_width) / 2) as f64,
+                ((target_height - fit_height) / 2) as f64,
+            )
+        }
+    };
+    Placement {
+        scale_x,
+        scale_y,
+        offset_x,
+        offset_y,
+        source_width,
+        source_height,
+    }
+}
+
+/// Fit an image to target_width x target_height using high-quality Lanczos3 filtering
+/// pad_color (0xRRGGBB) fills the bars left by Letterbox and is ignored otherwise
+pub fn resize_image(
+    img: &DynamicImage,
+    mode: ResizeMode,
+    target_width: u32,
+    target_height: u32,
+    pad_color: u32,
+) -> (DynamicImage, Placement) {
+    let (img_width, img_height) = img.dimensions();
+    let placement = placement(mode, img_width, img_height, target_width, target_height);
+    let resized_img = match mode {
+        ResizeMode::Stretch => img.resize_exact(target_width, target_height, FilterType::Lanczos3),
+        ResizeMode::CenterCrop => {
+            let (crop_size, crop_x, crop_y) = center_crop(img_width, img_height);
+            let cropped_img = img.crop_imm(crop_x, crop_y, crop_size, crop_size);
+            cropped_img.resize_exact(target_width, target_height, FilterType::Lanczos3)
+        }
+        ResizeMode::Letterbox => {
+            let (fit_width, fit_height) =
+                letterbox_size(img_width, img_height, target_width, target_height);
+            let fitted_img = img.resize_exact(fit_width, fit_height, FilterType::Lanczos3);
+
+            let [_, r, g, b] = pad_color.to_be_bytes();
+            let mut canvas =
+                RgbaImage::from_pixel(target_width, target_height, Rgba([r, g, b, 255]));
+            imageops::replace(
+                &mut canvas,
+                &fitted_img.to_rgba8(),
+                placement.offset_x as i64,
+                placement.offset_y as i64,
+            );
+            DynamicImage::ImageRgba8(canvas)
+        }
+    };
+    (resized_img, placement)
+}
+
+/// Side and top-left corner of the largest centered square
+fn center_crop(width: u32, height: u32) -> (u32, u32, u32) {
+    let crop_size = width.min(height);
+    (crop_size, (width - crop_size) / 2, (height - crop_size) / 2)
+}
+
+/// Largest size with the source aspect ratio that fits the target, at least 1x1
+fn letterbox_size(
+    source_width: u32,
+    source_height: u32,
+    target_width: u32,
+    target_height: u32,
+) -> (u32, u32) {
+    let scale = (target_width as f64 / source_width as f64)
+        .min(target_height as f64 / source_height as f64);
+    let fit = |source: u32, target: u32| ((source as f64 * scale).round() as u32).clamp(1, target);
+    (
+        fit(source_width, target_width),
+        fit(source_height, target_height),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    const PAD: u32 = 0x123456;
+    const PAD_PIXEL: Rgba<u8> = Rgba([0x12, 0x34, 0x56, 255]);
+    const FILL_PIXEL: Rgba<u8> = Rgba([0, 200, 0, 255]);
+
+    fn solid_image(width: u32, height: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(width, height, Rgb([0, 200, 0])))
+    }
+
+    fn assert_close(actual: f64, expected: f64) {
+        assert!(
+            (actual - expected).abs() < 1e-9,
+            "{} != {}",
+            actual,
+            expected
+        );
+    }
+
+    /// Letterbox a solid source into a 64x64 target and check every pixel is either
+    /// the source colour inside the fitted rectangle or the pad colour outside it
+    fn check_letterbox(source_width: u32, source_height: u32, fit: (u32, u32, u32, u32)) {
+        let (fit_x, fit_y, fit_width, fit_height) = fit;
+        let img = solid_image(source_width, source_height);
+        let (resized, placement) = resize_image(&img, ResizeMode::Letterbox, 64, 64, PAD);
+        let resized = resized.to_rgba8();
+        assert_eq!(resized.dimensions(), (64, 64));
+        for (x, y, pixel) in resized.enumerate_pixels() {
+            let inside =
+                (fit_x..fit_x + fit_width).contains(&x) && (fit_y..fit_y + fit_height).contains(&y);
+            let expected = if inside { FILL_PIXEL } else { PAD_PIXEL };
+            assert_eq!(*pixel, expected, "pixel ({}, {})", x, y);
+        }
+
+        // The fitted rectangle's corners map back to the source corners
+        assert_close(placement.offset_x, fit_x as f64);
+        assert_close(placement.offset_y, fit_y as f64);
+        assert_close(placement.to_source_x(fit_x as f64), 0.0);
+        assert_close(placement.to_source_y(fit_y as f64), 0.0);
+        assert_close(
+            placement.to_source_x((fit_x + fit_width) as f64),
+            source_width as f64,
+        );
+        assert_close(
+            placement.to_source_y((fit_y + fit_height) as f64),
+            source_height as f64,
+        );
+        // Coordinates in the bars clamp to the source edges
+        assert_close(placement.to_source_x(0.0), 0.0);
+        assert_close(placement.to_source_y(0.0), 0.0);
+        assert_close(placement.to_source_x(64.0), source_width as f64);
+        assert_close(placement.to_source_y(64.0), source_height as f64);
+    }
+
+    #[test]
+    fn letterboxes_wide_images_with_bars_above_and_below() {
+        check_letterbox(200, 100, (0, 16, 64, 32));
+    }
+
+    #[test]
+    fn letterboxes_tall_images_with_bars_left_and_right() {
+        check_letterbox(100, 200, (16, 0, 32, 64));
+    }
+
+    #[test]
+    fn maps_boxes_back_through_the_letterbox() {
+        let placement = placement(ResizeMode::Letterbox, 200, 100, 64, 64);
+        assert_close(placement.scale_x, 0.32);
+        assert_close(placement.scale_y, 0.32);
+        // A box around the middle of the fitted image
+        assert_close(placement.to_source_x(16.0), 50.0);
+        assert_close(placement.to_source_x(48.0), 150.0);
+        assert_close(placement.to_source_y(24.0), 25.0);
+        assert_close(placement.to_source_y(40.0), 75.0);
+    }
+
+    #[test]
+    fn centres_letterboxes_on_whole_pixels() {
+        // 65 * 100 / 200 = 32.5 rounds to 33, leaving 32 rows of bars
+        let placement = placement(ResizeMode::Letterbox, 200, 100, 65, 65);
+        assert_eq!((placement.offset_x, placement.offset_y), (0.0, 16.0));
+        assert_close(placement.scale_y, 33.0 / 100.0);
+    }
+
+    #[test]
+    fn hands_over_the_letterboxed_pixels() {
+        let placement = placement(ResizeMode::Letterbox, 2, 1, 2, 2);
+        let image = LetterboxedImage::new(vec![7; 16], placement);
+        assert_eq!(image.placement(), placement);
+        assert_eq!(image.data(), vec![7; 16]);
+        assert_eq!(image.into_data(), vec![7; 16]);
+    }
+
+    #[test]
+    fn keeps_letterboxed_images_at_least_one_pixel() {
+        assert_eq!(letterbox_size(1000, 1, 10, 10), (10, 1));
+        assert_eq!(letterbox_size(1, 1000, 10, 10), (1, 10));
+        assert_eq!(letterbox_size(10, 10, 10, 10), (10, 10));
+    }
+
+    #[test]
+    fn places_stretched_and_cropped_images() {
+        let stretch = placement(ResizeMode::Stretch, 200, 100, 50, 50);
+        assert_close(stretch.scale_x, 0.25);
+        assert_close(stretch.scale_y, 0.5);
+        assert_eq!((stretch.offset_x, stretch.offset_y), (0.0, 0.0));
+
+        // The centred 100x100 crop starts 50 pixels in
+        let crop = placement(ResizeMode::CenterCrop, 200, 100, 50, 50);
+        assert_close(crop.scale_x, 0.5);
+        assert_close(crop.offset_x, -25.0);
+        assert_close(crop.to_source_x(0.0), 50.0);
+        assert_close(crop.to_source_x(50.0), 150.0);
+        assert_close(crop.to_source_y(50.0), 100.0);
+    }
+}